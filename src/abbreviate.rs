@@ -0,0 +1,188 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide shortening of paths for display in status bars,
+//! prompts and other places where space is limited.
+//!
+//! Widths are measured in terminal columns of whole graphemes so wide
+//! (e.g. CJK) characters count as two and combining sequences are never
+//! split.
+
+use std::path::{is_separator, MAIN_SEPARATOR_STR};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "…";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Replace components in the middle of the path with "…" e.g.
+    /// "/home/…/project/src/lib.rs".
+    MiddleEllipsis,
+    /// Shorten directory names (from the left) to their first character
+    /// in the style of the fish shell's prompt e.g. "~/S/G/p/src".  The
+    /// home directory isn't replaced by "~" so use `contract_home()` on
+    /// the path first if that is wanted.
+    FishShell,
+}
+
+/// Return the display width of `text`.
+pub fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(UnicodeWidthStr::width).sum()
+}
+
+// The longest tail of `text` (preceded by "…") that fits in `max_width`.
+fn truncate_left(text: &str, max_width: usize) -> String {
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut width = display_width(ELLIPSIS);
+    let mut tail: Vec<&str> = Vec::new();
+    for grapheme in text.graphemes(true).rev() {
+        width += grapheme.width();
+        if width > max_width {
+            break;
+        }
+        tail.push(grapheme);
+    }
+    tail.push(ELLIPSIS);
+    tail.iter().rev().cloned().collect()
+}
+
+fn middle_ellipsis(parts: &[&str], max_width: usize) -> String {
+    let sep = MAIN_SEPARATOR_STR;
+    // keep the root and the first directory if possible
+    let head_len = if parts.len() > 1 && parts[0].is_empty() {
+        2
+    } else {
+        1
+    };
+    if parts.len() > head_len + 1 {
+        let head = parts[..head_len].join(sep);
+        for start in head_len + 1..parts.len() {
+            let candidate = [&head, ELLIPSIS, &parts[start..].join(sep)].join(sep);
+            if display_width(&candidate) <= max_width {
+                return candidate;
+            }
+        }
+    }
+    let last = parts[parts.len() - 1];
+    if parts.len() > 1 {
+        let candidate = [ELLIPSIS, last].join(sep);
+        if display_width(&candidate) <= max_width {
+            return candidate;
+        }
+    }
+    truncate_left(last, max_width)
+}
+
+fn first_grapheme(name: &str) -> String {
+    let mut graphemes = name.graphemes(true);
+    match graphemes.next() {
+        // keep hidden directories recognisable
+        Some(".") => graphemes
+            .next()
+            .map_or(".".to_string(), |g| format!(".{}", g)),
+        Some(grapheme) => grapheme.to_string(),
+        None => String::new(),
+    }
+}
+
+fn fish_shell(parts: &[&str], max_width: usize) -> String {
+    let mut parts: Vec<String> = parts.iter().map(|part| part.to_string()).collect();
+    let sep = MAIN_SEPARATOR_STR;
+    for index in 0..parts.len() - 1 {
+        if display_width(&parts.join(sep)) <= max_width {
+            break;
+        }
+        if parts[index] != "~" {
+            parts[index] = first_grapheme(&parts[index]);
+        }
+    }
+    let abbreviated = parts.join(sep);
+    if display_width(&abbreviated) <= max_width {
+        abbreviated
+    } else {
+        let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+        middle_ellipsis(&parts, max_width)
+    }
+}
+
+/// Shorten `path` (if necessary) so that it is no wider than `max_width`
+/// columns.  If `path` can't be shortened enough in the requested `style`
+/// the end of its last component is used.
+pub fn abbreviate_path(path: &str, max_width: usize, style: Style) -> String {
+    if display_width(path) <= max_width {
+        return path.to_string();
+    }
+    let parts: Vec<&str> = path.split(is_separator).collect();
+    match style {
+        Style::MiddleEllipsis => middle_ellipsis(&parts, max_width),
+        Style::FishShell => fish_shell(&parts, max_width),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn native(path: &str) -> String {
+        path.replace('/', MAIN_SEPARATOR_STR)
+    }
+
+    #[test]
+    fn middle_ellipsis_works() {
+        let path = native("/home/user/work/project/src/lib.rs");
+        let abbreviate = |width| abbreviate_path(&path, width, Style::MiddleEllipsis);
+        assert_eq!(abbreviate(100), path);
+        assert_eq!(abbreviate(26), native("/home/…/project/src/lib.rs"));
+        assert_eq!(abbreviate(16), native("/home/…/lib.rs"));
+        assert_eq!(abbreviate(8), native("…/lib.rs"));
+        assert_eq!(abbreviate(4), "….rs");
+        assert_eq!(abbreviate(0), "");
+        assert_eq!(
+            abbreviate_path(&native("a/b/c/d"), 6, Style::MiddleEllipsis),
+            native("a/…/d")
+        );
+    }
+
+    #[test]
+    fn fish_shell_works() {
+        let path = native("~/Source/GitHub/project/src");
+        let abbreviate = |width| abbreviate_path(&path, width, Style::FishShell);
+        assert_eq!(abbreviate(100), path);
+        assert_eq!(abbreviate(22), native("~/S/GitHub/project/src"));
+        assert_eq!(abbreviate(11), native("~/S/G/p/src"));
+        assert_eq!(abbreviate(7), native("~/…/src"));
+        let path = native("/home/.config/app");
+        assert_eq!(
+            abbreviate_path(&path, 9, Style::FishShell),
+            native("/h/.c/app")
+        );
+        assert_eq!(
+            abbreviate_path(&path, 8, Style::FishShell),
+            native("/h/…/app")
+        );
+    }
+
+    #[test]
+    fn display_width_works() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(display_width("日本"), 4);
+        let abbreviated = abbreviate_path(&native("/x/日本語/ファイル"), 9, Style::MiddleEllipsis);
+        assert_eq!(abbreviated, "…ファイル");
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide preservation of access control lists (POSIX ACLs
+//! on Linux and DACLs on Windows) when copying files.
+
+use std::io;
+use std::path::Path;
+
+/// The access control list of a file (in the platform's native form).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Acl(imp::Acl);
+
+/// Read the access control list of the file `path`.
+pub fn read_acl<P: AsRef<Path>>(path: &P) -> io::Result<Acl> {
+    imp::read_acl(path.as_ref()).map(Acl)
+}
+
+/// Give the file `path` the access control list `acl`.  An error of kind
+/// `Unsupported` is returned if the platform or file system can't hold it.
+pub fn apply_acl<P: AsRef<Path>>(path: &P, acl: &Acl) -> io::Result<()> {
+    imp::apply_acl(path.as_ref(), &acl.0)
+}
+
+/// Give the file `to` the access control list of the file `from`.
+pub fn copy_acl<F, T>(from: &F, to: &T) -> io::Result<()>
+where
+    F: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    apply_acl(to, &read_acl(from)?)
+}
+
+fn unsupported(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{}: access control lists are not supported", path.display()),
+    )
+}
+
+// POSIX ACLs are kept in "system.posix_acl_*" extended attributes.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    use libc;
+
+    use xattr::{get_xattr, remove_xattr, set_xattr};
+
+    const NAMES: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Acl {
+        values: [Option<Vec<u8>>; 2],
+    }
+
+    fn is_unsupported(err: &io::Error) -> bool {
+        err.raw_os_error() == Some(libc::EOPNOTSUPP)
+    }
+
+    pub fn read_acl(path: &Path) -> io::Result<Acl> {
+        let mut values = [None, None];
+        for (value, name) in values.iter_mut().zip(NAMES.iter()) {
+            *value = match get_xattr(&path, name) {
+                Ok(value) => value,
+                // a file system without ACLs only has the mode bits
+                Err(ref err) if is_unsupported(err) => None,
+                Err(err) => return Err(err),
+            };
+        }
+        Ok(Acl { values })
+    }
+
+    pub fn apply_acl(path: &Path, acl: &Acl) -> io::Result<()> {
+        for (value, name) in acl.values.iter().zip(NAMES.iter()) {
+            let result = match value {
+                Some(value) => set_xattr(&path, name, value),
+                None => match remove_xattr(&path, name) {
+                    Err(ref err)
+                        if is_unsupported(err) || err.raw_os_error() == Some(libc::ENODATA) =>
+                    {
+                        Ok(())
+                    }
+                    result => result,
+                },
+            };
+            match result {
+                Err(ref err) if is_unsupported(err) => return Err(super::unsupported(path)),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::path::Path;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{BOOL, DWORD};
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::accctrl::SE_FILE_OBJECT;
+    use winapi::um::aclapi::{GetNamedSecurityInfoW, SetNamedSecurityInfoW};
+    use winapi::um::securitybaseapi::{
+        GetSecurityDescriptorControl, GetSecurityDescriptorDacl, GetSecurityDescriptorLength,
+    };
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::winnt::{
+        DACL_SECURITY_INFORMATION, PACL, PROTECTED_DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR,
+        SECURITY_DESCRIPTOR_CONTROL, SE_DACL_PROTECTED, UNPROTECTED_DACL_SECURITY_INFORMATION,
+    };
+
+    use sys::wide_path;
+
+    // A self relative security descriptor (in a suitably aligned buffer).
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Acl {
+        descriptor: Vec<u64>,
+    }
+
+    fn check(path: &Path, result: DWORD) -> io::Result<()> {
+        if result == ERROR_SUCCESS {
+            Ok(())
+        } else {
+            let err = io::Error::from_raw_os_error(result as i32);
+            if err.kind() == io::ErrorKind::Unsupported {
+                Err(super::unsupported(path))
+            } else {
+                Err(err)
+            }
+        }
+    }
+
+    pub fn read_acl(path: &Path) -> io::Result<Acl> {
+        let wide = wide_path(path);
+        let mut dacl: PACL = ptr::null_mut();
+        let mut sd: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        check(path, unsafe {
+            GetNamedSecurityInfoW(
+                wide.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut dacl,
+                ptr::null_mut(),
+                &mut sd,
+            )
+        })?;
+        let len = unsafe { GetSecurityDescriptorLength(sd) } as usize;
+        let mut descriptor = vec![0u64; len.div_ceil(8)];
+        unsafe {
+            ptr::copy_nonoverlapping(sd as *const u8, descriptor.as_mut_ptr() as *mut u8, len);
+            LocalFree(sd);
+        }
+        Ok(Acl { descriptor })
+    }
+
+    pub fn apply_acl(path: &Path, acl: &Acl) -> io::Result<()> {
+        let sd = acl.descriptor.as_ptr() as PSECURITY_DESCRIPTOR;
+        let mut present: BOOL = 0;
+        let mut defaulted: BOOL = 0;
+        let mut dacl: PACL = ptr::null_mut();
+        let mut control: SECURITY_DESCRIPTOR_CONTROL = 0;
+        let mut revision: DWORD = 0;
+        unsafe {
+            if GetSecurityDescriptorDacl(sd, &mut present, &mut dacl, &mut defaulted) == 0
+                || GetSecurityDescriptorControl(sd, &mut control, &mut revision) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let info = if control & SE_DACL_PROTECTED != 0 {
+            DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION
+        } else {
+            DACL_SECURITY_INFORMATION | UNPROTECTED_DACL_SECURITY_INFORMATION
+        };
+        let mut wide = wide_path(path);
+        check(path, unsafe {
+            SetNamedSecurityInfoW(
+                wide.as_mut_ptr(),
+                SE_FILE_OBJECT,
+                info,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                dacl,
+                ptr::null_mut(),
+            )
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Acl;
+
+    pub fn read_acl(path: &Path) -> io::Result<Acl> {
+        Err(super::unsupported(path))
+    }
+
+    pub fn apply_acl(path: &Path, _acl: &Acl) -> io::Result<()> {
+        Err(super::unsupported(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn copy_acl_works() {
+        use xattr::{get_xattr, set_xattr};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let from = temp_dir.path().join("from");
+        let to = temp_dir.path().join("to");
+        fs::write(&from, "from").unwrap();
+        fs::write(&to, "to").unwrap();
+        // user::rw- user:12345:r-- group::r-- mask::r-- other::r--
+        let mut entries = vec![2u8, 0, 0, 0];
+        for &(tag, perm, id) in &[
+            (1u16, 6u16, u32::MAX),
+            (2, 4, 12345),
+            (4, 4, u32::MAX),
+            (0x10, 4, u32::MAX),
+            (0x20, 4, u32::MAX),
+        ] {
+            entries.extend_from_slice(&tag.to_le_bytes());
+            entries.extend_from_slice(&perm.to_le_bytes());
+            entries.extend_from_slice(&id.to_le_bytes());
+        }
+        if set_xattr(&from, &"system.posix_acl_access", &entries).is_err() {
+            // the file system doesn't do ACLs
+            return;
+        }
+        copy_acl(&from, &to).unwrap();
+        assert_eq!(read_acl(&from).unwrap(), read_acl(&to).unwrap());
+        assert!(get_xattr(&to, &"system.posix_acl_access")
+            .unwrap()
+            .is_some());
+
+        let plain = temp_dir.path().join("plain");
+        fs::write(&plain, "plain").unwrap();
+        copy_acl(&plain, &to).unwrap();
+        assert_eq!(get_xattr(&to, &"system.posix_acl_access").unwrap(), None);
+        assert!(read_acl(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn copy_acl_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let from = temp_dir.path().join("from");
+        let to = temp_dir.path().join("to");
+        fs::write(&from, "from").unwrap();
+        fs::write(&to, "to").unwrap();
+        copy_acl(&from, &to).unwrap();
+        assert_eq!(read_acl(&from).unwrap(), read_acl(&to).unwrap());
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    #[test]
+    fn acls_unsupported() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "contents").unwrap();
+        assert_eq!(
+            copy_acl(&file, &file).unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide the standard (XDG on Linux) per application
+//! configuration, data, cache and state directories and the search for
+//! configuration files in them.
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[cfg(not(unix))]
+use dirs;
+
+use super::context::Context;
+pub use super::exe::{exe_dir, exe_relative};
+use super::path_to_string;
+
+// Join `app` to `base` creating the result if it doesn't exist.
+fn app_dir(base: Option<PathBuf>, app: &str) -> io::Result<String> {
+    let base = base.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "standard directory not available on this platform",
+        )
+    })?;
+    let dir = base.join(app);
+    fs::create_dir_all(&dir)?;
+    Ok(path_to_string(&dir))
+}
+
+/// The directory for `app`'s configuration files (e.g.
+/// "~/.config/<app>" on Linux) which is created if necessary.
+pub fn app_config_dir(app: &str) -> io::Result<String> {
+    app_dir(config_dir(), app)
+}
+
+/// The directory for `app`'s data files (e.g. "~/.local/share/<app>" on
+/// Linux) which is created if necessary.
+pub fn app_data_dir(app: &str) -> io::Result<String> {
+    app_dir(data_dir(), app)
+}
+
+/// The directory for `app`'s cached files (e.g. "~/.cache/<app>" on Linux)
+/// which is created if necessary.
+pub fn app_cache_dir(app: &str) -> io::Result<String> {
+    app_dir(cache_dir(), app)
+}
+
+/// The directory for `app`'s state (history, logs, etc.) which persists
+/// between runs but isn't important enough to be kept with its data (e.g.
+/// "~/.local/state/<app>" on Linux) which is created if necessary.  On
+/// other platforms this is in the local data directory.
+pub fn app_state_dir(app: &str) -> io::Result<String> {
+    app_dir(state_dir(), app)
+}
+
+// The base directories are found relative to `Context::home_dir()` (so
+// that they follow its overrides) except on platforms where they aren't
+// in the home directory.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn xdg_dir(var: &str, default: &str) -> Option<PathBuf> {
+    match env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => Context::home_dir().map(|home_dir| home_dir.join(default)),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+#[cfg(target_os = "macos")]
+fn config_dir() -> Option<PathBuf> {
+    Context::home_dir().map(|home_dir| home_dir.join("Library/Application Support"))
+}
+
+#[cfg(target_os = "macos")]
+fn data_dir() -> Option<PathBuf> {
+    config_dir()
+}
+
+#[cfg(target_os = "macos")]
+fn cache_dir() -> Option<PathBuf> {
+    Context::home_dir().map(|home_dir| home_dir.join("Library/Caches"))
+}
+
+#[cfg(target_os = "macos")]
+fn state_dir() -> Option<PathBuf> {
+    config_dir()
+}
+
+#[cfg(not(unix))]
+fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir()
+}
+
+#[cfg(not(unix))]
+fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir()
+}
+
+#[cfg(not(unix))]
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir()
+}
+
+#[cfg(not(unix))]
+fn state_dir() -> Option<PathBuf> {
+    dirs::data_local_dir()
+}
+
+/// Return the existing configuration files named `file_name` for `app`
+/// in precedence order (highest first): the current directory and its
+/// ancestors (nearest first), `app`'s configuration directory, "/etc/<app>"
+/// (on unix) and the directory containing the executable.
+pub fn find_config_files(app: &str, file_name: &str) -> Vec<String> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if let Ok(current_dir) = Context::current_dir() {
+        candidates.extend(current_dir.ancestors().map(|dir| dir.join(file_name)));
+    }
+    if let Some(config_dir) = config_dir() {
+        candidates.push(config_dir.join(app).join(file_name));
+    }
+    if cfg!(unix) {
+        candidates.push(PathBuf::from("/etc").join(app).join(file_name));
+    }
+    if let Ok(exe_dir) = exe_dir() {
+        candidates.push(PathBuf::from(exe_dir).join(file_name));
+    }
+    let mut found: Vec<String> = Vec::new();
+    for candidate in candidates {
+        match candidate.to_str() {
+            // the same file may be reached more than one way
+            Some(text) if candidate.is_file() && !found.iter().any(|f| f == text) => {
+                found.push(text.to_string())
+            }
+            _ => (),
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn app_dir_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base = temp_dir.path().join("config");
+        let dir = app_dir(Some(base.clone()), "app").unwrap();
+        assert_eq!(PathBuf::from(&dir), base.join("app"));
+        assert!(base.join("app").is_dir());
+        assert_eq!(app_dir(Some(base), "app").unwrap(), dir);
+        assert!(app_dir(None, "app").is_err());
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn xdg_dir_uses_context_home() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let home = temp_dir.path();
+        let dir = Context::with_home(&home, || xdg_dir("PW_PATHUX_UNSET_DIR", ".config"));
+        assert_eq!(dir, Some(home.join(".config")));
+        if env::var_os("XDG_CACHE_HOME").is_none() {
+            let dir = Context::with_home(&home, || app_cache_dir("app")).unwrap();
+            assert_eq!(PathBuf::from(dir), home.join(".cache/app"));
+        }
+    }
+
+    #[test]
+    fn find_config_files_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outer = temp_dir.path().join("outer");
+        let inner = outer.join("inner");
+        fs::create_dir_all(&inner).unwrap();
+        let file_name = "pw_pathux_test.conf";
+        fs::write(outer.join(file_name), "").unwrap();
+        fs::write(inner.join(file_name), "").unwrap();
+        let found = Context::with_cwd(&inner, || find_config_files("pw_pathux_test", file_name));
+        assert_eq!(
+            found,
+            vec![
+                path_to_string(&inner.join(file_name)),
+                path_to_string(&outer.join(file_name)),
+            ]
+        );
+        let found = Context::with_cwd(&inner, || find_config_files("pw_pathux_test", "missing"));
+        assert!(found.is_empty());
+    }
+}
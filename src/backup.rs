@@ -0,0 +1,210 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide management of numbered (Emacs/GNU style
+//! "file.~N~") backup series.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::lock::PathLock;
+
+/// The numbered backups of a file.  Numbers only ever increase (gaps left
+/// by removed backups are not reused) so that the highest number is always
+/// the newest backup.
+#[derive(Debug, Clone)]
+pub struct BackupSeries {
+    path: PathBuf,
+    limit: usize,
+}
+
+impl BackupSeries {
+    /// The backup series for `path` keeping (at most) `limit` backups when
+    /// pruned (zero means no limit).
+    pub fn new<P: AsRef<Path>>(path: &P, limit: usize) -> Self {
+        BackupSeries {
+            path: path.as_ref().to_path_buf(),
+            limit,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn dir_path(&self) -> &Path {
+        match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    }
+
+    fn backup_name(&self, n: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".~{}~", n));
+        PathBuf::from(name)
+    }
+
+    // The file used to serialize changes to the series by separate writers.
+    fn lock_path(&self) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(".~lock~");
+        PathBuf::from(name)
+    }
+
+    /// The existing backups (and their numbers) oldest first.
+    pub fn existing(&self) -> io::Result<Vec<(usize, PathBuf)>> {
+        let file_name = match self.path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None => return Ok(Vec::new()),
+        };
+        let prefix = format!("{}.~", file_name);
+        let mut backups = Vec::new();
+        let read_dir = match fs::read_dir(self.dir_path()) {
+            Ok(read_dir) => read_dir,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(backups),
+            Err(err) => return Err(err),
+        };
+        for entry in read_dir {
+            let name = entry?.file_name();
+            let number = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|rest| rest.strip_suffix('~'))
+                .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|digits| digits.parse::<usize>().ok());
+            if let Some(n) = number.filter(|n| *n > 0) {
+                backups.push((n, self.backup_name(n)));
+            }
+        }
+        backups.sort();
+        Ok(backups)
+    }
+
+    /// The name that the next backup should be given.
+    pub fn next_name(&self) -> io::Result<PathBuf> {
+        let last = self.existing()?.last().map(|(n, _)| *n).unwrap_or(0);
+        Ok(self.backup_name(last + 1))
+    }
+
+    /// Remove the oldest backups so that no more than the limit remain and
+    /// return the paths of those removed.
+    pub fn prune(&self) -> io::Result<Vec<PathBuf>> {
+        let _lock = PathLock::exclusive(&self.lock_path())?;
+        self.prune_locked()
+    }
+
+    fn prune_locked(&self) -> io::Result<Vec<PathBuf>> {
+        let existing = self.existing()?;
+        if self.limit == 0 || existing.len() <= self.limit {
+            return Ok(Vec::new());
+        }
+        let excess = existing.len() - self.limit;
+        let mut removed = Vec::new();
+        for (_, path) in existing.into_iter().take(excess) {
+            match fs::remove_file(&path) {
+                // another writer got there first
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+                result => result?,
+            }
+            removed.push(path);
+        }
+        Ok(removed)
+    }
+
+    /// Copy the file to a new backup (if it exists), prune the series and
+    /// return the new backup's path.  Concurrent writers are serialized
+    /// with a lock on "file.~lock~".
+    pub fn backup(&self) -> io::Result<Option<PathBuf>> {
+        let _lock = PathLock::exclusive(&self.lock_path())?;
+        let mut source = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut backup_path = self.next_name()?;
+        let mut target = loop {
+            // in case a writer that doesn't use the lock made one
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&backup_path)
+            {
+                Ok(file) => break file,
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    backup_path = self.next_name()?;
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        io::copy(&mut source, &mut target)
+            .and_then(|_| fs::set_permissions(&backup_path, source.metadata()?.permissions()))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&backup_path);
+            })?;
+        self.prune_locked()?;
+        Ok(Some(backup_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn backup_series_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        let series = BackupSeries::new(&file, 2);
+        assert!(series.existing().unwrap().is_empty());
+        assert_eq!(
+            series.next_name().unwrap(),
+            temp_dir.path().join("file.~1~")
+        );
+        assert_eq!(series.backup().unwrap(), None);
+
+        for text in &["one", "two", "three"] {
+            fs::write(&file, text).unwrap();
+            series.backup().unwrap().unwrap();
+        }
+        let existing = series.existing().unwrap();
+        assert_eq!(
+            existing,
+            vec![
+                (2, temp_dir.path().join("file.~2~")),
+                (3, temp_dir.path().join("file.~3~"))
+            ]
+        );
+        assert_eq!(fs::read_to_string(&existing[1].1).unwrap(), "three");
+
+        // gaps aren't filled and strays are ignored
+        fs::write(temp_dir.path().join("file.~7~"), "").unwrap();
+        fs::write(temp_dir.path().join("file.~x~"), "").unwrap();
+        fs::write(temp_dir.path().join("file.~0~"), "").unwrap();
+        assert_eq!(
+            series.next_name().unwrap(),
+            temp_dir.path().join("file.~8~")
+        );
+        assert_eq!(
+            series.prune().unwrap(),
+            vec![temp_dir.path().join("file.~2~")]
+        );
+        assert!(series.prune().unwrap().is_empty());
+        assert!(BackupSeries::new(&file, 0).prune().unwrap().is_empty());
+    }
+}
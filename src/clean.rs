@@ -0,0 +1,121 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide pruning of old files from cache and temporary
+//! directories.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::glob::glob_match;
+use super::times::Timestamps;
+use super::trash::trash;
+use super::walk::Walker;
+
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// Only consider files whose names match one of these shell wild card
+    /// patterns (all files if empty).
+    pub include: Vec<String>,
+    /// Never remove files whose names match one of these patterns.
+    pub exclude: Vec<String>,
+    /// Move the files to the trash rather than deleting them.
+    pub trash: bool,
+    /// Report the files that would be removed without removing them.
+    pub dry_run: bool,
+}
+
+impl CleanOptions {
+    fn selects(&self, file_name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, file_name)))
+            && !self.exclude.iter().any(|p| glob_match(p, file_name))
+    }
+}
+
+/// Remove the files below `dir_path` that were last modified more than
+/// `age` ago and return their paths.  Directories (even if left empty) and
+/// symbolic links are left alone.
+pub fn clean_older_than<P: AsRef<Path>>(
+    dir_path: &P,
+    age: Duration,
+    options: &CleanOptions,
+) -> io::Result<Vec<PathBuf>> {
+    let mut stale = Vec::new();
+    for entry in Walker::new(dir_path) {
+        let entry = entry?;
+        if entry.is_file()
+            && options.selects(&entry.file_name())
+            && Timestamps::from_metadata(&entry.metadata()?)?.age() > age
+        {
+            stale.push(entry.path().to_path_buf());
+        }
+    }
+    if !options.dry_run {
+        for path in stale.iter() {
+            if options.trash {
+                trash(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(stale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::time::SystemTime;
+
+    use times::set_times;
+
+    #[test]
+    fn clean_older_than_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        let then = SystemTime::now() - Duration::from_secs(10 * 24 * 3600);
+        for name in &["old.tmp", "old.keep", "sub/old.tmp"] {
+            fs::write(root.join(name), "").unwrap();
+            set_times(&root.join(name), then, then).unwrap();
+        }
+        fs::write(root.join("new.tmp"), "").unwrap();
+        let week = Duration::from_secs(7 * 24 * 3600);
+
+        let options = CleanOptions {
+            exclude: vec!["*.keep".to_string()],
+            dry_run: true,
+            ..CleanOptions::default()
+        };
+        let mut stale = clean_older_than(&root, week, &options).unwrap();
+        stale.sort();
+        assert_eq!(stale, vec![root.join("old.tmp"), root.join("sub/old.tmp")]);
+        assert!(root.join("old.tmp").exists());
+
+        let options = CleanOptions {
+            include: vec!["*.tmp".to_string(), "*.keep".to_string()],
+            ..CleanOptions::default()
+        };
+        assert_eq!(clean_older_than(&root, week, &options).unwrap().len(), 3);
+        assert!(!root.join("old.keep").exists());
+        assert!(!root.join("sub/old.tmp").exists());
+        assert!(root.join("new.tmp").exists());
+        assert!(root.join("sub").is_dir());
+    }
+}
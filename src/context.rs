@@ -0,0 +1,278 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide look ups of the home (memoized) and current
+//! directories (as used for tilde expansion and relative paths) which can
+//! be overridden on a per thread basis e.g. for testing, and of explicit
+//! `PathCtx` contexts and `ExpandOptions` for resolving paths without using
+//! either.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::LocalKey;
+
+#[cfg(all(feature = "home", not(target_arch = "wasm32")))]
+use dirs;
+
+struct Cache {
+    home_dir: Option<Option<PathBuf>>,
+}
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache { home_dir: None });
+
+thread_local! {
+    static HOME_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    static CURRENT_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+type Override = &'static LocalKey<RefCell<Option<PathBuf>>>;
+
+// Reinstates the previous override when dropped (even during a panic).
+struct Restore {
+    key: Override,
+    previous: Option<PathBuf>,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        self.key.with(|cell| *cell.borrow_mut() = previous);
+    }
+}
+
+fn with_override<R, F: FnOnce() -> R>(key: Override, path: &Path, f: F) -> R {
+    let previous = key.with(|cell| cell.borrow_mut().replace(path.to_path_buf()));
+    let _restore = Restore { key, previous };
+    f()
+}
+
+#[cfg(all(feature = "home", not(target_arch = "wasm32")))]
+fn find_home_dir() -> Option<PathBuf> {
+    dirs::home_dir()
+}
+
+// WASI runtimes pass the user's home directory (if any) in the environment.
+#[cfg(all(feature = "home", target_os = "wasi"))]
+fn find_home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+// Without the "home" feature (or an operating system) there is no home
+// directory (unless one is supplied with `Context::with_home()`).
+#[cfg(not(any(
+    all(feature = "home", not(target_arch = "wasm32")),
+    all(feature = "home", target_os = "wasi")
+)))]
+fn find_home_dir() -> Option<PathBuf> {
+    None
+}
+
+/// The source of the home and current directories used throughout this
+/// crate.  The home directory is looked up once and then remembered (until
+/// `Context::invalidate()` is called) but the current directory is asked
+/// of the operating system every time.
+pub struct Context;
+
+impl Context {
+    pub fn home_dir() -> Option<PathBuf> {
+        if let Some(home_dir) = HOME_DIR_OVERRIDE.with(|cell| cell.borrow().clone()) {
+            return Some(home_dir);
+        }
+        let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+        cache.home_dir.get_or_insert_with(find_home_dir).clone()
+    }
+
+    pub fn current_dir() -> io::Result<PathBuf> {
+        if let Some(current_dir) = CURRENT_DIR_OVERRIDE.with(|cell| cell.borrow().clone()) {
+            return Ok(current_dir);
+        }
+        env::current_dir()
+    }
+
+    /// Change the process's current directory (the same as
+    /// `std::env::set_current_dir()`).
+    pub fn set_current_dir<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+        env::set_current_dir(path)
+    }
+
+    /// Forget the remembered home directory so that it is looked up afresh.
+    pub fn invalidate() {
+        let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+        cache.home_dir = None;
+    }
+
+    /// Run `f` with `home_dir()` returning `home` in this thread.
+    pub fn with_home<P: AsRef<Path>, R, F: FnOnce() -> R>(home: &P, f: F) -> R {
+        with_override(&HOME_DIR_OVERRIDE, home.as_ref(), f)
+    }
+
+    /// Run `f` with `current_dir()` returning `cwd` in this thread.
+    pub fn with_cwd<P: AsRef<Path>, R, F: FnOnce() -> R>(cwd: &P, f: F) -> R {
+        with_override(&CURRENT_DIR_OVERRIDE, cwd.as_ref(), f)
+    }
+}
+
+/// An explicit working directory and home directory against which paths
+/// can be resolved (by the `_in_ctx` functions and macros) without
+/// changing (or depending on) the process wide values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathCtx {
+    pub cwd: PathBuf,
+    pub home: Option<PathBuf>,
+}
+
+impl PathCtx {
+    pub fn new<C: AsRef<Path>>(cwd: &C, home: Option<&Path>) -> PathCtx {
+        PathCtx {
+            cwd: cwd.as_ref().to_path_buf(),
+            home: home.map(|home| home.to_path_buf()),
+        }
+    }
+
+    /// A context with the values currently supplied by `Context`.
+    pub fn current() -> io::Result<PathCtx> {
+        Ok(PathCtx {
+            cwd: Context::current_dir()?,
+            home: Context::home_dir(),
+        })
+    }
+}
+
+// The environment variable that names the home directory.
+#[cfg(windows)]
+const HOME_VAR: &str = "USERPROFILE";
+#[cfg(not(windows))]
+const HOME_VAR: &str = "HOME";
+
+/// Options for expanding and resolving paths (by the `_with` functions and
+/// macros) e.g. on behalf of another user.  Anything not supplied is taken
+/// from `Context` (or the process environment).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpandOptions {
+    pub home: Option<PathBuf>,
+    pub cwd: Option<PathBuf>,
+    pub env: Option<HashMap<String, String>>,
+}
+
+impl ExpandOptions {
+    pub fn new() -> ExpandOptions {
+        ExpandOptions::default()
+    }
+
+    pub fn home<P: AsRef<Path>>(mut self, home: &P) -> ExpandOptions {
+        self.home = Some(home.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn cwd<P: AsRef<Path>>(mut self, cwd: &P) -> ExpandOptions {
+        self.cwd = Some(cwd.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use `env` instead of the process environment.
+    pub fn env(mut self, env: HashMap<String, String>) -> ExpandOptions {
+        self.env = Some(env);
+        self
+    }
+
+    /// The value of the environment variable `name`.
+    pub fn var(&self, name: &str) -> Option<String> {
+        match self.env {
+            Some(ref env) => env.get(name).cloned(),
+            None => env::var(name).ok(),
+        }
+    }
+
+    /// The home directory: the one supplied, else the one named by a
+    /// supplied environment, else `Context::home_dir()`.
+    pub fn home_dir(&self) -> Option<PathBuf> {
+        if let Some(ref home) = self.home {
+            Some(home.clone())
+        } else if let Some(ref env) = self.env {
+            env.get(HOME_VAR)
+                .filter(|home| !home.is_empty())
+                .map(PathBuf::from)
+        } else {
+            Context::home_dir()
+        }
+    }
+
+    pub fn current_dir(&self) -> io::Result<PathBuf> {
+        match self.cwd {
+            Some(ref cwd) => Ok(cwd.clone()),
+            None => Context::current_dir(),
+        }
+    }
+
+    /// The equivalent `PathCtx`.
+    pub fn path_ctx(&self) -> io::Result<PathCtx> {
+        Ok(PathCtx {
+            cwd: self.current_dir()?,
+            home: self.home_dir(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn overrides_work() {
+        let real_cwd = env::current_dir().unwrap();
+        assert_eq!(Context::current_dir().unwrap(), real_cwd);
+        let home = Context::with_home(&"/somewhere", || {
+            Context::with_cwd(&"/elsewhere", || {
+                // overrides don't leak into other threads
+                let other = thread::spawn(Context::current_dir).join().unwrap();
+                assert_eq!(other.unwrap(), real_cwd);
+                assert_eq!(Context::current_dir().unwrap(), Path::new("/elsewhere"));
+                Context::home_dir()
+            })
+        });
+        assert_eq!(home, Some(PathBuf::from("/somewhere")));
+        assert_eq!(Context::current_dir().unwrap(), real_cwd);
+        assert_eq!(Context::home_dir(), find_home_dir());
+        Context::invalidate();
+        assert_eq!(Context::home_dir(), find_home_dir());
+    }
+
+    #[test]
+    fn expand_options_work() {
+        let opts = ExpandOptions::new().home(&"/home/other").cwd(&"/srv");
+        assert_eq!(opts.home_dir(), Some(PathBuf::from("/home/other")));
+        assert_eq!(opts.current_dir().unwrap(), Path::new("/srv"));
+        assert_eq!(
+            opts.path_ctx().unwrap(),
+            PathCtx::new(&"/srv", Some(Path::new("/home/other")))
+        );
+        let mut env = HashMap::new();
+        env.insert(HOME_VAR.to_string(), "/home/daemon".to_string());
+        let opts = ExpandOptions::new().env(env);
+        assert_eq!(opts.home_dir(), Some(PathBuf::from("/home/daemon")));
+        assert_eq!(opts.var(HOME_VAR), Some("/home/daemon".to_string()));
+        assert_eq!(opts.var("PATHUX_NOT_SET"), None);
+        let opts = ExpandOptions::new().env(HashMap::new());
+        assert_eq!(opts.home_dir(), None);
+        assert_eq!(ExpandOptions::new().home_dir(), Context::home_dir());
+    }
+}
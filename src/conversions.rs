@@ -0,0 +1,154 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide conversion of paths between Windows and the POSIX
+//! style views of the same file system used by WSL, Cygwin and MSYS.
+//!
+//! The conversions are purely textual.  Relative paths only have their
+//! separators converted and `None` is returned for absolute paths that
+//! have no equivalent in the target form.
+
+use super::long_path::from_extended_length;
+use super::{to_backslash, to_slash};
+
+const WSL_MOUNT: &str = "/mnt";
+const CYGWIN_MOUNT: &str = "/cygdrive";
+const MSYS_MOUNT: &str = "";
+const WSL_HOSTS: [&str; 2] = [r"\\wsl$\", r"\\wsl.localhost\"];
+
+fn is_separator(ch: char) -> bool {
+    ch == '\\' || ch == '/'
+}
+
+// Split "C:\rest" into ('c', "\rest").
+fn split_drive(text: &str) -> Option<(char, &str)> {
+    let mut chars = text.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    if chars.next() == Some(':') {
+        Some((drive.to_ascii_lowercase(), chars.as_str()))
+    } else {
+        None
+    }
+}
+
+fn windows_to_posix(path: &str, mount: &str) -> Option<String> {
+    let path = from_extended_length(&path).to_str()?.to_string();
+    match split_drive(&path) {
+        Some((drive, rest)) if rest.is_empty() || rest.starts_with(is_separator) => {
+            Some(format!("{}/{}{}", mount, drive, to_slash(rest)))
+        }
+        // drive relative paths (e.g. "C:file") have no equivalent
+        Some(_) => None,
+        None if path.starts_with(is_separator) => None,
+        None => Some(to_slash(&path)),
+    }
+}
+
+fn posix_to_windows(path: &str, mount: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return Some(to_backslash(path));
+    }
+    let mut chars = path.strip_prefix(mount)?.strip_prefix('/')?.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    let rest = chars.as_str();
+    if rest.is_empty() || rest.starts_with('/') {
+        let rest = rest.trim_start_matches('/');
+        Some(format!(
+            "{}:\\{}",
+            drive.to_ascii_uppercase(),
+            to_backslash(rest)
+        ))
+    } else {
+        None
+    }
+}
+
+/// Convert a Windows path to the path used to access it from within WSL
+/// e.g. "C:\Users\me" becomes "/mnt/c/Users/me" and
+/// "\\wsl$\Ubuntu\home\me" becomes "/home/me".
+pub fn windows_to_wsl(path: &str) -> Option<String> {
+    for host in WSL_HOSTS.iter() {
+        let is_wsl_host = path.len() >= host.len()
+            && path.is_char_boundary(host.len())
+            && path[..host.len()].eq_ignore_ascii_case(host);
+        if is_wsl_host {
+            let rest = &path[host.len()..];
+            return match rest.find(is_separator) {
+                Some(index) => Some(to_slash(&rest[index..])),
+                None => Some("/".to_string()),
+            };
+        }
+    }
+    windows_to_posix(path, WSL_MOUNT)
+}
+
+/// Convert a WSL path under "/mnt/<drive>" to the equivalent Windows path.
+pub fn wsl_to_windows(path: &str) -> Option<String> {
+    posix_to_windows(path, WSL_MOUNT)
+}
+
+/// Convert a Windows path to Cygwin form e.g. "C:\x" becomes "/cygdrive/c/x".
+pub fn windows_to_cygwin(path: &str) -> Option<String> {
+    windows_to_posix(path, CYGWIN_MOUNT)
+}
+
+/// Convert a Cygwin path under "/cygdrive/<drive>" to Windows form.
+pub fn cygwin_to_windows(path: &str) -> Option<String> {
+    posix_to_windows(path, CYGWIN_MOUNT)
+}
+
+/// Convert a Windows path to MSYS form e.g. "C:\x" becomes "/c/x".
+pub fn windows_to_msys(path: &str) -> Option<String> {
+    windows_to_posix(path, MSYS_MOUNT)
+}
+
+/// Convert an MSYS path under "/<drive>" to Windows form.
+pub fn msys_to_windows(path: &str) -> Option<String> {
+    posix_to_windows(path, MSYS_MOUNT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wsl_conversions_work() {
+        let wsl = |path| windows_to_wsl(path).unwrap();
+        assert_eq!(wsl(r"C:\Users\me"), "/mnt/c/Users/me");
+        assert_eq!(wsl(r"d:"), "/mnt/d");
+        assert_eq!(wsl(r"\\?\C:\x"), "/mnt/c/x");
+        assert_eq!(wsl(r"\\wsl$\Ubuntu\home\me"), "/home/me");
+        assert_eq!(wsl(r"\\wsl.localhost\Ubuntu"), "/");
+        assert_eq!(wsl(r"a\b"), "a/b");
+        assert_eq!(windows_to_wsl(r"C:file"), None);
+        assert_eq!(windows_to_wsl(r"\\server\share"), None);
+
+        let windows = |path| wsl_to_windows(path).unwrap();
+        assert_eq!(windows("/mnt/c/Users/me"), r"C:\Users\me");
+        assert_eq!(windows("/mnt/c"), r"C:\");
+        assert_eq!(windows("a/b"), r"a\b");
+        assert_eq!(wsl_to_windows("/home/me"), None);
+        assert_eq!(wsl_to_windows("/mnt/cd/x"), None);
+    }
+
+    #[test]
+    fn cygwin_and_msys_conversions_work() {
+        assert_eq!(windows_to_cygwin(r"C:\x\y").unwrap(), "/cygdrive/c/x/y");
+        assert_eq!(cygwin_to_windows("/cygdrive/c/x/y").unwrap(), r"C:\x\y");
+        assert_eq!(cygwin_to_windows("/usr/bin"), None);
+        assert_eq!(windows_to_msys(r"C:\x").unwrap(), "/c/x");
+        assert_eq!(msys_to_windows("/c/x").unwrap(), r"C:\x");
+        assert_eq!(msys_to_windows("/usr/bin"), None);
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide recursive copying of directory trees.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::durable::Durability;
+use super::fs_provider::{FsDirEntry, FsProvider, RealFs};
+use super::walk::Walker;
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    /// Copy into an existing destination directory replacing any files
+    /// (or symbolic links) that are already there.
+    pub overwrite: bool,
+    /// Don't copy anything on a different file system to the source (the
+    /// mount points are created as empty directories).
+    pub same_device_only: bool,
+    /// Give copied files and directories the access and modification
+    /// times of their originals.
+    pub preserve_times: bool,
+    /// Keep holes in sparse files (e.g. virtual machine images) rather
+    /// than filling them with zeroes.
+    pub preserve_sparse: bool,
+    /// With `Durability::Full`, flush the copied files and directories to
+    /// stable storage before returning.
+    pub durability: Durability,
+    /// Give copied files and directories the extended attributes (e.g.
+    /// SELinux labels) of their originals.
+    pub preserve_xattrs: bool,
+    /// Give copied files and directories the access control lists of
+    /// their originals (this needs the "acl" feature and fails with an
+    /// `Unsupported` error where ACLs can't be carried over).
+    pub preserve_acls: bool,
+}
+
+// Make way for a new file or link at `path` if allowed.
+fn clear_target<F: FsProvider>(fs: &F, path: &Path, options: &CopyOptions) -> io::Result<()> {
+    match fs.symlink_metadata(path) {
+        Ok(metadata) => {
+            if !options.overwrite || metadata.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{}: already exists", path.display()),
+                ));
+            }
+            fs.remove_file(path)
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+fn create_dir<F: FsProvider>(fs: &F, path: &Path, options: &CopyOptions) -> io::Result<()> {
+    match fs.create_dir(path) {
+        Err(ref err)
+            if err.kind() == io::ErrorKind::AlreadyExists
+                && options.overwrite
+                && fs.is_dir(path) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+// The canonical form of `path` where the part that doesn't exist yet is
+// appended to the canonical form of its longest existing ancestor.
+fn real_path<F: FsProvider>(fs: &F, path: &Path) -> PathBuf {
+    if let Ok(real_path) = fs.canonicalize(path) {
+        return real_path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if parent.as_os_str().is_empty() => {
+            real_path(fs, Path::new(".")).join(name)
+        }
+        (Some(parent), Some(name)) => real_path(fs, parent).join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Copy the directory `src` and everything below it to `dst` (which must
+/// not already exist unless `options.overwrite` is set).  Symbolic links
+/// are copied as links and special files (devices, pipes, etc.) are skipped.
+/// It is an `InvalidInput` error for `dst` to be inside `src`.
+pub fn copy_tree<S, D>(src: &S, dst: &D, options: &CopyOptions) -> io::Result<()>
+where
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    copy_tree_with(&RealFs, src, dst, options)
+}
+
+/// Like `copy_tree()` but within the file system `fs`.
+pub fn copy_tree_with<F, S, D>(fs: &F, src: &S, dst: &D, options: &CopyOptions) -> io::Result<()>
+where
+    F: FsProvider,
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    let src: &Path = src.as_ref();
+    let dst: &Path = dst.as_ref();
+    if !fs.metadata(src)?.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: not a directory", src.display()),
+        ));
+    }
+    if real_path(fs, dst).starts_with(fs.canonicalize(src)?) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{}: can't copy a directory into itself ({})",
+                src.display(),
+                dst.display()
+            ),
+        ));
+    }
+    create_dir(fs, dst, options)?;
+    // directory times must be set after their contents have been copied
+    let mut dirs = vec![(src.to_path_buf(), dst.to_path_buf())];
+    for entry in Walker::new_with(fs, &src).same_device_only(options.same_device_only) {
+        let entry = entry?;
+        let path: &Path = &entry.path();
+        let target = match path.strip_prefix(src) {
+            Ok(relative_path) => dst.join(relative_path),
+            Err(_) => continue,
+        };
+        if entry.is_dir() {
+            create_dir(fs, &target, options)?;
+            dirs.push((path.to_path_buf(), target));
+        } else if entry.is_symlink() {
+            clear_target(fs, &target, options)?;
+            fs.symlink(&fs.read_link(path)?, &target)?;
+        } else if entry.is_file() {
+            clear_target(fs, &target, options)?;
+            if options.preserve_sparse {
+                fs.copy_file_sparse(path, &target)?;
+            } else {
+                fs.copy_file(path, &target)?;
+            }
+            if options.preserve_xattrs {
+                fs.copy_xattrs(path, &target)?;
+            }
+            if options.preserve_acls {
+                fs.copy_acl(path, &target)?;
+            }
+            if options.preserve_times {
+                fs.copy_times(path, &target)?;
+            }
+            if options.durability == Durability::Full {
+                fs.sync(&target)?;
+            }
+        }
+    }
+    if options.preserve_xattrs {
+        for (path, target) in dirs.iter() {
+            fs.copy_xattrs(path, target)?;
+        }
+    }
+    if options.preserve_acls {
+        for (path, target) in dirs.iter() {
+            fs.copy_acl(path, target)?;
+        }
+    }
+    if options.preserve_times {
+        for (path, target) in dirs.iter().rev() {
+            fs.copy_times(path, target)?;
+        }
+    }
+    if options.durability == Durability::Full {
+        for (_, target) in dirs.iter().rev() {
+            fs.sync(target)?;
+        }
+        if let Some(parent) = dst.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs.sync(parent)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use fs_provider::MemFs;
+    use sparse::is_sparse;
+    use times::set_times;
+
+    #[test]
+    fn copy_tree_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        fs::create_dir_all(src.join("a/b")).unwrap();
+        fs::write(src.join("a/b/file"), "contents").unwrap();
+        fs::write(src.join("top"), "top").unwrap();
+        #[cfg(unix)]
+        ::std::os::unix::fs::symlink("a/b/file", src.join("link")).unwrap();
+
+        let options = CopyOptions::default();
+        copy_tree(&src, &dst, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(dst.join("a/b/file")).unwrap(),
+            "contents"
+        );
+        assert_eq!(fs::read_to_string(dst.join("top")).unwrap(), "top");
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(dst.join("link")).unwrap(),
+            Path::new("a/b/file")
+        );
+
+        assert!(copy_tree(&src, &dst, &options).is_err());
+        fs::write(src.join("top"), "new top").unwrap();
+        let options = CopyOptions {
+            overwrite: true,
+            ..CopyOptions::default()
+        };
+        copy_tree(&src, &dst, &options).unwrap();
+        assert_eq!(fs::read_to_string(dst.join("top")).unwrap(), "new top");
+        assert!(copy_tree(&src.join("top"), &dst, &options).is_err());
+
+        let options = CopyOptions {
+            durability: Durability::Full,
+            ..CopyOptions::default()
+        };
+        copy_tree(&src, &temp_dir.path().join("durable"), &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("durable/a/b/file")).unwrap(),
+            "contents"
+        );
+    }
+
+    #[test]
+    fn copy_tree_into_itself_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        fs::create_dir_all(src.join("a")).unwrap();
+        fs::write(src.join("a/file"), "contents").unwrap();
+        let options = CopyOptions {
+            overwrite: true,
+            ..CopyOptions::default()
+        };
+        for dst in &[src.join("a/copy"), src.join("a"), src.clone()] {
+            assert_eq!(
+                copy_tree(&src, dst, &options).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+        assert!(!src.join("a/copy").exists());
+        #[cfg(unix)]
+        {
+            let link = temp_dir.path().join("link");
+            ::std::os::unix::fs::symlink(&src, &link).unwrap();
+            assert!(copy_tree(&link, &src.join("copy"), &options).is_err());
+            assert!(copy_tree(&src, &link.join("copy"), &options).is_err());
+            assert!(!src.join("copy").exists());
+        }
+        copy_tree(&src.join("a"), &src.join("copy"), &options).unwrap();
+        assert!(src.join("copy/file").is_file());
+    }
+
+    #[test]
+    fn copy_tree_preserves_times() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        fs::create_dir_all(src.join("dir")).unwrap();
+        fs::write(src.join("dir/file"), "contents").unwrap();
+        let then = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        set_times(&src.join("dir/file"), then, then).unwrap();
+        set_times(&src.join("dir"), then, then).unwrap();
+        let options = CopyOptions {
+            preserve_times: true,
+            ..CopyOptions::default()
+        };
+        copy_tree(&src, &dst, &options).unwrap();
+        let modified = |path: PathBuf| fs::metadata(path).unwrap().modified().unwrap();
+        assert_eq!(modified(dst.join("dir/file")), then);
+        assert_eq!(modified(dst.join("dir")), then);
+        assert_eq!(modified(dst.clone()), modified(src.clone()));
+    }
+
+    #[test]
+    fn copy_tree_preserves_holes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        fs::create_dir(&src).unwrap();
+        let image = fs::File::create(src.join("image")).unwrap();
+        image.set_len(64 * 1024 * 1024).unwrap();
+        drop(image);
+        let options = CopyOptions {
+            preserve_sparse: true,
+            ..CopyOptions::default()
+        };
+        copy_tree(&src, &dst, &options).unwrap();
+        assert_eq!(
+            fs::metadata(dst.join("image")).unwrap().len(),
+            64 * 1024 * 1024
+        );
+        if is_sparse(&src.join("image")).unwrap() {
+            assert!(is_sparse(&dst.join("image")).unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_tree_preserves_xattrs() {
+        use xattr::{get_xattr, set_xattr};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        fs::create_dir(&src).unwrap();
+        fs::write(src.join("file"), "contents").unwrap();
+        if set_xattr(&src.join("file"), &"user.pathux", b"value").is_err() {
+            return;
+        }
+        set_xattr(&src, &"user.pathux", b"dir").unwrap();
+        let options = CopyOptions {
+            preserve_xattrs: true,
+            ..CopyOptions::default()
+        };
+        copy_tree(&src, &dst, &options).unwrap();
+        assert_eq!(
+            get_xattr(&dst.join("file"), &"user.pathux").unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(
+            get_xattr(&dst, &"user.pathux").unwrap(),
+            Some(b"dir".to_vec())
+        );
+    }
+
+    #[test]
+    fn copy_tree_with_works() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/src/a")).unwrap();
+        fs.write(Path::new("/src/a/file"), b"data").unwrap();
+        fs.symlink(Path::new("a/file"), Path::new("/src/link"))
+            .unwrap();
+        copy_tree_with(&fs, &"/src", &"/dst", &CopyOptions::default()).unwrap();
+        assert_eq!(fs.read(Path::new("/dst/a/file")).unwrap(), b"data");
+        assert_eq!(
+            fs.read_link(Path::new("/dst/link")).unwrap(),
+            Path::new("a/file")
+        );
+        assert!(copy_tree_with(&fs, &"/src", &"/dst", &CopyOptions::default()).is_err());
+        assert_eq!(
+            copy_tree_with(&fs, &"/src", &"/src/a/copy", &CopyOptions::default())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(!fs.exists(Path::new("/src/a/copy")));
+        let options = CopyOptions {
+            preserve_acls: true,
+            ..CopyOptions::default()
+        };
+        assert_eq!(
+            copy_tree_with(&fs, &"/src", &"/acl", &options)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide creation of files along with any missing parent
+//! directories (reporting which directories were created so that they can
+//! be removed again if need be).
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Create any missing ancestors of the file `path` returning the
+/// directories that were created (outermost first).
+pub fn create_dir_all_for<P: AsRef<Path>>(path: &P) -> io::Result<Vec<PathBuf>> {
+    let mut missing = Vec::new();
+    let mut dir_path = path.as_ref().parent();
+    while let Some(dir) = dir_path {
+        if dir.as_os_str().is_empty() || dir.exists() {
+            break;
+        }
+        missing.push(dir.to_path_buf());
+        dir_path = dir.parent();
+    }
+    let mut created = Vec::new();
+    for dir in missing.into_iter().rev() {
+        match fs::create_dir(&dir) {
+            Ok(()) => created.push(dir),
+            // someone else beat us to it
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => (),
+            Err(err) => {
+                let _ = remove_created_dirs(&created);
+                return Err(err);
+            }
+        }
+    }
+    Ok(created)
+}
+
+/// Remove the (empty) directories reported as created by
+/// `create_dir_all_for()` or `create_file_with_parents()`.
+pub fn remove_created_dirs(created: &[PathBuf]) -> io::Result<()> {
+    for dir in created.iter().rev() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Create (or truncate) the file `path` after creating any missing parent
+/// directories.  The directories that were created are returned along with
+/// the file and are removed again if the file can't be created.
+pub fn create_file_with_parents<P: AsRef<Path>>(path: &P) -> io::Result<(File, Vec<PathBuf>)> {
+    let created = create_dir_all_for(path)?;
+    match File::create(path) {
+        Ok(file) => Ok((file, created)),
+        Err(err) => {
+            let _ = remove_created_dirs(&created);
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::io::Write;
+
+    #[test]
+    fn create_file_with_parents_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let path = root.join("a/b/c.txt");
+        let (mut file, created) = create_file_with_parents(&path).unwrap();
+        file.write_all(b"text").unwrap();
+        assert_eq!(created, vec![root.join("a"), root.join("a/b")]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "text");
+        let (_, created) = create_file_with_parents(&root.join("a/d/e")).unwrap();
+        assert_eq!(created, vec![root.join("a/d")]);
+        assert!(create_dir_all_for(&root.join("x")).unwrap().is_empty());
+
+        // created directories are removed if the file can't be created
+        let too_long = root.join("new").join("x".repeat(300));
+        assert!(create_file_with_parents(&too_long).is_err());
+        assert!(!root.join("new").exists());
+        assert!(create_file_with_parents(&root.join("a/b")).is_err());
+    }
+}
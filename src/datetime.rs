@@ -0,0 +1,79 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Private minimal calendar support (UTC only) so that time stamps can be
+//! formatted without pulling in a date/time crate.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    pub fn from_unix_seconds(secs: i64) -> DateTime {
+        let days = secs.div_euclid(86_400);
+        let day_secs = secs.rem_euclid(86_400);
+        // Howard Hinnant's "civil_from_days" algorithm
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        DateTime {
+            year,
+            month,
+            day,
+            hour: (day_secs / 3600) as u32,
+            minute: (day_secs % 3600 / 60) as u32,
+            second: (day_secs % 60) as u32,
+        }
+    }
+
+    pub fn from_system_time(time: SystemTime) -> DateTime {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(err) => -(err.duration().as_secs() as i64),
+        };
+        DateTime::from_unix_seconds(secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_unix_seconds_works() {
+        let dt = DateTime::from_unix_seconds(0);
+        assert_eq!((dt.year, dt.month, dt.day, dt.hour), (1970, 1, 1, 0));
+        let dt = DateTime::from_unix_seconds(951_782_400 + 3_723);
+        assert_eq!(
+            (dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second),
+            (2000, 2, 29, 1, 2, 3)
+        );
+        let dt = DateTime::from_unix_seconds(-1);
+        assert_eq!((dt.year, dt.month, dt.day, dt.hour), (1969, 12, 31, 23));
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide summary statistics for the contents of a directory.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::walk::Walker;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirStats {
+    pub files: usize,
+    pub dirs: usize,
+    pub symlinks: usize,
+    /// Anything that isn't a file, directory or symbolic link.
+    pub others: usize,
+    /// Total length of the regular files.
+    pub total_bytes: u64,
+    /// Number of regular files with each extension ("" for no extension).
+    pub extensions: BTreeMap<String, usize>,
+    /// The (first found) entry with the most levels below the root.
+    pub deepest_path: Option<PathBuf>,
+    pub max_depth: usize,
+}
+
+/// Gather statistics for the entries in `root` (and, if `recursive`, all
+/// its sub directories).  Symbolic links are counted but not followed.
+pub fn dir_stats<P: AsRef<Path>>(root: &P, recursive: bool) -> io::Result<DirStats> {
+    let mut stats = DirStats::default();
+    let walker = if recursive {
+        Walker::new(root)
+    } else {
+        Walker::new(root).max_depth(1)
+    };
+    for entry in walker {
+        let entry = entry?;
+        if entry.is_file() {
+            stats.files += 1;
+            stats.total_bytes += entry.metadata()?.len();
+            let extension = match entry.path().extension() {
+                Some(extension) => extension.to_string_lossy().into_owned(),
+                None => String::new(),
+            };
+            *stats.extensions.entry(extension).or_insert(0) += 1;
+        } else if entry.is_dir() {
+            stats.dirs += 1;
+        } else if entry.is_symlink() {
+            stats.symlinks += 1;
+        } else {
+            stats.others += 1;
+        }
+        if entry.depth() > stats.max_depth {
+            stats.max_depth = entry.depth();
+            stats.deepest_path = Some(entry.path());
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn dir_stats_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/lib.rs"), "12345").unwrap();
+        fs::write(root.join("main.rs"), "123").unwrap();
+        fs::write(root.join("README"), "").unwrap();
+
+        let stats = dir_stats(&root, true).unwrap();
+        assert_eq!((stats.files, stats.dirs, stats.symlinks), (3, 2, 0));
+        assert_eq!(stats.total_bytes, 8);
+        assert_eq!(stats.extensions.get("rs"), Some(&2));
+        assert_eq!(stats.extensions.get(""), Some(&1));
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.deepest_path, Some(root.join("a/b/lib.rs")));
+
+        let stats = dir_stats(&root, false).unwrap();
+        assert_eq!((stats.files, stats.dirs), (2, 1));
+        assert_eq!(stats.max_depth, 1);
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide `du` like disk usage calculations for a subtree.
+
+use std::collections::HashSet;
+use std::fs::{self, DirEntry, Metadata};
+use std::io;
+use std::path::Path;
+
+use super::filesystem::same_filesystem;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+#[derive(Debug, Clone)]
+pub struct DuOptions {
+    /// Don't count anything on a different file system to the root.
+    pub same_device_only: bool,
+    /// Don't count symbolic links themselves (they are never followed).
+    pub skip_symlinks: bool,
+    /// Count files with multiple hard links only once.
+    pub count_hard_links_once: bool,
+}
+
+impl Default for DuOptions {
+    fn default() -> Self {
+        DuOptions {
+            same_device_only: false,
+            skip_symlinks: false,
+            count_hard_links_once: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DuTotals {
+    /// Sum of the files' lengths.
+    pub apparent_size: u64,
+    /// Space actually allocated on disk (same as `apparent_size` on
+    /// platforms other than unix).
+    pub allocated_size: u64,
+    /// Number of entries (files, directories, links, etc.) counted.
+    pub entries: u64,
+}
+
+impl DuTotals {
+    fn add(&mut self, other: &DuTotals) {
+        self.apparent_size += other.apparent_size;
+        self.allocated_size += other.allocated_size;
+        self.entries += other.entries;
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DuReport {
+    /// Totals for the whole subtree including the root itself.
+    pub total: DuTotals,
+    /// Totals for each direct child of the root (if it's a directory)
+    /// sorted by name.
+    pub children: Vec<(String, DuTotals)>,
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &Metadata) -> u64 {
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn multi_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    if metadata.nlink() > 1 && !metadata.is_dir() {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn multi_link_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct Counter<'a> {
+    options: &'a DuOptions,
+    root: &'a Path,
+    seen: HashSet<(u64, u64)>,
+}
+
+impl<'a> Counter<'a> {
+    fn wanted(&mut self, path: &Path, metadata: &Metadata) -> io::Result<bool> {
+        if self.options.skip_symlinks && metadata.file_type().is_symlink() {
+            return Ok(false);
+        }
+        // only directories can be mount points
+        if self.options.same_device_only
+            && metadata.is_dir()
+            && !same_filesystem(&self.root, &path)?
+        {
+            return Ok(false);
+        }
+        if self.options.count_hard_links_once {
+            if let Some(id) = multi_link_id(metadata) {
+                return Ok(self.seen.insert(id));
+            }
+        }
+        Ok(true)
+    }
+
+    // Return the wanted children of the directory at `path`.
+    fn children(&mut self, path: &Path) -> io::Result<Vec<(DirEntry, Metadata)>> {
+        let mut children = Vec::new();
+        for e_entry in fs::read_dir(path)? {
+            let dir_entry = e_entry?;
+            let metadata = match dir_entry.metadata() {
+                Ok(metadata) => metadata,
+                // we assume that "not found" is due to a race condition and ignore it
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            if self.wanted(&dir_entry.path(), &metadata)? {
+                children.push((dir_entry, metadata));
+            }
+        }
+        Ok(children)
+    }
+
+    fn tally(&mut self, path: &Path, metadata: &Metadata, totals: &mut DuTotals) -> io::Result<()> {
+        totals.apparent_size += metadata.len();
+        totals.allocated_size += allocated_size(metadata);
+        totals.entries += 1;
+        if metadata.is_dir() {
+            for (dir_entry, metadata) in self.children(path)? {
+                self.tally(&dir_entry.path(), &metadata, totals)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calculate the disk usage of `path` and everything below it.
+/// Symbolic links are never followed.
+pub fn disk_usage<P: AsRef<Path>>(path: &P, options: &DuOptions) -> io::Result<DuReport> {
+    let path: &Path = path.as_ref();
+    let metadata = fs::symlink_metadata(path)?;
+    let mut counter = Counter {
+        options,
+        root: path,
+        seen: HashSet::new(),
+    };
+    let mut report = DuReport::default();
+    report.total.apparent_size = metadata.len();
+    report.total.allocated_size = allocated_size(&metadata);
+    report.total.entries = 1;
+    if metadata.is_dir() {
+        for (dir_entry, metadata) in counter.children(path)? {
+            let mut totals = DuTotals::default();
+            counter.tally(&dir_entry.path(), &metadata, &mut totals)?;
+            report.total.add(&totals);
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+            report.children.push((name, totals));
+        }
+        report.children.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn disk_usage_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a"), "12345").unwrap();
+        fs::write(root.join("sub/b"), "123").unwrap();
+        let dir_size = fs::metadata(root).unwrap().len();
+        let sub_size = fs::metadata(root.join("sub")).unwrap().len();
+
+        let report = disk_usage(&root, &DuOptions::default()).unwrap();
+        assert_eq!(report.total.entries, 4);
+        assert_eq!(report.total.apparent_size, dir_size + sub_size + 8);
+        assert_eq!(report.children.len(), 2);
+        assert_eq!(report.children[0].0, "a");
+        assert_eq!(report.children[0].1.apparent_size, 5);
+        assert_eq!(report.children[1].1.entries, 2);
+
+        let report = disk_usage(&root.join("a"), &DuOptions::default()).unwrap();
+        assert_eq!(report.total.apparent_size, 5);
+        assert!(report.children.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn disk_usage_counts_hard_links_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a"), "12345").unwrap();
+        fs::hard_link(root.join("a"), root.join("b")).unwrap();
+        let report = disk_usage(&root, &DuOptions::default()).unwrap();
+        assert_eq!(report.total.entries, 2);
+        let options = DuOptions {
+            count_hard_links_once: false,
+            ..DuOptions::default()
+        };
+        let report = disk_usage(&root, &options).unwrap();
+        assert_eq!(report.total.entries, 3);
+    }
+}
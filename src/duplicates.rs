@@ -0,0 +1,279 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide comparison of file contents and a finder for files
+//! with identical contents.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+use super::fs_provider::FileId;
+use super::hash::{hash_file, hash_reader, HashAlgo};
+use super::walk::Walker;
+
+#[derive(Debug, Clone)]
+pub struct DupOptions {
+    /// Files smaller than this are ignored (the default of 1 skips empty files).
+    pub min_size: u64,
+    /// Number of leading bytes hashed to weed out non duplicates cheaply
+    /// before the full contents are hashed.
+    pub partial_hash_len: u64,
+    pub algo: HashAlgo,
+}
+
+impl Default for DupOptions {
+    fn default() -> Self {
+        DupOptions {
+            min_size: 1,
+            partial_hash_len: 4096,
+            algo: HashAlgo::Sha256,
+        }
+    }
+}
+
+fn partial_hash(path: &Path, len: u64, algo: HashAlgo) -> io::Result<String> {
+    let file = File::open(path)?;
+    hash_reader(&mut file.take(len), algo)
+}
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Read until `buffer` is full or end of file returning the number of bytes
+// read.
+fn fill_buffer<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// Return `true` if the files `a` and `b` have the same contents.  Files
+/// of different sizes are rejected without being read and otherwise the
+/// comparison stops at the first difference.
+pub fn files_identical<A: AsRef<Path>, B: AsRef<Path>>(a: &A, b: &B) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut file_a = File::open(a)?;
+    let mut file_b = File::open(b)?;
+    let mut buffer_a = vec![0u8; CHUNK_SIZE];
+    let mut buffer_b = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n_a = fill_buffer(&mut file_a, &mut buffer_a)?;
+        let n_b = fill_buffer(&mut file_b, &mut buffer_b)?;
+        if buffer_a[..n_a] != buffer_b[..n_b] {
+            return Ok(false);
+        } else if n_a < CHUNK_SIZE {
+            return Ok(true);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(_path: &Path, metadata: &Metadata) -> io::Result<FileId> {
+    Ok(FileId::Inode(metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path, _metadata: &Metadata) -> io::Result<FileId> {
+    Ok(FileId::Path(fs::canonicalize(path)?))
+}
+
+// Split each group into sub groups with equal keys discarding singletons.
+fn refine<K, F>(groups: Vec<Vec<PathBuf>>, key: F) -> io::Result<Vec<Vec<PathBuf>>>
+where
+    K: Eq + Hash,
+    F: Fn(&Path) -> io::Result<K>,
+{
+    let mut refined = Vec::new();
+    for group in groups {
+        let mut by_key: HashMap<K, Vec<PathBuf>> = HashMap::new();
+        for path in group {
+            let k = key(&path)?;
+            by_key.entry(k).or_default().push(path);
+        }
+        refined.extend(by_key.into_values().filter(|v| v.len() > 1));
+    }
+    Ok(refined)
+}
+
+/// Find groups of regular files (below any of `roots`) that have identical
+/// contents.  Candidates are grouped by size, then by a hash of their
+/// first few bytes and finally by a hash of their full contents (or a
+/// direct comparison when there are only two candidates).  The
+/// paths in each group, and the groups themselves, are sorted.  A file is
+/// only considered once even if `roots` overlap and hard links to a file
+/// (on Unix) aren't reported as duplicates of it.  Fails if any candidate
+/// can't be read.
+///
+/// The roots are walked with the (serial) `Walker` as this crate has no
+/// parallel walker: the time goes on hashing the candidates anyway.
+pub fn find_duplicates<P: AsRef<Path>>(
+    roots: &[P],
+    options: &DupOptions,
+) -> io::Result<Vec<Vec<PathBuf>>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut seen: HashSet<FileId> = HashSet::new();
+    for root in roots {
+        for entry in Walker::new(root) {
+            let entry = entry?;
+            if !entry.is_file() {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let path = entry.path();
+            if metadata.len() >= options.min_size && seen.insert(file_id(&path, &metadata)?) {
+                by_size.entry(metadata.len()).or_default().push(path);
+            }
+        }
+    }
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    for (size, v) in by_size.into_iter().filter(|(_, v)| v.len() > 1) {
+        if size > options.partial_hash_len {
+            groups.extend(refine(vec![v], |path| {
+                partial_hash(path, options.partial_hash_len, options.algo)
+            })?);
+        } else {
+            groups.push(v);
+        }
+    }
+    // a direct comparison is cheaper than hashing when there are only two
+    let (pairs, groups): (Vec<_>, Vec<_>) = groups.into_iter().partition(|v| v.len() == 2);
+    let mut groups = refine(groups, |path| hash_file(&path, options.algo))?;
+    for pair in pairs {
+        if files_identical(&pair[0], &pair[1])? {
+            groups.push(pair);
+        }
+    }
+    for group in groups.iter_mut() {
+        group.sort();
+    }
+    groups.sort();
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn find_duplicates_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a/one"), "same").unwrap();
+        fs::write(root.join("b/one"), "same").unwrap();
+        fs::write(root.join("b/two"), "diff").unwrap();
+        fs::write(root.join("a/empty"), "").unwrap();
+        fs::write(root.join("b/empty"), "").unwrap();
+
+        let options = DupOptions {
+            partial_hash_len: 2,
+            ..DupOptions::default()
+        };
+        let dups = find_duplicates(&[root.join("a"), root.join("b")], &options).unwrap();
+        assert_eq!(dups, vec![vec![root.join("a/one"), root.join("b/one")]]);
+
+        let options = DupOptions {
+            min_size: 0,
+            ..DupOptions::default()
+        };
+        let dups = find_duplicates(&[root], &options).unwrap();
+        assert_eq!(dups.len(), 2);
+        assert_eq!(dups[0], vec![root.join("a/empty"), root.join("b/empty")]);
+    }
+
+    #[test]
+    fn find_duplicates_ignores_overlaps() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("a/one"), "same").unwrap();
+        fs::write(root.join("two"), "same").unwrap();
+        fs::write(root.join("a/three"), "diff").unwrap();
+        let options = DupOptions::default();
+        let dups = find_duplicates(&[root.to_path_buf(), root.join("a")], &options).unwrap();
+        assert_eq!(dups, vec![vec![root.join("a/one"), root.join("two")]]);
+        let dups = find_duplicates(&[root.join("a"), root.join("a")], &options).unwrap();
+        assert!(dups.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_duplicates_ignores_hard_links() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("one"), "same").unwrap();
+        fs::hard_link(root.join("one"), root.join("link")).unwrap();
+        let options = DupOptions::default();
+        assert!(find_duplicates(&[root], &options).unwrap().is_empty());
+        fs::write(root.join("two"), "same").unwrap();
+        let dups = find_duplicates(&[root], &options).unwrap();
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].len(), 2);
+        assert!(dups[0].contains(&root.join("two")));
+    }
+
+    #[test]
+    fn refine_reports_errors() {
+        let group = vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")];
+        let refined = refine(vec![group.clone()], |path| Ok(path == Path::new("c"))).unwrap();
+        assert_eq!(refined, vec![vec![PathBuf::from("a"), PathBuf::from("b")]]);
+        let result = refine(vec![group], |path| {
+            if path == Path::new("b") {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, "b"))
+            } else {
+                Ok(0)
+            }
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn files_identical_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let big: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        fs::write(root.join("a"), &big).unwrap();
+        fs::write(root.join("b"), &big).unwrap();
+        assert!(files_identical(&root.join("a"), &root.join("b")).unwrap());
+        let mut changed = big.clone();
+        changed[150_000] ^= 1;
+        fs::write(root.join("c"), &changed).unwrap();
+        assert!(!files_identical(&root.join("a"), &root.join("c")).unwrap());
+        fs::write(root.join("d"), &big[..1000]).unwrap();
+        assert!(!files_identical(&root.join("a"), &root.join("d")).unwrap());
+        fs::write(root.join("e"), "").unwrap();
+        fs::write(root.join("f"), "").unwrap();
+        assert!(files_identical(&root.join("e"), &root.join("f")).unwrap());
+        assert!(files_identical(&root.join("a"), &root.join("none")).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide helpers for flushing files and directories to
+//! stable storage so that state survives a crash.
+
+use std::fs::File;
+#[cfg(windows)]
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+use super::fs_provider::{FsProvider, RealFs};
+
+/// How hard to try to make written data survive a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Leave it to the operating system to write data when it sees fit.
+    #[default]
+    Relaxed,
+    /// Flush files, and the directories that contain them, to stable
+    /// storage before returning.
+    Full,
+}
+
+// FlushFileBuffers() needs a handle with write access.
+#[cfg(windows)]
+fn open_for_sync(path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).open(path)
+}
+
+#[cfg(not(windows))]
+fn open_for_sync(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+/// Flush the contents and metadata of the file `path` to stable storage
+/// (i.e. `fsync()`).
+pub fn sync_file<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+    open_for_sync(path.as_ref())?.sync_all()
+}
+
+/// Flush the contents of the file `path` to stable storage leaving out
+/// metadata not needed to read it back (i.e. `fdatasync()`).
+pub fn sync_file_data<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+    open_for_sync(path.as_ref())?.sync_data()
+}
+
+/// Flush the directory `path` to stable storage so that entries created,
+/// renamed or removed in it persist.  This is a no op on platforms (e.g.
+/// Windows) where directories can't be synced.
+pub fn sync_dir<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+    imp::sync_dir(path.as_ref())
+}
+
+/// Flush the directory containing `path` to stable storage.
+pub fn sync_parent_dir<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+    sync_parent_dir_with(&RealFs, path)
+}
+
+/// Like `sync_file()` or `sync_dir()` (whichever applies) but within the
+/// file system `fs`.
+pub fn sync_with<F: FsProvider, P: AsRef<Path>>(fs: &F, path: &P) -> io::Result<()> {
+    fs.sync(path.as_ref())
+}
+
+/// Like `sync_parent_dir()` but within the file system `fs`.
+pub fn sync_parent_dir_with<F: FsProvider, P: AsRef<Path>>(fs: &F, path: &P) -> io::Result<()> {
+    match path.as_ref().parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs.sync(parent),
+        _ => fs.sync(Path::new(".")),
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub fn sync_dir(path: &Path) -> io::Result<()> {
+        File::open(path)?.sync_all()
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    pub fn sync_dir(path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: directory not found", path.display()),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn sync_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "contents").unwrap();
+        sync_file(&file).unwrap();
+        sync_file_data(&file).unwrap();
+        sync_dir(&temp_dir.path()).unwrap();
+        sync_parent_dir(&file).unwrap();
+        assert!(sync_file(&temp_dir.path().join("missing")).is_err());
+        assert!(sync_dir(&temp_dir.path().join("missing")).is_err());
+        assert_eq!(Durability::default(), Durability::Relaxed);
+    }
+
+    #[test]
+    fn sync_with_works() {
+        use fs_provider::MemFs;
+
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/a/b")).unwrap();
+        fs.write(Path::new("/a/b/file"), b"contents").unwrap();
+        sync_with(&fs, &"/a/b/file").unwrap();
+        sync_with(&fs, &"/a/b").unwrap();
+        sync_parent_dir_with(&fs, &"/a/b/file").unwrap();
+        assert!(sync_with(&fs, &"/a/missing").is_err());
+        assert!(sync_parent_dir_with(&fs, &"/missing/file").is_err());
+    }
+}
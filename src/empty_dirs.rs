@@ -0,0 +1,118 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide detection and removal of empty directories.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::fs_provider::FsProvider;
+use super::walk::Walker;
+
+pub fn is_dir_empty<P: AsRef<Path>>(path: &P) -> io::Result<bool> {
+    Ok(fs::read_dir(path)?.next().is_none())
+}
+
+/// Like `is_dir_empty()` but within the file system `fs`.
+pub fn is_dir_empty_with<F: FsProvider, P: AsRef<Path>>(fs: &F, path: &P) -> io::Result<bool> {
+    Ok(fs.read_dir(path.as_ref())?.is_empty())
+}
+
+/// Return the paths of all directories below `root` that have no entries.
+pub fn find_empty_dirs<P: AsRef<Path>>(root: &P) -> io::Result<Vec<PathBuf>> {
+    let mut empty_dirs = Vec::new();
+    for entry in Walker::new(root) {
+        let entry = entry?;
+        if entry.is_dir() && is_dir_empty(&entry.path())? {
+            empty_dirs.push(entry.path());
+        }
+    }
+    Ok(empty_dirs)
+}
+
+// Return whether `dir_path` is (or, if `dry_run`, would be) empty after
+// pruning its sub directories.
+fn prune(dir_path: &Path, dry_run: bool, pruned: &mut Vec<PathBuf>) -> io::Result<bool> {
+    let mut is_empty = true;
+    for e_entry in fs::read_dir(dir_path)? {
+        let dir_entry = e_entry?;
+        if dir_entry.file_type()?.is_dir() {
+            let path = dir_entry.path();
+            if prune(&path, dry_run, pruned)? {
+                if !dry_run {
+                    fs::remove_dir(&path)?;
+                }
+                pruned.push(path);
+                continue;
+            }
+        }
+        is_empty = false;
+    }
+    Ok(is_empty)
+}
+
+/// Remove directories below `root` that are empty or only contain empty
+/// directories (bottom up) and return their paths in the order that they
+/// were removed.  If `dry_run` is `true` nothing is removed but the result
+/// is the same.  `root` itself is never removed.
+pub fn prune_empty_dirs<P: AsRef<Path>>(root: &P, dry_run: bool) -> io::Result<Vec<PathBuf>> {
+    let mut pruned = Vec::new();
+    prune(root.as_ref(), dry_run, &mut pruned)?;
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn empty_dirs_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("d/e")).unwrap();
+        fs::write(root.join("d/file"), "").unwrap();
+
+        assert!(!is_dir_empty(&root).unwrap());
+        assert!(is_dir_empty(&root.join("a/b/c")).unwrap());
+        let mut empty_dirs = find_empty_dirs(&root).unwrap();
+        empty_dirs.sort();
+        assert_eq!(empty_dirs, vec![root.join("a/b/c"), root.join("d/e")]);
+
+        let mut would_prune = prune_empty_dirs(&root, true).unwrap();
+        assert_eq!(would_prune.len(), 4);
+        assert!(root.join("a/b/c").is_dir());
+        let mut pruned = prune_empty_dirs(&root, false).unwrap();
+        would_prune.sort();
+        pruned.sort();
+        assert_eq!(pruned, would_prune);
+        assert!(!root.join("a").exists());
+        assert!(!root.join("d/e").exists());
+        assert!(root.join("d/file").exists());
+    }
+
+    #[test]
+    fn is_dir_empty_with_works() {
+        use fs_provider::MemFs;
+
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/a/empty")).unwrap();
+        assert!(is_dir_empty_with(&fs, &"/a/empty").unwrap());
+        assert!(!is_dir_empty_with(&fs, &"/a").unwrap());
+        assert!(is_dir_empty_with(&fs, &"/missing").is_err());
+    }
+}
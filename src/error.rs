@@ -0,0 +1,100 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide the error type for operations that can fail for
+//! reasons other than I/O errors.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum PathuxError {
+    Io(io::Error),
+    /// The path is absolute (or has a drive/UNC prefix) where a relative
+    /// path is required.
+    NotRelative(String),
+    /// The path is relative where an absolute path is required.
+    NotAbsolute(String),
+    /// The path would lead outside of the directory it must stay within.
+    Escapes(String),
+    /// The name can't be used as a file name (as is).
+    InvalidName(String),
+    /// The text isn't a valid (file) URL.
+    InvalidUrl(String),
+    /// The text isn't validly percent encoded.
+    InvalidEncoding(String),
+    /// The text isn't a valid path template.
+    InvalidTemplate(String),
+    /// No value was supplied for the named template placeholder.
+    MissingValue(String),
+    /// The text isn't a valid (single) path component or prefix.
+    InvalidComponent(String),
+    /// The home directory couldn't be found.
+    NoHome,
+    /// The current directory couldn't be found.
+    NoCwd(io::Error),
+    /// The path isn't under the directory it must be relative to.
+    NotUnderBase(String),
+    /// The text isn't a valid bind mount ("host:container") specification.
+    InvalidMount(String),
+}
+
+impl fmt::Display for PathuxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathuxError::Io(err) => write!(f, "{}", err),
+            PathuxError::NotRelative(path) => write!(f, "{}: path is not relative", path),
+            PathuxError::NotAbsolute(path) => write!(f, "{}: path is not absolute", path),
+            PathuxError::Escapes(path) => write!(f, "{}: path escapes its base directory", path),
+            PathuxError::InvalidName(name) => write!(f, "{:?}: not a valid file name", name),
+            PathuxError::InvalidUrl(url) => write!(f, "{}: not a valid file URL", url),
+            PathuxError::InvalidEncoding(text) => write!(f, "{}: invalid percent encoding", text),
+            PathuxError::InvalidTemplate(text) => write!(f, "{}: invalid path template", text),
+            PathuxError::MissingValue(name) => write!(f, "{}: no value for placeholder", name),
+            PathuxError::InvalidComponent(text) => {
+                write!(f, "{:?}: not a valid path component", text)
+            }
+            PathuxError::NoHome => write!(f, "could not find home directory"),
+            PathuxError::NoCwd(err) => write!(f, "could not find current directory: {}", err),
+            PathuxError::NotUnderBase(path) => write!(f, "{}: not under base directory", path),
+            PathuxError::InvalidMount(spec) => write!(f, "{:?}: not a valid bind mount", spec),
+        }
+    }
+}
+
+impl error::Error for PathuxError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PathuxError::Io(err) | PathuxError::NoCwd(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PathuxError {
+    fn from(err: io::Error) -> PathuxError {
+        PathuxError::Io(err)
+    }
+}
+
+impl From<PathuxError> for io::Error {
+    fn from(err: PathuxError) -> io::Error {
+        match err {
+            PathuxError::Io(err) => err,
+            PathuxError::NoHome => io::Error::new(io::ErrorKind::NotFound, err.to_string()),
+            _ => io::Error::new(io::ErrorKind::InvalidInput, err.to_string()),
+        }
+    }
+}
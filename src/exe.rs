@@ -0,0 +1,55 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide the location of the running executable and of
+//! resources installed alongside it.
+
+use std::env;
+use std::io;
+use std::path::PathBuf;
+
+use super::path_to_string;
+
+/// The directory containing the running executable (with symbolic links
+/// resolved so that it is the real location of the executable).
+pub fn exe_dir() -> io::Result<String> {
+    let exe = env::current_exe()?.canonicalize()?;
+    match exe.parent().and_then(|dir| dir.to_str()) {
+        Some(dir) => Ok(dir.to_string()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "executable's directory path is not valid UTF-8",
+        )),
+    }
+}
+
+/// The path `rel` relative to the executable's directory e.g. for
+/// locating resources installed alongside it.
+pub fn exe_relative(rel: &str) -> io::Result<String> {
+    Ok(path_to_string(&PathBuf::from(exe_dir()?).join(rel)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exe_dir_works() {
+        let exe = env::current_exe().unwrap().canonicalize().unwrap();
+        let dir = exe_dir().unwrap();
+        assert_eq!(Some(PathBuf::from(&dir).as_path()), exe.parent());
+        let file_name = exe.file_name().unwrap().to_str().unwrap();
+        assert_eq!(PathBuf::from(exe_relative(file_name).unwrap()), exe);
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide conversion between (native) absolute paths and
+//! `file://` URLs such as those supplied by drag and drop.
+
+use super::error::PathuxError;
+#[cfg(windows)]
+use super::long_path::from_extended_length;
+use super::percent_encoding::{percent_decode_path, percent_encode_path};
+
+/// Return the `file://` URL for the absolute path `path`.  On Windows
+/// drive paths give URLs of the form "file:///C:/dir" and UNC paths
+/// "file://server/share/dir".
+pub fn path_to_file_url(path: &str) -> Result<String, PathuxError> {
+    #[cfg(windows)]
+    {
+        let text = from_extended_length(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let bytes = text.as_bytes();
+        if let Some(unc) = text.strip_prefix("//") {
+            let (host, rest) = unc.split_at(unc.find('/').unwrap_or(unc.len()));
+            if !host.is_empty() {
+                return Ok(format!("file://{}{}", host, percent_encode_path(rest)));
+            }
+        } else if bytes.len() > 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && bytes[2] == b'/'
+        {
+            return Ok(format!("file:///{}", percent_encode_path(&text)));
+        }
+        Err(PathuxError::NotAbsolute(path.to_string()))
+    }
+    #[cfg(not(windows))]
+    {
+        if path.starts_with('/') {
+            Ok(format!("file://{}", percent_encode_path(path)))
+        } else {
+            Err(PathuxError::NotAbsolute(path.to_string()))
+        }
+    }
+}
+
+/// Return the (native) path for the `file:` URL `url`.  URLs with a host
+/// other than "localhost" give UNC paths.  URLs whose path contains a
+/// percent encoded separator ("%2F" or "%5C") or nul ("%00") are invalid.
+pub fn file_url_to_path(url: &str) -> Result<String, PathuxError> {
+    let invalid = || PathuxError::InvalidUrl(url.to_string());
+    let rest = match url.get(..5) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("file:") => &url[5..],
+        _ => return Err(invalid()),
+    };
+    let rest = &rest[..rest.find(['?', '#']).unwrap_or(rest.len())];
+    let (host, path) = if let Some(authority) = rest.strip_prefix("//") {
+        authority.split_at(authority.find('/').unwrap_or(authority.len()))
+    } else {
+        ("", rest)
+    };
+    if !path.starts_with('/') {
+        return Err(invalid());
+    }
+    let decoded = percent_decode_path(path).map_err(|_| invalid())?;
+    // encoded separators (e.g. "%2F") or nuls would change the path's meaning
+    let count = |text: &str, ch: char| text.matches(ch).count();
+    if ['/', '\\', '\0']
+        .iter()
+        .any(|&ch| count(&decoded, ch) != count(path, ch))
+    {
+        return Err(invalid());
+    }
+    let path = decoded;
+    let host = if host.eq_ignore_ascii_case("localhost") {
+        ""
+    } else {
+        host
+    };
+    #[cfg(windows)]
+    {
+        let bytes = path.as_bytes();
+        let is_drive = bytes.len() > 2
+            && bytes[1].is_ascii_alphabetic()
+            && (bytes[2] == b':' || bytes[2] == b'|')
+            && (bytes.len() == 3 || bytes[3] == b'/');
+        if !host.is_empty() {
+            Ok(format!(r"\\{}{}", host, path.replace('/', "\\")))
+        } else if is_drive {
+            let drive = format!("{}:{}", &path[1..2], &path[3..]);
+            let drive = if drive.len() == 2 { drive + "/" } else { drive };
+            Ok(drive.replace('/', "\\"))
+        } else {
+            Err(invalid())
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        if host.is_empty() {
+            Ok(path)
+        } else {
+            Ok(format!("//{}{}", host, path))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn file_urls_work() {
+        let url = path_to_file_url("/home/me/a b#1.txt").unwrap();
+        assert_eq!(url, "file:///home/me/a%20b%231.txt");
+        assert_eq!(file_url_to_path(&url).unwrap(), "/home/me/a b#1.txt");
+        assert!(path_to_file_url("relative").is_err());
+        assert_eq!(file_url_to_path("file:/x/y").unwrap(), "/x/y");
+        assert_eq!(file_url_to_path("FILE://localhost/x?q=1").unwrap(), "/x");
+        assert_eq!(
+            file_url_to_path("file://host/share/x").unwrap(),
+            "//host/share/x"
+        );
+        assert!(file_url_to_path("http://host/x").is_err());
+        assert!(file_url_to_path("file:x").is_err());
+    }
+
+    #[test]
+    fn file_urls_reject_encoded_separators() {
+        assert!(file_url_to_path("file:///a%2Fb").is_err());
+        assert!(file_url_to_path("file:///a%2fb").is_err());
+        assert!(file_url_to_path("file:///a%5Cb").is_err());
+        assert!(file_url_to_path("file:///a%00b").is_err());
+        assert!(file_url_to_path("file://host/a%2F..%2Fb").is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn file_urls_work() {
+        let url = path_to_file_url(r"C:\Users\me\a b.txt").unwrap();
+        assert_eq!(url, "file:///C:/Users/me/a%20b.txt");
+        assert_eq!(file_url_to_path(&url).unwrap(), r"C:\Users\me\a b.txt");
+        let url = path_to_file_url(r"\\server\share\x").unwrap();
+        assert_eq!(url, "file://server/share/x");
+        assert_eq!(file_url_to_path(&url).unwrap(), r"\\server\share\x");
+        assert!(path_to_file_url("relative").is_err());
+        assert_eq!(file_url_to_path("file:///c|").unwrap(), r"c:\");
+        assert!(file_url_to_path("file:///x").is_err());
+    }
+}
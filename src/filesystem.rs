@@ -0,0 +1,378 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide information about the file systems that paths
+//! live on.
+
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsStats {
+    /// Size of the file system in bytes.
+    pub total: u64,
+    /// Free bytes including those reserved for the super user.
+    pub free: u64,
+    /// Free bytes available to the caller.
+    pub available: u64,
+    /// The file system's type (e.g. "ext4" or "NTFS") if it can be determined.
+    pub fs_type: Option<String>,
+    pub read_only: bool,
+}
+
+/// Return statistics for the file system containing `path`.
+pub fn fs_stats<P: AsRef<Path>>(path: &P) -> io::Result<FsStats> {
+    imp::fs_stats(path.as_ref())
+}
+
+/// Return `true` if `path` is a directory on which a file system is
+/// mounted (or a volume's root directory on Windows).
+pub fn is_mount_point<P: AsRef<Path>>(path: &P) -> io::Result<bool> {
+    imp::is_mount_point(path.as_ref())
+}
+
+/// Return `true` if `a` and `b` are on the same file system, i.e. a file
+/// could be renamed from one to the other.  Symbolic links are followed.
+pub fn same_filesystem<A: AsRef<Path>, B: AsRef<Path>>(a: &A, b: &B) -> io::Result<bool> {
+    imp::same_filesystem(a.as_ref(), b.as_ref())
+}
+
+/// The kind of device a root directory (drive) lives on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveType {
+    Fixed,
+    Removable,
+    Network,
+    CdRom,
+    RamDisk,
+    Unknown,
+}
+
+/// Return the root directories of the file system: the available drives
+/// (e.g. "C:\\") on Windows and "/" elsewhere.
+pub fn list_roots() -> Vec<String> {
+    imp::list_roots()
+}
+
+/// Return the type of the drive that `path` lives on.  On unix this is
+/// worked out from the file system type so removable media can't be told
+/// apart from fixed disks.
+pub fn drive_type<P: AsRef<Path>>(path: &P) -> DriveType {
+    imp::drive_type(path.as_ref())
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs;
+    use std::io;
+    use std::mem;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    use libc;
+
+    use super::{DriveType, FsStats};
+    use sys::c_path;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn fs_type(c_path: &::std::ffi::CStr) -> Option<String> {
+        use std::convert::TryFrom;
+
+        let mut buf: libc::statfs = unsafe { mem::zeroed() };
+        if unsafe { libc::statfs(c_path.as_ptr(), &mut buf) } != 0 {
+            return None;
+        }
+        // the magic numbers are 32 bit but the type of `f_type` varies (and
+        // where it's an i32 the bigger ones come out negative)
+        let magic = match u32::try_from(buf.f_type) {
+            Ok(magic) => magic,
+            Err(_) => match i32::try_from(buf.f_type) {
+                Ok(magic) => magic as u32,
+                Err(_) => return Some(format!("{:#x}", buf.f_type)),
+            },
+        };
+        let name = match magic {
+            0xEF53 => "ext2/3/4",
+            0x9123_683E => "btrfs",
+            0x5846_5342 => "xfs",
+            0x2FC1_2FC1 => "zfs",
+            0xF2F5_2010 => "f2fs",
+            0x0102_1994 => "tmpfs",
+            0x8584_58F6 => "ramfs",
+            0x794C_7630 => "overlay",
+            0x7371_7368 => "squashfs",
+            0x9660 => "iso9660",
+            0x4D44 => "vfat",
+            0x2011_BAB0 => "exfat",
+            0x5346_544E => "ntfs",
+            0x6969 => "nfs",
+            0xFF53_4D42 => "cifs",
+            0xFE53_4D42 => "smb2",
+            0x6573_5546 => "fuse",
+            0x9FA0 => "proc",
+            0x6265_6572 => "sysfs",
+            0x1CD1 => "devpts",
+            0x6367_7270 => "cgroup2",
+            magic => return Some(format!("{:#x}", magic)),
+        };
+        Some(name.to_string())
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    fn fs_type(c_path: &::std::ffi::CStr) -> Option<String> {
+        let mut buf: libc::statfs = unsafe { mem::zeroed() };
+        if unsafe { libc::statfs(c_path.as_ptr(), &mut buf) } != 0 {
+            return None;
+        }
+        let name: Vec<u8> = buf
+            .f_fstypename
+            .iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as u8)
+            .collect();
+        Some(String::from_utf8_lossy(&name).into_owned())
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    fn fs_type(_c_path: &::std::ffi::CStr) -> Option<String> {
+        None
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    pub fn fs_stats(path: &Path) -> io::Result<FsStats> {
+        let c_path = c_path(path)?;
+        let mut buf: libc::statvfs = unsafe { mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut buf) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fragment_size = buf.f_frsize as u64;
+        Ok(FsStats {
+            total: buf.f_blocks as u64 * fragment_size,
+            free: buf.f_bfree as u64 * fragment_size,
+            available: buf.f_bavail as u64 * fragment_size,
+            fs_type: fs_type(&c_path),
+            read_only: buf.f_flag & libc::ST_RDONLY as libc::c_ulong != 0,
+        })
+    }
+
+    pub fn is_mount_point(path: &Path) -> io::Result<bool> {
+        let metadata = fs::symlink_metadata(path)?;
+        if !metadata.is_dir() {
+            return Ok(false);
+        }
+        let parent_metadata = fs::metadata(path.join(".."))?;
+        // the root directory is its own parent
+        Ok(metadata.dev() != parent_metadata.dev() || metadata.ino() == parent_metadata.ino())
+    }
+
+    pub fn same_filesystem(a: &Path, b: &Path) -> io::Result<bool> {
+        Ok(fs::metadata(a)?.dev() == fs::metadata(b)?.dev())
+    }
+
+    pub fn list_roots() -> Vec<String> {
+        vec!["/".to_string()]
+    }
+
+    pub fn drive_type(path: &Path) -> DriveType {
+        let fs_type = match c_path(path).ok().and_then(|c_path| fs_type(&c_path)) {
+            Some(fs_type) => fs_type,
+            None => return DriveType::Unknown,
+        };
+        match fs_type.as_str() {
+            "nfs" | "cifs" | "smb2" | "smbfs" | "afpfs" | "webdav" => DriveType::Network,
+            "tmpfs" | "ramfs" => DriveType::RamDisk,
+            "iso9660" | "cd9660" | "udf" => DriveType::CdRom,
+            _ => DriveType::Fixed,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::mem;
+    use std::path::Path;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, MAX_PATH};
+    use winapi::shared::ntdef::ULARGE_INTEGER;
+    use winapi::um::fileapi::{
+        GetDiskFreeSpaceExW, GetDriveTypeW, GetLogicalDrives, GetVolumeInformationW,
+        GetVolumePathNameW,
+    };
+    use winapi::um::winbase::{
+        DRIVE_CDROM, DRIVE_FIXED, DRIVE_RAMDISK, DRIVE_REMOTE, DRIVE_REMOVABLE,
+    };
+    use winapi::um::winnt::FILE_READ_ONLY_VOLUME;
+
+    use super::{DriveType, FsStats};
+    use sys::{from_wide, wide_path};
+
+    fn volume_path(path: &Path) -> io::Result<String> {
+        let wide = wide_path(path);
+        let mut volume = [0u16; MAX_PATH + 1];
+        let ok = unsafe {
+            GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as DWORD)
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(from_wide(&volume).to_lowercase())
+    }
+
+    pub fn fs_stats(path: &Path) -> io::Result<FsStats> {
+        let wide = wide_path(path);
+        let mut available: ULARGE_INTEGER = unsafe { mem::zeroed() };
+        let mut total: ULARGE_INTEGER = unsafe { mem::zeroed() };
+        let mut free: ULARGE_INTEGER = unsafe { mem::zeroed() };
+        let ok =
+            unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut volume = [0u16; MAX_PATH + 1];
+        let mut fs_name = [0u16; MAX_PATH + 1];
+        let mut flags: DWORD = 0;
+        let ok = unsafe {
+            GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as DWORD) != 0
+                && GetVolumeInformationW(
+                    volume.as_ptr(),
+                    ptr::null_mut(),
+                    0,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    &mut flags,
+                    fs_name.as_mut_ptr(),
+                    fs_name.len() as DWORD,
+                ) != 0
+        };
+        Ok(FsStats {
+            total: unsafe { *total.QuadPart() },
+            free: unsafe { *free.QuadPart() },
+            available: unsafe { *available.QuadPart() },
+            fs_type: if ok { Some(from_wide(&fs_name)) } else { None },
+            read_only: ok && flags & FILE_READ_ONLY_VOLUME != 0,
+        })
+    }
+
+    pub fn is_mount_point(path: &Path) -> io::Result<bool> {
+        let abs_path = path.canonicalize()?;
+        let volume = volume_path(&abs_path)?;
+        let mut abs_path = abs_path.to_string_lossy().to_lowercase();
+        if !abs_path.ends_with('\\') {
+            abs_path.push('\\');
+        }
+        Ok(abs_path.trim_start_matches(r"\\?\") == volume.trim_start_matches(r"\\?\"))
+    }
+
+    pub fn same_filesystem(a: &Path, b: &Path) -> io::Result<bool> {
+        Ok(volume_path(&a.canonicalize()?)? == volume_path(&b.canonicalize()?)?)
+    }
+
+    pub fn list_roots() -> Vec<String> {
+        let drives = unsafe { GetLogicalDrives() };
+        (0..26)
+            .filter(|bit| drives & (1 << bit) != 0)
+            .map(|bit| format!("{}:\\", (b'A' + bit as u8) as char))
+            .collect()
+    }
+
+    pub fn drive_type(path: &Path) -> DriveType {
+        let volume = match volume_path(path) {
+            Ok(volume) => volume,
+            Err(_) => return DriveType::Unknown,
+        };
+        let wide: Vec<u16> = volume.encode_utf16().chain(Some(0)).collect();
+        match unsafe { GetDriveTypeW(wide.as_ptr()) } {
+            DRIVE_FIXED => DriveType::Fixed,
+            DRIVE_REMOVABLE => DriveType::Removable,
+            DRIVE_REMOTE => DriveType::Network,
+            DRIVE_CDROM => DriveType::CdRom,
+            DRIVE_RAMDISK => DriveType::RamDisk,
+            _ => DriveType::Unknown,
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    use super::{DriveType, FsStats};
+
+    pub fn fs_stats(_path: &Path) -> io::Result<FsStats> {
+        Err(io::Error::other(
+            "file system statistics are not available on this platform",
+        ))
+    }
+
+    pub fn is_mount_point(_path: &Path) -> io::Result<bool> {
+        Err(io::Error::other(
+            "mount points can't be detected on this platform",
+        ))
+    }
+
+    pub fn same_filesystem(_a: &Path, _b: &Path) -> io::Result<bool> {
+        Err(io::Error::other(
+            "file systems can't be compared on this platform",
+        ))
+    }
+
+    pub fn list_roots() -> Vec<String> {
+        vec!["/".to_string()]
+    }
+
+    pub fn drive_type(_path: &Path) -> DriveType {
+        DriveType::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fs_stats_works() {
+        let stats = fs_stats(&".").unwrap();
+        assert!(stats.total >= stats.free);
+        assert!(stats.free >= stats.available);
+        assert!(fs_stats(&"/this/does/not/exist").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mount_points_work() {
+        assert!(is_mount_point(&"/").unwrap());
+        assert!(!is_mount_point(&"src").unwrap());
+        assert!(!is_mount_point(&"Cargo.toml").unwrap());
+        assert!(same_filesystem(&"src", &"Cargo.toml").unwrap());
+        assert!(same_filesystem(&"/this/does/not/exist", &"src").is_err());
+    }
+
+    #[test]
+    fn list_roots_works() {
+        let roots = list_roots();
+        assert!(!roots.is_empty());
+        let cwd = std::env::current_dir().unwrap();
+        assert!(roots.iter().any(|root| cwd.starts_with(root)));
+        assert_ne!(drive_type(&"."), DriveType::Unknown);
+    }
+}
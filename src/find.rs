@@ -0,0 +1,236 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide a `find` like query layer on top of the walker.
+//!
+//! ```no_run
+//! use pw_pathux::find::find;
+//!
+//! for entry in find(&"src").name_glob("*.rs").min_size(1024).max_depth(4) {
+//!     println!("{}", entry.unwrap().path().display());
+//! }
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::glob::{glob_match, glob_match_with, GlobOptions};
+use super::times::Timestamps;
+use super::walk::{WalkEntry, WalkError, Walker};
+
+type Predicate = Box<dyn Fn(&WalkEntry) -> io::Result<bool>>;
+
+/// A query builder returned by `find()`.  An entry is yielded only if it
+/// satisfies all of the conditions that have been added.
+pub struct Find {
+    root: PathBuf,
+    max_depth: Option<usize>,
+    min_depth: usize,
+    predicates: Vec<Predicate>,
+}
+
+pub fn find<P: AsRef<Path>>(root: &P) -> Find {
+    Find {
+        root: root.as_ref().to_path_buf(),
+        max_depth: None,
+        min_depth: 1,
+        predicates: Vec::new(),
+    }
+}
+
+impl Find {
+    /// Add an arbitrary condition.
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&WalkEntry) -> io::Result<bool> + 'static,
+    {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Only entries whose file name matches the shell wild card `pattern`.
+    pub fn name_glob(self, pattern: &str) -> Self {
+        let pattern = pattern.to_string();
+        self.filter(move |entry| Ok(glob_match(&pattern, &entry.file_name())))
+    }
+
+    /// Like `name_glob()` but matching is modified by `options`.
+    pub fn name_glob_with(self, pattern: &str, options: &GlobOptions) -> Self {
+        let pattern = pattern.to_string();
+        let options = options.clone();
+        self.filter(move |entry| Ok(glob_match_with(&pattern, &entry.file_name(), &options)))
+    }
+
+    pub fn files_only(self) -> Self {
+        self.filter(|entry| Ok(entry.is_file()))
+    }
+
+    pub fn dirs_only(self) -> Self {
+        self.filter(|entry| Ok(entry.is_dir()))
+    }
+
+    /// Only entries whose length is at least `size` bytes.
+    pub fn min_size(self, size: u64) -> Self {
+        self.filter(move |entry| Ok(entry.metadata()?.len() >= size))
+    }
+
+    /// Only entries whose length is at most `size` bytes.
+    pub fn max_size(self, size: u64) -> Self {
+        self.filter(move |entry| Ok(entry.metadata()?.len() <= size))
+    }
+
+    /// Only entries modified no more than `duration` ago.
+    pub fn modified_within(self, duration: Duration) -> Self {
+        self.filter(move |entry| {
+            Ok(Timestamps::from_metadata(&entry.metadata()?)?.age() <= duration)
+        })
+    }
+
+    /// Only entries modified more than `duration` ago.
+    pub fn modified_before(self, duration: Duration) -> Self {
+        self.filter(
+            move |entry| Ok(Timestamps::from_metadata(&entry.metadata()?)?.age() > duration),
+        )
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn min_depth(mut self, min_depth: usize) -> Self {
+        self.min_depth = min_depth;
+        self
+    }
+}
+
+pub struct FindIter {
+    walker: Walker,
+    min_depth: usize,
+    predicates: Vec<Predicate>,
+}
+
+impl Iterator for FindIter {
+    type Item = Result<WalkEntry, WalkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        'entries: loop {
+            let entry = match self.walker.next()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+            if entry.depth() < self.min_depth {
+                continue;
+            }
+            for predicate in self.predicates.iter() {
+                match predicate(&entry) {
+                    Ok(true) => (),
+                    Ok(false) => continue 'entries,
+                    Err(err) => return Some(Err(WalkError::Io(entry.path(), err))),
+                }
+            }
+            return Some(Ok(entry));
+        }
+    }
+}
+
+impl IntoIterator for Find {
+    type Item = Result<WalkEntry, WalkError>;
+    type IntoIter = FindIter;
+
+    fn into_iter(self) -> FindIter {
+        let mut walker = Walker::new(&self.root);
+        if let Some(max_depth) = self.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        FindIter {
+            walker,
+            min_depth: self.min_depth,
+            predicates: self.predicates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    fn found(query: Find, root: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = query
+            .into_iter()
+            .map(|e| e.unwrap().path().strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn find_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::write(root.join("src/lib.rs"), vec![b'x'; 2000]).unwrap();
+        fs::write(root.join("src/bin/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("README.md"), "").unwrap();
+
+        assert_eq!(
+            found(find(&root).name_glob("*.rs"), root),
+            vec![
+                PathBuf::from("src/bin/main.rs"),
+                PathBuf::from("src/lib.rs")
+            ]
+        );
+        assert_eq!(
+            found(find(&root).name_glob("*.rs").min_size(1024), root),
+            vec![PathBuf::from("src/lib.rs")]
+        );
+        assert_eq!(
+            found(find(&root).name_glob("*.rs").max_depth(2), root),
+            vec![PathBuf::from("src/lib.rs")]
+        );
+        assert_eq!(
+            found(find(&root).dirs_only().min_depth(2), root),
+            vec![PathBuf::from("src/bin")]
+        );
+        assert_eq!(
+            found(
+                find(&root)
+                    .files_only()
+                    .modified_within(Duration::from_secs(3600)),
+                root
+            )
+            .len(),
+            3
+        );
+        assert!(found(find(&root).modified_before(Duration::from_secs(3600)), root).is_empty());
+
+        fs::write(root.join("re\u{301}sume\u{301}.txt"), "").unwrap();
+        let options = GlobOptions {
+            normalize_unicode: true,
+        };
+        assert_eq!(
+            found(
+                find(&root).name_glob_with("r\u{e9}sum\u{e9}*", &options),
+                root
+            )
+            .len(),
+            1
+        );
+    }
+}
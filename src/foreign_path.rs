@@ -0,0 +1,409 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide `str_path` style operations on paths that use an
+//! explicit separator rather than `MAIN_SEPARATOR` (e.g. Windows paths
+//! stored in data files being handled on Linux and vice versa).
+//!
+//! Everything is purely textual so it behaves the same way on all
+//! platforms.
+
+use std::str::FromStr;
+
+use super::str_path::{StrPathComponent, StrPathPrefix};
+
+/// The path syntax that the operations use: POSIX ('/' separated) or
+/// Windows ('\' separated, '/' also accepted, with prefixes such as "C:"
+/// and "\\server\share").
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForeignPath {
+    separator: char,
+}
+
+impl ForeignPath {
+    pub fn posix() -> Self {
+        ForeignPath { separator: '/' }
+    }
+
+    pub fn windows() -> Self {
+        ForeignPath { separator: '\\' }
+    }
+
+    /// The syntax of the platform we're running on.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Self::windows()
+        } else {
+            Self::posix()
+        }
+    }
+
+    /// The syntax using `separator` which must be '/' or '\'.
+    pub fn from_separator(separator: char) -> Option<Self> {
+        match separator {
+            '/' => Some(Self::posix()),
+            '\\' => Some(Self::windows()),
+            _ => None,
+        }
+    }
+
+    pub fn separator(&self) -> char {
+        self.separator
+    }
+
+    pub fn is_windows(&self) -> bool {
+        self.separator == '\\'
+    }
+
+    fn is_separator(&self, c: char) -> bool {
+        c == '/' || (self.is_windows() && c == '\\')
+    }
+
+    // Split `path` after its prefix (if any).  Within verbatim ("\\?\")
+    // paths only '\' is a separator.
+    fn split_prefix<'a>(&self, path: &'a str) -> (Option<StrPathPrefix>, &'a str, bool) {
+        if !self.is_windows() {
+            return (None, path, false);
+        }
+        let is_separator = |c: char| c == '\\' || c == '/';
+        // the end of the `n` fields of `path` starting at `start`
+        let end_of_fields = |start: usize, n: usize, verbatim: bool| {
+            let mut end = start;
+            for i in 0..n {
+                let field_start = if i == 0 { end } else { end + 1 };
+                if field_start > path.len() {
+                    break;
+                }
+                end = path[field_start..]
+                    .find(|c| c == '\\' || (!verbatim && c == '/'))
+                    .map_or(path.len(), |len| field_start + len);
+            }
+            end
+        };
+        let (end, verbatim) = if path.starts_with(r"\\?\UNC\") {
+            (end_of_fields(8, 2, true), true)
+        } else if path.starts_with(r"\\?\") {
+            (end_of_fields(4, 1, true), true)
+        } else if path.starts_with(r"\\.\") {
+            (end_of_fields(4, 1, false), false)
+        } else if path.len() > 2
+            && path.starts_with(is_separator)
+            && path[1..].starts_with(is_separator)
+        {
+            (end_of_fields(2, 2, false), false)
+        } else if path.as_bytes().get(1) == Some(&b':') {
+            (2, false)
+        } else {
+            return (None, path, false);
+        };
+        match StrPathPrefix::from_str(&path[..end].replace('/', "\\")) {
+            Ok(prefix) => (Some(prefix), &path[end..], verbatim),
+            Err(_) => (None, path, false),
+        }
+    }
+
+    /// The components of `path` with a leading "~" as
+    /// `StrPathComponent::HomeDir`.  As with `std::path::Path`, repeated
+    /// separators and "." components (other than a leading one) are
+    /// ignored.
+    pub fn components(&self, path: &str) -> Vec<StrPathComponent> {
+        let (prefix, rest, verbatim) = self.split_prefix(path);
+        let mut components = Vec::new();
+        let has_prefix = prefix.is_some();
+        if let Some(prefix) = prefix {
+            components.push(StrPathComponent::Prefix(prefix));
+        }
+        let is_separator = |c: char| {
+            if verbatim {
+                c == '\\'
+            } else {
+                self.is_separator(c)
+            }
+        };
+        let has_root = rest.starts_with(is_separator);
+        if has_root {
+            components.push(StrPathComponent::RootDir);
+        }
+        for (i, part) in rest
+            .split(is_separator)
+            .filter(|p| !p.is_empty())
+            .enumerate()
+        {
+            let is_first = i == 0 && !has_prefix && !has_root;
+            components.push(match part {
+                "." if is_first => StrPathComponent::CurDir,
+                "." => continue,
+                ".." => StrPathComponent::ParentDir,
+                "~" if is_first => StrPathComponent::HomeDir,
+                _ => StrPathComponent::Normal(part.to_string()),
+            });
+        }
+        components
+    }
+
+    /// The path made up of `components` (using this syntax's separator).
+    pub fn path_from_components(&self, components: &[StrPathComponent]) -> String {
+        let mut path = String::new();
+        let mut needs_separator = false;
+        for component in components {
+            match component {
+                // a drive relative path (e.g. "C:foo") has no separator
+                // after its prefix
+                StrPathComponent::Prefix(prefix) => {
+                    path.push_str(&prefix.to_string());
+                    needs_separator = false;
+                    continue;
+                }
+                StrPathComponent::RootDir => {
+                    path.push(self.separator);
+                    needs_separator = false;
+                    continue;
+                }
+                component => {
+                    if needs_separator {
+                        path.push(self.separator);
+                    }
+                    path.push_str(&component.to_string());
+                }
+            }
+            needs_separator = true;
+        }
+        path
+    }
+
+    /// Rewrite `path` using this syntax's separator only (e.g. "a/b\c"
+    /// becomes "a\b\c" for Windows).
+    pub fn normalize_separators(&self, path: &str) -> String {
+        self.path_from_components(&self.components(path))
+    }
+
+    /// Resolve "." and ".." components of `path` lexically ("." if
+    /// nothing is left).  ".." at the root is dropped.
+    pub fn normalize(&self, path: &str) -> String {
+        let mut components: Vec<StrPathComponent> = Vec::new();
+        for component in self.components(path) {
+            match component {
+                StrPathComponent::CurDir => (),
+                StrPathComponent::ParentDir => match components.last() {
+                    Some(StrPathComponent::Normal(_)) => {
+                        components.pop();
+                    }
+                    Some(StrPathComponent::RootDir) => (),
+                    Some(StrPathComponent::Prefix(_)) if self.is_absolute(path) => (),
+                    _ => components.push(component),
+                },
+                component => components.push(component),
+            }
+        }
+        if components.is_empty() {
+            ".".to_string()
+        } else {
+            self.path_from_components(&components)
+        }
+    }
+
+    /// Rewrite `path` (in this syntax) in the syntax `other`.
+    pub fn translate(&self, path: &str, other: &ForeignPath) -> String {
+        other.path_from_components(&self.components(path))
+    }
+
+    /// Paths starting with "~" are neither absolute nor relative.  Windows
+    /// paths need a prefix (drive or share) as well as a root directory.
+    pub fn is_absolute(&self, path: &str) -> bool {
+        let components = self.components(path);
+        match components.first() {
+            Some(StrPathComponent::RootDir) => !self.is_windows(),
+            Some(StrPathComponent::Prefix(StrPathPrefix::Disk(_))) => {
+                components.get(1) == Some(&StrPathComponent::RootDir)
+            }
+            Some(StrPathComponent::Prefix(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_relative(&self, path: &str) -> bool {
+        !self.is_absolute(path) && !self.is_relative_to_home(path)
+    }
+
+    pub fn is_relative_to_home(&self, path: &str) -> bool {
+        self.components(path).first() == Some(&StrPathComponent::HomeDir)
+    }
+
+    pub fn file_name(&self, path: &str) -> Option<String> {
+        match self.components(path).pop() {
+            Some(StrPathComponent::Normal(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The path without its last component ("" for a single relative
+    /// component) or `None` if it's a root (or empty).
+    pub fn parent(&self, path: &str) -> Option<String> {
+        let mut components = self.components(path);
+        match components.pop() {
+            Some(StrPathComponent::Prefix(_)) | Some(StrPathComponent::RootDir) | None => None,
+            Some(_) => Some(self.path_from_components(&components)),
+        }
+    }
+
+    /// `path` with `other` appended (or `other` if it is absolute or, on
+    /// Windows, has a prefix).  If `other` has a root (but no prefix) on
+    /// Windows it replaces everything but the prefix of `path`.
+    pub fn join(&self, path: &str, other: &str) -> String {
+        let mut components = self.components(path);
+        let others = self.components(other);
+        match others.first() {
+            Some(StrPathComponent::Prefix(_)) => return self.path_from_components(&others),
+            Some(StrPathComponent::RootDir) => {
+                components.retain(|c| matches!(c, StrPathComponent::Prefix(_)));
+            }
+            _ => (),
+        }
+        components.extend(others);
+        self.path_from_components(&components)
+    }
+
+    pub fn with_file_name(&self, path: &str, name: &str) -> String {
+        match self.parent(path) {
+            Some(parent) => self.join(&parent, name),
+            None => self.join(path, name),
+        }
+    }
+
+    /// Remove `base` from the start of `path` comparing by component.
+    /// Windows paths are compared ignoring case.
+    pub fn strip_prefix(&self, path: &str, base: &str) -> Option<String> {
+        let components = self.components(path);
+        let base = self.components(base);
+        if base.len() > components.len() {
+            return None;
+        }
+        let same = |a: &StrPathComponent, b: &StrPathComponent| {
+            if self.is_windows() {
+                a.to_string().to_lowercase() == b.to_string().to_lowercase()
+            } else {
+                a == b
+            }
+        };
+        if components.iter().zip(base.iter()).all(|(a, b)| same(a, b)) {
+            Some(self.path_from_components(&components[base.len()..]))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_work() {
+        let windows = ForeignPath::windows();
+        assert_eq!(
+            windows.components(r"C:\a/b\\.\c"),
+            vec![
+                StrPathComponent::Prefix(StrPathPrefix::Disk(b'C')),
+                StrPathComponent::RootDir,
+                StrPathComponent::Normal("a".to_string()),
+                StrPathComponent::Normal("b".to_string()),
+                StrPathComponent::Normal("c".to_string()),
+            ]
+        );
+        assert_eq!(
+            windows.components(r"\\server\share\x")[0],
+            StrPathComponent::Prefix(StrPathPrefix::UNC(
+                "server".to_string(),
+                "share".to_string()
+            ))
+        );
+        assert_eq!(
+            windows.components(r"\\?\C:\a/b").last(),
+            Some(&StrPathComponent::Normal("a/b".to_string()))
+        );
+        let posix = ForeignPath::posix();
+        assert_eq!(
+            posix.components(r"./~/a\b/../"),
+            vec![
+                StrPathComponent::CurDir,
+                StrPathComponent::Normal("~".to_string()),
+                StrPathComponent::Normal(r"a\b".to_string()),
+                StrPathComponent::ParentDir,
+            ]
+        );
+        assert_eq!(posix.components("~/x")[0], StrPathComponent::HomeDir);
+        assert_eq!(
+            posix.components("C:/x")[0],
+            StrPathComponent::Normal("C:".to_string())
+        );
+    }
+
+    #[test]
+    fn foreign_path_operations_work() {
+        let windows = ForeignPath::windows();
+        let posix = ForeignPath::posix();
+        assert_eq!(ForeignPath::from_separator('\\'), Some(windows));
+        assert_eq!(ForeignPath::from_separator(':'), None);
+        assert!(windows.is_absolute(r"C:\x"));
+        assert!(windows.is_absolute(r"\\server\share"));
+        assert!(!windows.is_absolute(r"\x"));
+        assert!(!windows.is_absolute("C:x"));
+        assert!(posix.is_absolute("/x"));
+        assert!(posix.is_relative("x/y"));
+        assert!(windows.is_relative_to_home(r"~\x"));
+        assert!(!posix.is_relative("~/x"));
+
+        assert_eq!(windows.join(r"C:\a", "b/c"), r"C:\a\b\c");
+        assert_eq!(windows.join(r"C:\a", r"\b"), r"C:\b");
+        assert_eq!(windows.join(r"C:\a", r"D:\b"), r"D:\b");
+        assert_eq!(windows.join("C:", "x"), "C:x");
+        assert_eq!(windows.join("C:foo", "x"), r"C:foo\x");
+        assert_eq!(windows.join("C:", r"\x"), r"C:\x");
+        assert_eq!(windows.normalize_separators("C:foo"), "C:foo");
+        assert_eq!(windows.normalize_separators("C:"), "C:");
+        assert_eq!(windows.normalize("C:foo/./bar"), r"C:foo\bar");
+        assert_eq!(posix.join("/a", "b"), "/a/b");
+        assert_eq!(posix.join("/a", "/b"), "/b");
+        assert_eq!(posix.join("", "b"), "b");
+
+        assert_eq!(windows.parent(r"C:\a\b"), Some(r"C:\a".to_string()));
+        assert_eq!(windows.parent(r"C:\a"), Some(r"C:\".to_string()));
+        assert_eq!(windows.parent(r"C:\"), None);
+        assert_eq!(windows.parent("C:foo"), Some("C:".to_string()));
+        assert_eq!(windows.parent("C:"), None);
+        assert_eq!(posix.parent("a"), Some("".to_string()));
+        assert_eq!(posix.parent("/"), None);
+        assert_eq!(windows.file_name(r"C:\a\b.txt"), Some("b.txt".to_string()));
+        assert_eq!(posix.file_name("a/.."), None);
+        assert_eq!(posix.with_file_name("a/b", "c"), "a/c");
+
+        assert_eq!(
+            windows.strip_prefix(r"c:\Users\Me\x", r"C:\users\me"),
+            Some("x".to_string())
+        );
+        assert_eq!(posix.strip_prefix("/a/bc", "/a/b"), None);
+        assert_eq!(windows.normalize_separators("a/b"), r"a\b");
+        assert_eq!(posix.normalize("./a/./b/../c/"), "a/c");
+        assert_eq!(posix.normalize("/../a"), "/a");
+        assert_eq!(posix.normalize("a/../../b"), "../b");
+        assert_eq!(posix.normalize("a/.."), ".");
+        assert_eq!(windows.normalize(r"C:\a\..\..\b"), r"C:\b");
+        assert_eq!(
+            windows.normalize(r"\\server\share\.."),
+            "\\\\server\\share\\"
+        );
+        assert_eq!(windows.translate(r"a\b\c", &posix), "a/b/c");
+        assert_eq!(posix.translate("/a/b", &windows), r"\a\b");
+    }
+}
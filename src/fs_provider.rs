@@ -0,0 +1,1070 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide a pluggable file system abstraction so that path
+//! handling logic can be exercised against an in memory file system
+//! (`MemFs`) in tests and against the real one (`RealFs`) otherwise.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, FileType};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+use std::vec;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+#[cfg(feature = "acl")]
+use super::acl::copy_acl;
+use super::durable::{sync_dir, sync_file};
+use super::filesystem::same_filesystem;
+use super::sparse::copy_sparse;
+use super::symlink::make_symlink;
+use super::times::copy_times;
+#[cfg(unix)]
+use super::xattr::copy_xattrs;
+use super::UsableDirEntry;
+
+/// The type of a directory entry (symbolic links are not followed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
+    Other,
+}
+
+impl EntryKind {
+    #[cfg(unix)]
+    pub(crate) fn from_file_type(file_type: FileType) -> EntryKind {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_file() {
+            EntryKind::File
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_block_device() {
+            EntryKind::BlockDevice
+        } else if file_type.is_char_device() {
+            EntryKind::CharDevice
+        } else if file_type.is_fifo() {
+            EntryKind::Fifo
+        } else if file_type.is_socket() {
+            EntryKind::Socket
+        } else {
+            EntryKind::Other
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn from_file_type(file_type: FileType) -> EntryKind {
+        if file_type.is_file() {
+            EntryKind::File
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::Other
+        }
+    }
+}
+
+/// The subset of metadata supported by all providers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsMetadata {
+    pub kind: EntryKind,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FsMetadata {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == EntryKind::Symlink
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsEntry {
+    path: PathBuf,
+    kind: EntryKind,
+}
+
+impl FsEntry {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The type of the entry itself (symbolic links are not followed).
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == EntryKind::Symlink
+    }
+}
+
+/// The entries of a directory as yielded by `FsProvider::open_dir()`.
+pub trait FsDirEntry {
+    fn path(&self) -> PathBuf;
+    fn name(&self) -> OsString;
+    /// The type of the entry (or of its target if it has been followed).
+    fn kind(&self) -> EntryKind;
+
+    fn is_dir(&self) -> bool {
+        self.kind() == EntryKind::Dir
+    }
+
+    fn is_file(&self) -> bool {
+        self.kind() == EntryKind::File
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.kind() == EntryKind::Symlink
+    }
+}
+
+impl FsDirEntry for FsEntry {
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn name(&self) -> OsString {
+        self.path
+            .file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_default()
+    }
+
+    fn kind(&self) -> EntryKind {
+        self.kind
+    }
+}
+
+impl FsDirEntry for UsableDirEntry {
+    fn path(&self) -> PathBuf {
+        self.dir_entry.path()
+    }
+
+    fn name(&self) -> OsString {
+        self.dir_entry.file_name()
+    }
+
+    fn kind(&self) -> EntryKind {
+        EntryKind::from_file_type(self.file_type)
+    }
+}
+
+/// Identifies a file independently of the path used to reach it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FileId {
+    /// Device and inode numbers.
+    Inode(u64, u64),
+    /// The canonical path.
+    Path(PathBuf),
+}
+
+/// The file system operations used by the `_with` variants of this
+/// crate's functions (and by `Walker`).
+pub trait FsProvider {
+    type DirEntry: FsDirEntry;
+    type ReadDir: Iterator<Item = io::Result<Self::DirEntry>>;
+
+    /// An iterator over the entries of the directory at `path` (in no
+    /// particular order) which reads them on demand where possible.
+    fn open_dir(&self, path: &Path) -> io::Result<Self::ReadDir>;
+    /// Make `entry`, a symbolic link, describe the link's target instead.
+    /// Returns `false` (leaving `entry` unchanged) if the link dangles.
+    fn follow_symlink(&self, entry: &mut Self::DirEntry) -> bool;
+    /// The entries of the directory at `path` (in no particular order).
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>>;
+    /// Metadata for `path` (following symbolic links).
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata>;
+    /// Metadata for `path` itself.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FsMetadata>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Create (or truncate) the file at `path` with the given contents.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// The path of the file `path` refers to with all symbolic links
+    /// resolved (by default `path` itself if it exists).
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.symlink_metadata(path)?;
+        Ok(path.to_path_buf())
+    }
+
+    /// The identity of the file `path` refers to (by default its
+    /// canonical path).
+    fn file_id(&self, path: &Path) -> io::Result<FileId> {
+        self.canonicalize(path).map(FileId::Path)
+    }
+
+    /// Copy the contents of the file `from` to `to` returning the number
+    /// of bytes copied.
+    fn copy_file(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let contents = self.read(from)?;
+        self.write(to, &contents)?;
+        Ok(contents.len() as u64)
+    }
+
+    /// Like `copy_file()` but holes in sparse files are preserved (if the
+    /// file system supports them).
+    fn copy_file_sparse(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        self.copy_file(from, to)
+    }
+
+    /// Whether `a` and `b` are on the same (physical) file system.
+    fn same_filesystem(&self, _a: &Path, _b: &Path) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// Give `to` the same access and modification times as `from` (if
+    /// the file system records them).
+    fn copy_times(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Flush the file or directory `path` to stable storage (if the file
+    /// system has any).
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.symlink_metadata(path).map(|_| ())
+    }
+
+    /// Give `to` the same extended attributes as `from` (if the file
+    /// system has them).
+    fn copy_xattrs(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Give `to` the same access control list as `from`.
+    fn copy_acl(&self, _from: &Path, to: &Path) -> io::Result<()> {
+        Err(error(
+            io::ErrorKind::Unsupported,
+            to,
+            "access control lists are not supported",
+        ))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        if path.as_os_str().is_empty() || self.is_dir(path) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        match self.create_dir(path) {
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && self.is_dir(path) => {
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).map(|m| m.is_file()).unwrap_or(false)
+    }
+}
+
+/// The real file system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+fn fs_metadata(metadata: fs::Metadata) -> FsMetadata {
+    FsMetadata {
+        kind: EntryKind::from_file_type(metadata.file_type()),
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+    }
+}
+
+/// The iterator returned by `RealFs::open_dir()`.
+#[derive(Debug)]
+pub struct RealReadDir(fs::ReadDir);
+
+impl Iterator for RealReadDir {
+    type Item = io::Result<UsableDirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let dir_entry = match self.0.next()? {
+                Ok(dir_entry) => dir_entry,
+                Err(err) => return Some(Err(err)),
+            };
+            match dir_entry.file_type() {
+                Ok(file_type) => {
+                    return Some(Ok(UsableDirEntry {
+                        dir_entry,
+                        file_type,
+                    }))
+                }
+                // we assume that "not found" is due to a race condition and ignore it
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => {
+                    let what = err.to_string();
+                    return Some(Err(error(err.kind(), &dir_entry.path(), &what)));
+                }
+            }
+        }
+    }
+}
+
+impl FsProvider for RealFs {
+    type DirEntry = UsableDirEntry;
+    type ReadDir = RealReadDir;
+
+    fn open_dir(&self, path: &Path) -> io::Result<RealReadDir> {
+        fs::read_dir(path).map(RealReadDir)
+    }
+
+    fn follow_symlink(&self, entry: &mut UsableDirEntry) -> bool {
+        match fs::metadata(entry.dir_entry.path()) {
+            Ok(metadata) => {
+                entry.file_type = metadata.file_type();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>> {
+        let mut entries = Vec::new();
+        for e_entry in fs::read_dir(path)? {
+            let dir_entry = e_entry?;
+            match dir_entry.file_type() {
+                Ok(file_type) => entries.push(FsEntry {
+                    path: dir_entry.path(),
+                    kind: EntryKind::from_file_type(file_type),
+                }),
+                // we assume that "not found" is due to a race condition and ignore it
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        fs::metadata(path).map(fs_metadata)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        fs::symlink_metadata(path).map(fs_metadata)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        make_symlink(&target, &link)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    #[cfg(unix)]
+    fn file_id(&self, path: &Path) -> io::Result<FileId> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileId::Inode(metadata.dev(), metadata.ino()))
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn copy_file_sparse(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        copy_sparse(&from, &to)
+    }
+
+    fn same_filesystem(&self, a: &Path, b: &Path) -> io::Result<bool> {
+        same_filesystem(&a, &b)
+    }
+
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        copy_times(&from, &to)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            sync_dir(&path)
+        } else {
+            sync_file(&path)
+        }
+    }
+
+    #[cfg(unix)]
+    fn copy_xattrs(&self, from: &Path, to: &Path) -> io::Result<()> {
+        copy_xattrs(&from, &to)
+    }
+
+    #[cfg(feature = "acl")]
+    fn copy_acl(&self, from: &Path, to: &Path) -> io::Result<()> {
+        copy_acl(&from, &to)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir(SystemTime),
+    File(Vec<u8>, SystemTime),
+    Symlink(PathBuf),
+}
+
+/// An in memory file system (intended for tests).  Paths are used as
+/// given apart from the removal of "." components and the lexical
+/// resolution of ".." components so relative and absolute paths name
+/// different files.
+#[derive(Debug)]
+pub struct MemFs {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+    // directories given their own (synthetic) device by `set_device()`
+    devices: Mutex<BTreeMap<PathBuf, u64>>,
+}
+
+impl Default for MemFs {
+    fn default() -> Self {
+        MemFs::new()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{}: not found", path.display()),
+    )
+}
+
+fn error(kind: io::ErrorKind, path: &Path, what: &str) -> io::Error {
+    io::Error::new(kind, format!("{}: {}", path.display(), what))
+}
+
+fn mem_key(path: &Path) -> PathBuf {
+    let mut key = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if key.file_name().is_some() => {
+                key.pop();
+            }
+            // ".." at the root is the root
+            Component::ParentDir if key.has_root() => (),
+            component => key.push(component),
+        }
+    }
+    key
+}
+
+impl MemFs {
+    /// An empty file system containing only the root directory.
+    pub fn new() -> MemFs {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::from("/"), Node::Dir(SystemTime::now()));
+        MemFs {
+            nodes: Mutex::new(nodes),
+            devices: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Put the directory `path` and everything below it (that isn't
+    /// itself given a device) on the file system with id `device` as if
+    /// it were a mount point.  The root is on device zero.
+    pub fn set_device<P: AsRef<Path>>(&self, path: &P, device: u64) -> io::Result<()> {
+        let key = self.resolve(path.as_ref())?;
+        if !self.is_dir(&key) {
+            return Err(error(
+                io::ErrorKind::Other,
+                path.as_ref(),
+                "not a directory",
+            ));
+        }
+        let mut devices = self.devices.lock().unwrap_or_else(|err| err.into_inner());
+        devices.insert(key, device);
+        Ok(())
+    }
+
+    // The device of the node `path` refers to.
+    fn device(&self, path: &Path) -> io::Result<u64> {
+        let key = self.resolve(path)?;
+        let devices = self.devices.lock().unwrap_or_else(|err| err.into_inner());
+        Ok(key
+            .ancestors()
+            .find_map(|dir| devices.get(dir).cloned())
+            .unwrap_or(0))
+    }
+
+    fn nodes(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Check that `key`'s parent is an existing directory.
+    fn check_parent(nodes: &BTreeMap<PathBuf, Node>, key: &Path) -> io::Result<()> {
+        match key.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => match nodes.get(parent) {
+                Some(Node::Dir(_)) => Ok(()),
+                Some(_) => Err(error(io::ErrorKind::Other, parent, "not a directory")),
+                None => Err(not_found(parent)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // The key of the node that `path` refers to after following links in
+    // its directory components (and in its last component if `follow_last`).
+    fn lookup(
+        nodes: &BTreeMap<PathBuf, Node>,
+        path: &Path,
+        follow_last: bool,
+    ) -> io::Result<PathBuf> {
+        let mut key = mem_key(path);
+        for _ in 0..40 {
+            let mut prefix = PathBuf::new();
+            let mut components = key.components();
+            let mut link_key = None;
+            while let Some(component) = components.next() {
+                prefix.push(component);
+                let rest = components.as_path();
+                if rest.as_os_str().is_empty() && !follow_last {
+                    break;
+                }
+                if let Some(Node::Symlink(target)) = nodes.get(&prefix) {
+                    let base = prefix.parent().unwrap_or_else(|| Path::new(""));
+                    link_key = Some(mem_key(&base.join(target).join(rest)));
+                    break;
+                }
+            }
+            match link_key {
+                Some(link_key) => key = link_key,
+                None => return Ok(key),
+            }
+        }
+        Err(error(
+            io::ErrorKind::Other,
+            path,
+            "too many levels of symbolic links",
+        ))
+    }
+
+    // Insert a new node where nothing currently exists.
+    fn insert(&self, path: &Path, node: Node) -> io::Result<()> {
+        let mut nodes = self.nodes();
+        let key = MemFs::lookup(&nodes, path, false)?;
+        if nodes.contains_key(&key) {
+            return Err(error(io::ErrorKind::AlreadyExists, path, "already exists"));
+        }
+        MemFs::check_parent(&nodes, &key)?;
+        nodes.insert(key, node);
+        Ok(())
+    }
+
+    // The key of the node that `path` refers to after following links.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let nodes = self.nodes();
+        let key = MemFs::lookup(&nodes, path, true)?;
+        if nodes.contains_key(&key) {
+            Ok(key)
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    // The key of the node that is `path` itself.
+    fn key(&self, path: &Path) -> io::Result<PathBuf> {
+        MemFs::lookup(&self.nodes(), path, false)
+    }
+
+    fn node_metadata(node: &Node) -> FsMetadata {
+        match node {
+            Node::Dir(modified) => FsMetadata {
+                kind: EntryKind::Dir,
+                len: 0,
+                modified: Some(*modified),
+            },
+            Node::File(contents, modified) => FsMetadata {
+                kind: EntryKind::File,
+                len: contents.len() as u64,
+                modified: Some(*modified),
+            },
+            Node::Symlink(target) => FsMetadata {
+                kind: EntryKind::Symlink,
+                len: target.as_os_str().len() as u64,
+                modified: None,
+            },
+        }
+    }
+}
+
+impl FsProvider for MemFs {
+    type DirEntry = FsEntry;
+    type ReadDir = vec::IntoIter<io::Result<FsEntry>>;
+
+    fn open_dir(&self, path: &Path) -> io::Result<Self::ReadDir> {
+        let entries: Vec<io::Result<FsEntry>> = self.read_dir(path)?.into_iter().map(Ok).collect();
+        Ok(entries.into_iter())
+    }
+
+    fn follow_symlink(&self, entry: &mut FsEntry) -> bool {
+        match self.metadata(&entry.path) {
+            Ok(metadata) => {
+                entry.kind = metadata.kind;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>> {
+        let key = self.resolve(path)?;
+        let nodes = self.nodes();
+        match nodes.get(&key) {
+            Some(Node::Dir(_)) => (),
+            _ => return Err(error(io::ErrorKind::Other, path, "not a directory")),
+        }
+        let path = mem_key(path);
+        Ok(nodes
+            .iter()
+            .filter(|&(child, _)| child.parent() == Some(&key))
+            .map(|(child, node)| FsEntry {
+                path: path.join(child.file_name().unwrap_or_default()),
+                kind: MemFs::node_metadata(node).kind,
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        let key = self.resolve(path)?;
+        match self.nodes().get(&key) {
+            Some(node) => Ok(MemFs::node_metadata(node)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        let key = self.key(path)?;
+        match self.nodes().get(&key) {
+            Some(node) => Ok(MemFs::node_metadata(node)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let key = self.key(path)?;
+        match self.nodes().get(&key) {
+            Some(Node::Symlink(target)) => Ok(target.clone()),
+            Some(_) => Err(error(
+                io::ErrorKind::InvalidInput,
+                path,
+                "not a symbolic link",
+            )),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let key = self.resolve(path)?;
+        match self.nodes().get(&key) {
+            Some(Node::File(contents, _)) => Ok(contents.clone()),
+            Some(_) => Err(error(io::ErrorKind::Other, path, "is a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut nodes = self.nodes();
+        let key = MemFs::lookup(&nodes, path, true)?;
+        if let Some(Node::Dir(_)) = nodes.get(&key) {
+            return Err(error(io::ErrorKind::Other, path, "is a directory"));
+        }
+        MemFs::check_parent(&nodes, &key)?;
+        nodes.insert(key, Node::File(contents.to_vec(), SystemTime::now()));
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.insert(path, Node::Dir(SystemTime::now()))
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        self.insert(link, Node::Symlink(target.to_path_buf()))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let key = self.key(path)?;
+        let mut nodes = self.nodes();
+        match nodes.get(&key) {
+            Some(Node::Dir(_)) => Err(error(io::ErrorKind::Other, path, "is a directory")),
+            Some(_) => {
+                nodes.remove(&key);
+                Ok(())
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let key = self.key(path)?;
+        let mut nodes = self.nodes();
+        match nodes.get(&key) {
+            Some(Node::Dir(_)) => {
+                if nodes.keys().any(|child| child.parent() == Some(&key)) {
+                    return Err(error(io::ErrorKind::Other, path, "directory not empty"));
+                }
+                nodes.remove(&key);
+                Ok(())
+            }
+            Some(_) => Err(error(io::ErrorKind::Other, path, "not a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve(path)
+    }
+
+    fn same_filesystem(&self, a: &Path, b: &Path) -> io::Result<bool> {
+        Ok(self.device(a)? == self.device(b)?)
+    }
+
+    // Only modification times are recorded.
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from_key = self.resolve(from)?;
+        let to_key = self.resolve(to)?;
+        let mut nodes = self.nodes();
+        let time = match nodes.get(&from_key) {
+            Some(Node::Dir(time)) | Some(Node::File(_, time)) => *time,
+            _ => return Err(not_found(from)),
+        };
+        match nodes.get_mut(&to_key) {
+            Some(Node::Dir(to_time)) | Some(Node::File(_, to_time)) => *to_time = time,
+            _ => return Err(not_found(to)),
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes();
+        let from_key = MemFs::lookup(&nodes, from, false)?;
+        let to_key = MemFs::lookup(&nodes, to, false)?;
+        if !nodes.contains_key(&from_key) {
+            return Err(not_found(from));
+        }
+        if to_key.starts_with(&from_key) && to_key != from_key {
+            return Err(error(
+                io::ErrorKind::InvalidInput,
+                to,
+                "can't move a directory into itself",
+            ));
+        }
+        MemFs::check_parent(&nodes, &to_key)?;
+        match (nodes.get(&from_key), nodes.get(&to_key)) {
+            (Some(Node::Dir(_)), Some(Node::Dir(_)))
+                if nodes.keys().any(|child| child.parent() == Some(&to_key)) =>
+            {
+                return Err(error(io::ErrorKind::Other, to, "directory not empty"))
+            }
+            (Some(Node::Dir(_)), Some(Node::Dir(_))) => (),
+            (Some(Node::Dir(_)), Some(_)) => {
+                return Err(error(io::ErrorKind::Other, to, "not a directory"))
+            }
+            (Some(_), Some(Node::Dir(_))) => {
+                return Err(error(io::ErrorKind::Other, to, "is a directory"))
+            }
+            _ => (),
+        }
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|key| key.starts_with(&from_key))
+            .cloned()
+            .collect();
+        nodes.remove(&to_key);
+        for key in moved {
+            if let Some(node) = nodes.remove(&key) {
+                let new_key = match key.strip_prefix(&from_key) {
+                    Ok(rest) if !rest.as_os_str().is_empty() => to_key.join(rest),
+                    _ => to_key.clone(),
+                };
+                nodes.insert(new_key, node);
+            }
+        }
+        Ok(())
+    }
+}
+
+// So that a `Walker` (which owns its provider) can borrow one.
+impl<F: FsProvider + ?Sized> FsProvider for &F {
+    type DirEntry = F::DirEntry;
+    type ReadDir = F::ReadDir;
+
+    fn open_dir(&self, path: &Path) -> io::Result<Self::ReadDir> {
+        (**self).open_dir(path)
+    }
+
+    fn follow_symlink(&self, entry: &mut Self::DirEntry) -> bool {
+        (**self).follow_symlink(entry)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>> {
+        (**self).read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        (**self).metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        (**self).symlink_metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        (**self).read_link(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        (**self).create_dir(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        (**self).symlink(target, link)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        (**self).remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        (**self).remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).rename(from, to)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        (**self).canonicalize(path)
+    }
+
+    fn file_id(&self, path: &Path) -> io::Result<FileId> {
+        (**self).file_id(path)
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        (**self).copy_file(from, to)
+    }
+
+    fn copy_file_sparse(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        (**self).copy_file_sparse(from, to)
+    }
+
+    fn same_filesystem(&self, a: &Path, b: &Path) -> io::Result<bool> {
+        (**self).same_filesystem(a, b)
+    }
+
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).copy_times(from, to)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        (**self).sync(path)
+    }
+
+    fn copy_xattrs(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).copy_xattrs(from, to)
+    }
+
+    fn copy_acl(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).copy_acl(from, to)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        (**self).create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        (**self).exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        (**self).is_dir(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        (**self).is_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn mem_fs_works() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/a/b/c")).unwrap();
+        fs.write(Path::new("/a/b/file"), b"contents").unwrap();
+        fs.symlink(Path::new("b/file"), Path::new("/a/link"))
+            .unwrap();
+        assert!(fs.is_dir(Path::new("/a/b/c")));
+        assert!(fs.is_file(Path::new("/a/./link")));
+        assert!(fs
+            .symlink_metadata(Path::new("/a/link"))
+            .unwrap()
+            .is_symlink());
+        assert_eq!(fs.read(Path::new("/a/link")).unwrap(), b"contents");
+        fs.symlink(Path::new("../file"), Path::new("/a/b/c/up"))
+            .unwrap();
+        assert_eq!(fs.read(Path::new("/a/b/../b/c/up")).unwrap(), b"contents");
+        fs.symlink(Path::new("/a/b"), Path::new("/a/dir")).unwrap();
+        assert_eq!(fs.read(Path::new("/a/dir/c/up")).unwrap(), b"contents");
+        fs.write(Path::new("/a/dir/new"), b"new").unwrap();
+        assert!(fs.is_file(Path::new("/a/b/new")));
+        fs.remove_file(Path::new("/a/dir/new")).unwrap();
+        fs.remove_file(Path::new("/a/dir")).unwrap();
+        assert_eq!(fs.read(Path::new("/a/b/c/up")).unwrap(), b"contents");
+        assert!(fs.is_dir(Path::new("/a/b/c/../../../a")));
+        assert_eq!(
+            fs.canonicalize(Path::new("/a/b/c/up")).unwrap(),
+            Path::new("/a/b/file")
+        );
+        assert_eq!(fs.open_dir(Path::new("/a/b")).unwrap().count(), 2);
+        assert_eq!(fs.metadata(Path::new("/a/b/file")).unwrap().len, 8);
+        assert!(fs.write(Path::new("/x/file"), b"").is_err());
+        assert!(fs.create_dir(Path::new("/a")).is_err());
+        assert!(fs.remove_dir(Path::new("/a/b")).is_err());
+
+        fs.rename(Path::new("/a/b"), Path::new("/d")).unwrap();
+        assert!(!fs.exists(Path::new("/a/b")));
+        assert!(fs.is_dir(Path::new("/d/c")));
+        assert!(fs.is_file(Path::new("/d/file")));
+        fs.remove_file(Path::new("/d/file")).unwrap();
+        fs.remove_file(Path::new("/d/c/up")).unwrap();
+        fs.remove_dir(Path::new("/d/c")).unwrap();
+        fs.remove_dir(Path::new("/d")).unwrap();
+        assert!(!fs.exists(Path::new("/d")));
+    }
+
+    #[test]
+    fn mem_fs_devices_work() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/a/b/c")).unwrap();
+        fs.write(Path::new("/file"), b"").unwrap();
+        assert!(fs
+            .same_filesystem(Path::new("/"), Path::new("/a/b/c"))
+            .unwrap());
+        fs.set_device(&"/a/b", 1).unwrap();
+        assert!(fs.same_filesystem(Path::new("/"), Path::new("/a")).unwrap());
+        assert!(!fs
+            .same_filesystem(Path::new("/a"), Path::new("/a/b"))
+            .unwrap());
+        assert!(fs
+            .same_filesystem(Path::new("/a/b"), Path::new("/a/b/c"))
+            .unwrap());
+        assert!(fs.set_device(&"/file", 2).is_err());
+        assert!(fs
+            .same_filesystem(Path::new("/"), Path::new("/missing"))
+            .is_err());
+    }
+
+    #[test]
+    fn real_fs_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        RealFs.create_dir_all(&root.join("a/b")).unwrap();
+        RealFs.write(&root.join("a/file"), b"xyz").unwrap();
+        assert!(RealFs.is_dir(&root.join("a/b")));
+        assert_eq!(RealFs.metadata(&root.join("a/file")).unwrap().len, 3);
+        assert_eq!(
+            RealFs.canonicalize(&root.join("a/b/..")).unwrap(),
+            root.join("a").canonicalize().unwrap()
+        );
+        let names: Vec<OsString> = RealFs
+            .open_dir(&root.join("a"))
+            .unwrap()
+            .map(|e| e.unwrap().name())
+            .collect();
+        assert_eq!(names.len(), 2);
+    }
+}
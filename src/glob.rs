@@ -0,0 +1,160 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide shell style wild card matching of file names.
+//!
+//! Supported syntax: `*` (any sequence of characters), `?` (any single
+//! character), `[abc]`, `[a-z]` and `[!a-z]` (or `[^a-z]`) character
+//! classes and `\` to escape the following character.
+
+use unicode_normalization::UnicodeNormalization;
+
+// If `pattern[start]` is '[' and starts a well formed class return the
+// index just past the class and whether `ch` is a member.
+fn match_class(pattern: &[char], start: usize, ch: char) -> Option<(usize, bool)> {
+    let mut index = start + 1;
+    let negated = match pattern.get(index) {
+        Some('!') | Some('^') => {
+            index += 1;
+            true
+        }
+        _ => false,
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let c = *pattern.get(index)?;
+        if c == ']' && !first {
+            return Some((index + 1, matched != negated));
+        }
+        first = false;
+        if pattern.get(index + 1) == Some(&'-') && pattern.get(index + 2).is_some_and(|e| *e != ']')
+        {
+            let end = pattern[index + 2];
+            if c <= ch && ch <= end {
+                matched = true;
+            }
+            index += 3;
+        } else {
+            if c == ch {
+                matched = true;
+            }
+            index += 1;
+        }
+    }
+}
+
+// If the single (non '*') pattern element at `pattern[index]` matches `ch`
+// return the index of the next element.
+fn match_one(pattern: &[char], index: usize, ch: char) -> Option<usize> {
+    match pattern[index] {
+        '?' => Some(index + 1),
+        '\\' if index + 1 < pattern.len() => {
+            if pattern[index + 1] == ch {
+                Some(index + 2)
+            } else {
+                None
+            }
+        }
+        '[' => match match_class(pattern, index, ch) {
+            Some((next, true)) => Some(next),
+            Some((_, false)) => None,
+            None if ch == '[' => Some(index + 1),
+            None => None,
+        },
+        c if c == ch => Some(index + 1),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GlobOptions {
+    /// Convert both pattern and text to Unicode normalization form C
+    /// before matching (so that e.g. NFD names from macOS match).
+    pub normalize_unicode: bool,
+}
+
+/// Return `true` if the whole of `text` matches the wild card `pattern`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    glob_match_with(pattern, text, &GlobOptions::default())
+}
+
+/// Like `glob_match()` but as modified by `options`.
+pub fn glob_match_with(pattern: &str, text: &str, options: &GlobOptions) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = if options.normalize_unicode {
+        (pattern.nfc().collect(), text.nfc().collect())
+    } else {
+        (pattern.chars().collect(), text.chars().collect())
+    };
+    let (mut p_index, mut t_index) = (0, 0);
+    // where to resume if the current attempt fails after a '*'
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t_index < text.len() {
+        if p_index < pattern.len() && pattern[p_index] == '*' {
+            backtrack = Some((p_index, t_index));
+            p_index += 1;
+        } else if let Some(next) = if p_index < pattern.len() {
+            match_one(&pattern, p_index, text[t_index])
+        } else {
+            None
+        } {
+            p_index = next;
+            t_index += 1;
+        } else if let Some((star_index, star_t_index)) = backtrack {
+            p_index = star_index + 1;
+            t_index = star_t_index + 1;
+            backtrack = Some((star_index, t_index));
+        } else {
+            return false;
+        }
+    }
+    pattern[p_index..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_works() {
+        assert!(glob_match("*.rs", "lib.rs"));
+        assert!(glob_match("*.rs", ".rs"));
+        assert!(!glob_match("*.rs", "lib.rs.bak"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file.txt"));
+        assert!(glob_match("[abc]*", "beta"));
+        assert!(!glob_match("[!abc]*", "beta"));
+        assert!(glob_match("img[0-9].png", "img7.png"));
+        assert!(!glob_match("img[0-9].png", "imgX.png"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[", "["));
+        assert!(glob_match(r"\*", "*"));
+        assert!(!glob_match(r"\*", "x"));
+        assert!(glob_match("résumé*", "résumé.pdf"));
+    }
+
+    #[test]
+    fn glob_match_with_works() {
+        let options = GlobOptions {
+            normalize_unicode: true,
+        };
+        let nfd_name = "re\u{301}sume\u{301}.pdf";
+        assert!(!glob_match("r\u{e9}sum\u{e9}*", nfd_name));
+        assert!(glob_match_with("r\u{e9}sum\u{e9}*", nfd_name, &options));
+        assert!(glob_match_with("r[\u{e9}]*", nfd_name, &options));
+    }
+}
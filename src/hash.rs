@@ -0,0 +1,307 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide content hashing of files and trees.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use super::walk::Walker;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgo {
+    /// Fast but not collision resistant: fine for spotting accidental change.
+    Fnv1a64,
+    Sha256,
+    /// Cryptographic like `Sha256` but faster.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgo {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgo::Fnv1a64 => "fnv1a64",
+            HashAlgo::Sha256 => "sha256",
+            #[cfg(feature = "blake3")]
+            HashAlgo::Blake3 => "blake3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HashAlgo> {
+        match name {
+            "fnv1a64" => Some(HashAlgo::Fnv1a64),
+            "sha256" => Some(HashAlgo::Sha256),
+            #[cfg(feature = "blake3")]
+            "blake3" => Some(HashAlgo::Blake3),
+            _ => None,
+        }
+    }
+}
+
+enum HashState {
+    Fnv1a64(u64),
+    Sha256(Sha256),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut string = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(string, "{:02x}", byte).expect("writing to a String can't fail");
+    }
+    string
+}
+
+impl HashState {
+    fn new(algo: HashAlgo) -> HashState {
+        match algo {
+            HashAlgo::Fnv1a64 => HashState::Fnv1a64(FNV_OFFSET_BASIS),
+            HashAlgo::Sha256 => HashState::Sha256(Sha256::new()),
+            #[cfg(feature = "blake3")]
+            HashAlgo::Blake3 => HashState::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            HashState::Fnv1a64(hash) => {
+                for byte in data {
+                    *hash ^= u64::from(*byte);
+                    *hash = hash.wrapping_mul(FNV_PRIME);
+                }
+            }
+            HashState::Sha256(hasher) => hasher.update(data),
+            #[cfg(feature = "blake3")]
+            HashState::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn hex_digest(self) -> String {
+        match self {
+            HashState::Fnv1a64(hash) => format!("{:016x}", hash),
+            HashState::Sha256(hasher) => to_hex(&hasher.finalize()),
+            #[cfg(feature = "blake3")]
+            HashState::Blake3(hasher) => to_hex(hasher.finalize().as_bytes()),
+        }
+    }
+}
+
+/// Return the lower case hexadecimal digest of everything read from `reader`.
+pub fn hash_reader<R: Read>(reader: &mut R, algo: HashAlgo) -> io::Result<String> {
+    let mut state = HashState::new(algo);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => state.update(&buffer[..n]),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(state.hex_digest())
+}
+
+/// Return the lower case hexadecimal digest of `data`.
+pub fn hash_bytes(data: &[u8], algo: HashAlgo) -> String {
+    let mut state = HashState::new(algo);
+    state.update(data);
+    state.hex_digest()
+}
+
+/// Return the lower case hexadecimal digest of the file's contents.
+/// The file is read in blocks so it may safely be changed (or truncated)
+/// by someone else while it's being hashed: see `hash_file_mmap()`.
+pub fn hash_file<P: AsRef<Path>>(path: &P, algo: HashAlgo) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    hash_reader(&mut file, algo)
+}
+
+/// Like `hash_file()` but the file is memory mapped (rather than copied
+/// through a buffer) which is faster for big files.
+///
+/// # Safety
+///
+/// The file mustn't be truncated while it's being hashed as the process
+/// would then be killed by a bus error.  The result is unspecified if the
+/// file is otherwise modified.
+#[cfg(all(feature = "mmap", any(unix, windows)))]
+pub unsafe fn hash_file_mmap<P: AsRef<Path>>(path: &P, algo: HashAlgo) -> io::Result<String> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        // empty files can't be mapped
+        return Ok(hash_bytes(b"", algo));
+    }
+    let map = memmap2::Mmap::map(&file)?;
+    Ok(hash_bytes(&map, algo))
+}
+
+/// Return a digest of the regular files below `root` that depends on both
+/// their contents and their paths (relative to `root`) but not on the
+/// order in which the directories are read or on the platform's path
+/// separator.  Symbolic links are not followed and empty directories make
+/// no difference.
+pub fn hash_tree<P: AsRef<Path>>(root: &P, algo: HashAlgo) -> io::Result<String> {
+    let root: &Path = root.as_ref();
+    let mut file_hashes = BTreeMap::new();
+    for entry in Walker::new(&root) {
+        let entry = entry?;
+        if !entry.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let rel_path: Vec<String> = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .iter()
+            .map(|part| part.to_string_lossy().into_owned())
+            .collect();
+        file_hashes.insert(rel_path.join("/"), hash_file(&path, algo)?);
+    }
+    let mut state = HashState::new(algo);
+    for (rel_path, hash) in file_hashes {
+        // paths can't contain nul bytes so this is unambiguous
+        state.update(rel_path.as_bytes());
+        state.update(b"\0");
+        state.update(hash.as_bytes());
+        state.update(b"\n");
+    }
+    Ok(state.hex_digest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn hash_reader_works() {
+        assert_eq!(
+            hash_reader(&mut "".as_bytes(), HashAlgo::Fnv1a64).unwrap(),
+            "cbf29ce484222325"
+        );
+        assert_eq!(hash_bytes(b"", HashAlgo::Fnv1a64), "cbf29ce484222325");
+        assert_eq!(
+            hash_reader(&mut "a".as_bytes(), HashAlgo::Fnv1a64).unwrap(),
+            "af63dc4c8601ec8c"
+        );
+        assert_eq!(
+            hash_reader(&mut "abc".as_bytes(), HashAlgo::Sha256).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_works() {
+        assert_eq!(
+            hash_reader(&mut "abc".as_bytes(), HashAlgo::Blake3).unwrap(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        // from the official test vectors (input bytes cycle through 0..251)
+        let data: Vec<u8> = (0..1025).map(|i| (i % 251) as u8).collect();
+        assert_eq!(
+            hash_bytes(&data, HashAlgo::Blake3),
+            "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444"
+        );
+    }
+
+    #[test]
+    fn algo_names_round_trip() {
+        for algo in &[HashAlgo::Fnv1a64, HashAlgo::Sha256] {
+            assert_eq!(HashAlgo::from_name(algo.name()), Some(*algo));
+        }
+        #[cfg(feature = "blake3")]
+        assert_eq!(HashAlgo::from_name("blake3"), Some(HashAlgo::Blake3));
+        assert_eq!(HashAlgo::from_name("md5"), None);
+    }
+
+    #[test]
+    fn hash_file_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        // bigger than the read buffer
+        let data: Vec<u8> = (0..102_400).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        assert_eq!(
+            hash_file(&path, HashAlgo::Sha256).unwrap(),
+            "74588b7f0bcc354ac14d9cf199fa3a20c05f0c7293b9075b2f2e146e718de800"
+        );
+        #[cfg(feature = "blake3")]
+        assert_eq!(
+            hash_file(&path, HashAlgo::Blake3).unwrap(),
+            "bc3e3d41a1146b069abffad3c0d44860cf664390afce4d9661f7902e7943e085"
+        );
+        assert!(hash_file(&temp_dir.path().join("missing"), HashAlgo::Sha256).is_err());
+    }
+
+    #[cfg(all(feature = "mmap", any(unix, windows)))]
+    #[test]
+    fn hash_file_mmap_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        let data: Vec<u8> = (0..102_400).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        for algo in &[HashAlgo::Fnv1a64, HashAlgo::Sha256] {
+            assert_eq!(
+                unsafe { hash_file_mmap(&path, *algo) }.unwrap(),
+                hash_file(&path, *algo).unwrap()
+            );
+        }
+        fs::write(&path, "").unwrap();
+        assert_eq!(
+            unsafe { hash_file_mmap(&path, HashAlgo::Sha256) }.unwrap(),
+            hash_bytes(b"", HashAlgo::Sha256)
+        );
+        assert!(
+            unsafe { hash_file_mmap(&temp_dir.path().join("missing"), HashAlgo::Sha256) }.is_err()
+        );
+    }
+
+    #[test]
+    fn hash_tree_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/x"), "x").unwrap();
+        fs::write(root.join("y"), "y").unwrap();
+        let digest = hash_tree(&root, HashAlgo::Sha256).unwrap();
+        assert_eq!(digest.len(), 64);
+        fs::create_dir(root.join("empty")).unwrap();
+        assert_eq!(hash_tree(&root, HashAlgo::Sha256).unwrap(), digest);
+        // moving a file changes the digest
+        fs::rename(root.join("y"), root.join("a/y")).unwrap();
+        let moved = hash_tree(&root, HashAlgo::Sha256).unwrap();
+        assert_ne!(moved, digest);
+        fs::rename(root.join("a/y"), root.join("y")).unwrap();
+        assert_eq!(hash_tree(&root, HashAlgo::Sha256).unwrap(), digest);
+        fs::write(root.join("y"), "z").unwrap();
+        assert_ne!(hash_tree(&root, HashAlgo::Sha256).unwrap(), digest);
+        assert_ne!(hash_tree(&root, HashAlgo::Fnv1a64).unwrap(), digest);
+    }
+}
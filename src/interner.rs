@@ -0,0 +1,176 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide interning of path strings so that large numbers
+//! of paths can be held without duplicating their common directories.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, MAIN_SEPARATOR};
+use std::sync::Arc;
+
+#[derive(Debug)]
+struct Node {
+    parent: Option<InternedPath>,
+    name: Arc<str>,
+    is_root: bool,
+}
+
+/// A cheaply cloned and compared handle for a path interned by a
+/// `PathInterner`.  Handles from the same interner are equal if and only
+/// if their paths are.
+#[derive(Debug, Clone)]
+pub struct InternedPath(Arc<Node>);
+
+impl InternedPath {
+    /// The last component of the path (or the root e.g. "/" or "C:\").
+    pub fn file_name(&self) -> &str {
+        &self.0.name
+    }
+
+    pub fn parent(&self) -> Option<&InternedPath> {
+        self.0.parent.as_ref()
+    }
+
+    fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+}
+
+impl PartialEq for InternedPath {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for InternedPath {}
+
+impl Hash for InternedPath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state)
+    }
+}
+
+impl fmt::Display for InternedPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref parent) = self.0.parent {
+            write!(f, "{}", parent)?;
+            if !parent.0.is_root {
+                write!(f, "{}", MAIN_SEPARATOR)?;
+            }
+        }
+        write!(f, "{}", self.0.name)
+    }
+}
+
+/// A store of interned paths in which each distinct directory (and file
+/// name) is only held once.
+#[derive(Debug, Default)]
+pub struct PathInterner {
+    names: HashSet<Arc<str>>,
+    paths: HashMap<(usize, Arc<str>), InternedPath>,
+}
+
+impl PathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of distinct paths (including directories of interned
+    /// paths) held.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    fn name(&mut self, name: &str) -> Arc<str> {
+        if let Some(name) = self.names.get(name) {
+            return Arc::clone(name);
+        }
+        let name: Arc<str> = Arc::from(name);
+        self.names.insert(Arc::clone(&name));
+        name
+    }
+
+    fn child(&mut self, parent: Option<InternedPath>, name: &str, is_root: bool) -> InternedPath {
+        let name = self.name(name);
+        let key = (parent.as_ref().map_or(0, InternedPath::id), name);
+        if let Some(path) = self.paths.get(&key) {
+            return path.clone();
+        }
+        let path = InternedPath(Arc::new(Node {
+            parent,
+            name: Arc::clone(&key.1),
+            is_root,
+        }));
+        self.paths.insert(key, path.clone());
+        path
+    }
+
+    /// Return the handle for `path` (which must not be empty).  Paths are
+    /// interned by component so that "a//b/" and "a/./b" give the same
+    /// handle as "a/b".
+    pub fn intern(&mut self, path: &str) -> InternedPath {
+        let mut root = String::new();
+        let mut names: Vec<&str> = Vec::new();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Prefix(prefix) => root.push_str(&prefix.as_os_str().to_string_lossy()),
+                Component::RootDir => root.push(MAIN_SEPARATOR),
+                Component::CurDir if !names.is_empty() => (),
+                _ => names.push(component.as_os_str().to_str().unwrap_or_default()),
+            }
+        }
+        let mut interned = if root.is_empty() {
+            None
+        } else {
+            Some(self.child(None, &root, true))
+        };
+        for name in names {
+            interned = Some(self.child(interned, name, false));
+        }
+        match interned {
+            Some(interned) => interned,
+            None => self.child(None, "", false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_interner_works() {
+        let mut interner = PathInterner::new();
+        let c = interner.intern("a/b/c");
+        let d = interner.intern("a//b/./d/");
+        assert_eq!(interner.len(), 4);
+        assert_eq!(c.parent(), d.parent());
+        assert_eq!(c, interner.intern("a/b/c"));
+        assert_ne!(c, d);
+        assert_eq!(c.file_name(), "c");
+        assert!(Arc::ptr_eq(&interner.intern("x/c").0.name, &c.0.name));
+        let sep = MAIN_SEPARATOR.to_string();
+        assert_eq!(c.to_string(), ["a", "b", "c"].join(&sep));
+        let absolute = format!("{}usr{}bin", sep, sep);
+        assert_eq!(interner.intern(&absolute).to_string(), absolute);
+        assert_eq!(interner.intern("./x").to_string(), format!(".{}x", sep));
+        assert_eq!(interner.intern("").to_string(), "");
+    }
+}
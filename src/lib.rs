@@ -12,18 +12,111 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "blake3")]
+extern crate blake3;
+#[cfg(all(feature = "home", not(target_arch = "wasm32")))]
 pub extern crate dirs;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+extern crate sha2;
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+
+#[cfg(unix)]
+extern crate libc;
+#[cfg(all(feature = "mmap", any(unix, windows)))]
+extern crate memmap2;
+#[cfg(windows)]
+extern crate winapi;
 
-use std::env;
 use std::ffi::OsString;
 use std::fs::{DirEntry, FileType, Metadata};
 use std::io;
 use std::io::Write;
-use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+#[cfg(unix)]
+use std::os::unix::fs::{DirEntryExt, MetadataExt};
+use std::path::{self, Component, Path, PathBuf, MAIN_SEPARATOR};
 
 #[macro_use]
 pub mod str_path;
 
+pub mod abbreviate;
+#[cfg(feature = "acl")]
+pub mod acl;
+#[cfg(all(feature = "home", not(target_arch = "wasm32")))]
+pub mod app_dirs;
+pub mod backup;
+pub mod clean;
+pub mod context;
+pub mod conversions;
+pub mod copy;
+pub mod create;
+pub mod dir_stats;
+pub mod disk_usage;
+pub mod duplicates;
+pub mod durable;
+pub mod empty_dirs;
+pub mod error;
+pub mod exe;
+pub mod file_url;
+pub mod filesystem;
+pub mod find;
+pub mod foreign_path;
+pub mod fs_provider;
+pub mod glob;
+pub mod hash;
+pub mod interner;
+pub mod listing;
+pub mod lock;
+pub mod long_path;
+pub mod manifest;
+#[cfg(feature = "mime")]
+pub mod mime;
+pub mod normalization;
+#[cfg(unix)]
+pub mod owners;
+pub mod path_lit;
+pub mod path_set;
+pub mod path_trie;
+pub mod percent_encoding;
+pub mod permissions;
+pub mod pid_file;
+pub mod portability;
+pub mod pure_path;
+pub mod quoting;
+pub mod ranking;
+pub mod remap;
+pub mod remove;
+pub mod rotate;
+pub mod safe_join;
+pub mod sanitize;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+pub mod shard;
+pub mod sniff;
+pub mod sorting;
+pub mod sparse;
+pub mod symlink;
+pub mod temp_file;
+pub mod template;
+pub mod times;
+pub mod touch;
+pub mod trash;
+pub mod tree;
+pub mod unc;
+pub mod walk;
+#[cfg(unix)]
+pub mod xattr;
+
+mod datetime;
+mod sys;
+
+use context::{Context, ExpandOptions, PathCtx};
+
+pub use error::PathuxError;
+
 pub fn strip_n_levels<P: AsRef<Path>>(path: &P, n: usize) -> PathBuf {
     let path: &Path = path.as_ref();
     let mut components = path.components();
@@ -33,8 +126,9 @@ pub fn strip_n_levels<P: AsRef<Path>>(path: &P, n: usize) -> PathBuf {
     components.as_path().to_path_buf()
 }
 
+// On Windows both '/' and '\\' are separators.
 pub fn split_path_text(text: &str) -> (&str, &str) {
-    if let Some(index) = text.rfind(MAIN_SEPARATOR) {
+    if let Some(index) = text.rfind(path::is_separator) {
         (&text[..index + 1], &text[index + 1..])
     } else {
         ("", text)
@@ -49,6 +143,40 @@ pub fn file_name_text(text: &str) -> &str {
     split_path_text(text).1
 }
 
+/// Convert all separators ('/' or '\\') in `text` to the native separator
+/// and collapse runs of them into one.  On Windows a leading pair (as in
+/// UNC paths) is kept.
+pub fn normalize_separators(text: &str) -> String {
+    let is_separator = |ch: char| ch == '/' || ch == '\\';
+    let mut normalized = String::with_capacity(text.len());
+    if cfg!(windows) && text.len() > 1 && text.chars().take(2).all(is_separator) {
+        normalized.push(MAIN_SEPARATOR);
+    }
+    let mut after_separator = false;
+    for ch in text.chars() {
+        if is_separator(ch) {
+            if !after_separator {
+                normalized.push(MAIN_SEPARATOR);
+            }
+            after_separator = true;
+        } else {
+            normalized.push(ch);
+            after_separator = false;
+        }
+    }
+    normalized
+}
+
+/// Replace all back slashes in `text` with forward slashes.
+pub fn to_slash(text: &str) -> String {
+    text.replace('\\', "/")
+}
+
+/// Replace all forward slashes in `text` with back slashes.
+pub fn to_backslash(text: &str) -> String {
+    text.replace('/', "\\")
+}
+
 pub fn path_to_string(path: &Path) -> String {
     if let Some(path_str) = path.to_str() {
         path_str.to_string()
@@ -94,15 +222,134 @@ pub fn first_subpath_as_os_string(path: &Path) -> Option<OsString> {
     None
 }
 
+/// Replace a leading `~` component of `path` with the home directory.  This
+/// is purely lexical: whether or not `path` exists makes no difference.
+pub fn expand_tilde(path: &Path) -> io::Result<PathBuf> {
+    expand_tilde_with(path, &ExpandOptions::default())
+}
+
+/// Like `expand_tilde()` but using the home directory in `opts`.
+pub fn expand_tilde_with(path: &Path, opts: &ExpandOptions) -> io::Result<PathBuf> {
+    expand_tilde_checked(path, opts).map_err(io::Error::from)
+}
+
+fn expand_tilde_checked(path: &Path, opts: &ExpandOptions) -> Result<PathBuf, PathuxError> {
+    let mut components = path.components();
+    match components.next() {
+        Some(Component::Normal(text)) if text == "~" => match opts.home_dir() {
+            Some(home_dir) => Ok(home_dir.join(components.as_path())),
+            None => Err(PathuxError::NoHome),
+        },
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Like `expand_tilde()` but failing with `PathuxError::NoHome` if the
+/// home directory is needed but can't be found.
+pub fn expand_home_dir_checked(path: &Path) -> Result<PathBuf, PathuxError> {
+    expand_tilde_checked(path, &ExpandOptions::default())
+}
+
+// Remove "." components and apply ".." ones (without looking at the file
+// system so symbolic links aren't followed).  ".." at the root is dropped.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => result.push(".."),
+            },
+            _ => result.push(component),
+        }
+    }
+    result
+}
+
+/// Turn `path` into an absolute, normalized path: a leading `~` is
+/// expanded, relative paths are taken to be relative to the current
+/// directory and "." and ".." components are resolved lexically.
+pub fn resolve(path: &Path) -> io::Result<PathBuf> {
+    resolve_with(path, &ExpandOptions::default())
+}
+
+/// Like `resolve()` but using the directories in `opts`.
+pub fn resolve_with(path: &Path, opts: &ExpandOptions) -> io::Result<PathBuf> {
+    let path = expand_tilde_with(path, opts)?;
+    if path.is_absolute() {
+        Ok(normalize_lexically(&path))
+    } else {
+        Ok(normalize_lexically(&opts.current_dir()?.join(path)))
+    }
+}
+
+/// How `absolutize()` deals with "." and ".." components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolve {
+    /// Remove them textually (".." removes the preceding component even if
+    /// it is a symbolic link).
+    Lexical,
+    /// Consult the file system (like `realpath`) so symbolic links are
+    /// followed before ".." is applied.  Components that don't exist are
+    /// dealt with lexically.
+    Physical,
+}
+
+/// Turn `path` into an absolute path (relative to the current directory)
+/// with "." and ".." removed as specified by `resolve`.
+pub fn absolutize(path: &Path, resolve: Resolve) -> io::Result<PathBuf> {
+    let abs_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        absolute_path_buf_in_dir(path, &Context::current_dir()?)
+    };
+    match resolve {
+        Resolve::Lexical => Ok(normalize_lexically(&abs_path)),
+        Resolve::Physical => {
+            for ancestor in abs_path.ancestors() {
+                match ancestor.canonicalize() {
+                    Ok(real_path) => {
+                        let rest = abs_path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+                        return Ok(normalize_lexically(&real_path.join(rest)));
+                    }
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(normalize_lexically(&abs_path))
+        }
+    }
+}
+
+#[deprecated(
+    note = "expansion depends on whether the path exists: use expand_tilde() or resolve()"
+)]
 pub fn expand_home_dir(path: &Path) -> Option<PathBuf> {
+    #[allow(deprecated)]
+    expand_home_dir_with(path, &ExpandOptions::default())
+}
+
+/// Like `expand_home_dir()` but using the directories in `opts`.
+#[deprecated(
+    note = "expansion depends on whether the path exists: use expand_tilde_with() or resolve_with()"
+)]
+pub fn expand_home_dir_with(path: &Path, opts: &ExpandOptions) -> Option<PathBuf> {
+    let exists = match opts.cwd {
+        Some(ref cwd) => cwd.join(path).exists(),
+        None => path.exists(),
+    };
     if path.is_absolute() {
         return Some(path.to_path_buf());
-    } else if !path.exists() {
+    } else if !exists {
         let mut components = path.components();
         if let Some(first_component) = components.next() {
             if let Component::Normal(text) = first_component {
                 if text == "~" {
-                    if let Some(home_dir_path) = dirs::home_dir() {
+                    if let Some(home_dir_path) = opts.home_dir() {
                         return Some(home_dir_path.join(components.as_path()));
                     }
                 }
@@ -112,23 +359,31 @@ pub fn expand_home_dir(path: &Path) -> Option<PathBuf> {
     None
 }
 
+#[deprecated(note = "use expand_tilde() or resolve()")]
 pub fn expand_home_dir_or_mine(path: &Path) -> PathBuf {
+    #[allow(deprecated)]
     expand_home_dir(path).unwrap_or(path.to_path_buf())
 }
 
+fn absolute_path_buf_in_dir(path: &Path, current_dir_path: &Path) -> PathBuf {
+    let mut components = path.components();
+    if let Some(first_component) = components.next() {
+        if let Component::CurDir = first_component {
+            current_dir_path.join(components.as_path())
+        } else {
+            current_dir_path.join(path)
+        }
+    } else {
+        current_dir_path.to_path_buf()
+    }
+}
+
+/// A convenience function that panics if the current directory can't be
+/// found and leaves any ".." components in place: see `absolutize()`.
 pub fn absolute_path_buf(path: &Path) -> PathBuf {
     if path.is_relative() {
-        if let Ok(current_dir_path) = env::current_dir() {
-            let mut components = path.components();
-            if let Some(first_component) = components.next() {
-                if let Component::CurDir = first_component {
-                    return current_dir_path.join(components.as_path());
-                } else {
-                    return current_dir_path.join(path);
-                }
-            } else {
-                return current_dir_path;
-            }
+        if let Ok(current_dir_path) = Context::current_dir() {
+            return absolute_path_buf_in_dir(path, &current_dir_path);
         } else {
             panic!(
                 "File: {} Line: {} : can't find current directory???",
@@ -140,25 +395,97 @@ pub fn absolute_path_buf(path: &Path) -> PathBuf {
     path.to_path_buf()
 }
 
+/// Like `absolute_path_buf()` but relative paths are resolved against
+/// `ctx.cwd` rather than the current directory.
+pub fn absolute_path_buf_in_ctx(path: &Path, ctx: &PathCtx) -> PathBuf {
+    if path.is_relative() {
+        absolute_path_buf_in_dir(path, &ctx.cwd)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// A convenience wrapper around `relative_path_buf_checked()` that panics
+/// if the current directory can't be found.
 pub fn relative_path_buf(path: &Path) -> Option<PathBuf> {
+    match relative_path_buf_checked(path) {
+        Ok(rel_path) => Some(rel_path),
+        Err(PathuxError::NotUnderBase(_)) => None,
+        Err(err) => panic!("File: {} Line: {} : {}", file!(), line!(), err),
+    }
+}
+
+/// The path of `path` relative to the current directory (relative paths
+/// are returned unchanged).
+pub fn relative_path_buf_checked(path: &Path) -> Result<PathBuf, PathuxError> {
     if path.is_absolute() {
-        if let Ok(current_dir_path) = env::current_dir() {
-            if let Ok(rel_path) = path.strip_prefix(&current_dir_path) {
-                return Some(rel_path.to_path_buf());
-            } else {
-                return None;
-            }
-        } else {
-            panic!(
-                "File: {} Line: {} : can't find current directory???",
-                file!(),
-                line!()
-            )
+        let current_dir_path = Context::current_dir().map_err(PathuxError::NoCwd)?;
+        match path.strip_prefix(&current_dir_path) {
+            Ok(rel_path) => Ok(rel_path.to_path_buf()),
+            Err(_) => Err(PathuxError::NotUnderBase(
+                path.to_string_lossy().into_owned(),
+            )),
         }
-    };
-    Some(path.to_path_buf())
+    } else {
+        Ok(path.to_path_buf())
+    }
 }
 
+/// The path of `path` relative to the directory `base` using ".."
+/// components where `path` isn't below `base` e.g. "/a/b/c" relative to
+/// "/a/d" is "../b/c".  If more than `max_up` ".." components would be
+/// needed (or the paths are on different Windows drives) the absolute path
+/// is returned instead.  Relative arguments are taken to be relative to
+/// the current directory and both paths are normalized lexically.
+pub fn relative_path_buf_from(
+    path: &Path,
+    base: &Path,
+    max_up: Option<usize>,
+) -> io::Result<PathBuf> {
+    let abs_path = absolutize(path, Resolve::Lexical)?;
+    let abs_base = absolutize(base, Resolve::Lexical)?;
+    let path_components: Vec<Component> = abs_path.components().collect();
+    let base_components: Vec<Component> = abs_base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(base_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let ups = base_components.len() - common;
+    let has_common_root = path_components
+        .iter()
+        .zip(base_components.iter())
+        .take_while(|(a, _)| !matches!(a, Component::Normal(_)))
+        .all(|(a, b)| a == b);
+    if !has_common_root || max_up.is_some_and(|max_up| ups > max_up) {
+        return Ok(abs_path);
+    }
+    let mut rel_path = PathBuf::new();
+    for _ in 0..ups {
+        rel_path.push("..");
+    }
+    for component in &path_components[common..] {
+        rel_path.push(component);
+    }
+    if rel_path.as_os_str().is_empty() {
+        rel_path.push(".");
+    }
+    Ok(rel_path)
+}
+
+/// Like `relative_path_buf()` but relative to `ctx.cwd` rather than the
+/// current directory.
+pub fn relative_path_buf_in_ctx(path: &Path, ctx: &PathCtx) -> Option<PathBuf> {
+    if path.is_absolute() {
+        path.strip_prefix(&ctx.cwd).ok().map(|p| p.to_path_buf())
+    } else {
+        Some(path.to_path_buf())
+    }
+}
+
+/// A convenience wrapper around `relative_path_buf_checked()` that returns
+/// `path` unchanged if it isn't under the current directory (and panics if
+/// the current directory can't be found).
 pub fn relative_path_buf_or_mine(path: &Path) -> PathBuf {
     relative_path_buf(path).unwrap_or(path.to_path_buf())
 }
@@ -170,10 +497,19 @@ pub struct UsableDirEntry {
 }
 
 impl UsableDirEntry {
+    /// The entries of the directory at `dir_path`.  Their types are taken
+    /// from the directory itself (where the platform records them) and no
+    /// metadata is read until `metadata()` is called.
     pub fn get_entries<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<UsableDirEntry>> {
         usable_dir_entries(dir_path)
     }
 
+    /// Like `get_entries()` but errors reading the directory are returned
+    /// rather than skipped, reported or panicked on.
+    pub fn get_entries_lazy<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<UsableDirEntry>> {
+        usable_dir_entries_lazy(dir_path)
+    }
+
     pub fn path(&self) -> PathBuf {
         self.dir_entry.path()
     }
@@ -201,6 +537,59 @@ impl UsableDirEntry {
     pub fn metadata(&self) -> io::Result<Metadata> {
         self.dir_entry.metadata()
     }
+
+    /// The access, modification and creation times of this entry.
+    pub fn timestamps(&self) -> io::Result<times::Timestamps> {
+        times::Timestamps::from_metadata(&self.dir_entry.metadata()?)
+    }
+
+    /// The number of hard links to this entry.
+    #[cfg(unix)]
+    pub fn nlink(&self) -> io::Result<u64> {
+        Ok(self.dir_entry.metadata()?.nlink())
+    }
+
+    /// The inode number of this entry (as recorded in the directory so no
+    /// `stat()` is needed).
+    #[cfg(unix)]
+    pub fn inode(&self) -> u64 {
+        self.dir_entry.ino()
+    }
+
+    /// The id of the device containing this entry.
+    #[cfg(unix)]
+    pub fn device(&self) -> io::Result<u64> {
+        Ok(self.dir_entry.metadata()?.dev())
+    }
+
+    /// The name of the user that owns this entry (if known).
+    #[cfg(unix)]
+    pub fn owner_name(&self) -> Option<String> {
+        owners::user_name(self.dir_entry.metadata().ok()?.uid())
+    }
+
+    /// The name of the group that owns this entry (if known).
+    #[cfg(unix)]
+    pub fn group_name(&self) -> Option<String> {
+        owners::group_name(self.dir_entry.metadata().ok()?.gid())
+    }
+
+    /// Return `true` if this is a file (or a link to one) that the current
+    /// process may execute.
+    pub fn is_executable(&self) -> bool {
+        permissions::path_is_executable(&self.path())
+    }
+
+    /// Guess the MIME type from the extension ("inode/directory" for
+    /// directories).
+    #[cfg(feature = "mime")]
+    pub fn guess_mime(&self) -> Option<&'static str> {
+        if self.is_dir() {
+            Some("inode/directory")
+        } else {
+            mime::guess_mime(&self.path())
+        }
+    }
 }
 
 pub fn usable_dir_entries<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<UsableDirEntry>> {
@@ -210,9 +599,10 @@ pub fn usable_dir_entries<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<Usable
     for e_entry in read_dir {
         match e_entry {
             Ok(dir_entry) => {
-                match dir_entry.metadata() {
-                    Ok(metadata) => {
-                        let file_type = metadata.file_type();
+                // the type recorded in the directory itself (d_type on Linux)
+                // so the entry only needs to be stat()ed if it's unknown
+                match dir_entry.file_type() {
+                    Ok(file_type) => {
                         let usable_entry = UsableDirEntry {
                             dir_entry,
                             file_type,
@@ -272,10 +662,29 @@ pub fn usable_dir_entries<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<Usable
     Ok(entries)
 }
 
+pub fn usable_dir_entries_lazy<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<UsableDirEntry>> {
+    let mut entries: Vec<UsableDirEntry> = Vec::new();
+    for e_entry in dir_path.as_ref().read_dir()? {
+        let dir_entry = e_entry?;
+        match dir_entry.file_type() {
+            Ok(file_type) => entries.push(UsableDirEntry {
+                dir_entry,
+                file_type,
+            }),
+            // we assume that "not found" is due to a race condition and ignore it
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(entries)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    extern crate tempfile;
+
     #[test]
     fn split_path_text_works() {
         match MAIN_SEPARATOR {
@@ -294,10 +703,29 @@ mod tests {
                 );
                 assert_eq!(split_path_text("~"), ("", "~"));
             }
+            '\\' => {
+                assert_eq!(split_path_text(r"C:\a/b"), (r"C:\a/", "b"));
+                assert_eq!(split_path_text(r"a\b\c"), (r"a\b\", "c"));
+                assert_eq!(split_path_text("a/b"), ("a/", "b"));
+            }
             _ => panic!("File: {} Line: {} : new test required"),
         }
     }
 
+    #[test]
+    fn separator_conversions_work() {
+        assert_eq!(to_slash(r"a\b/c\"), "a/b/c/");
+        assert_eq!(to_backslash(r"a/b\c/"), r"a\b\c\");
+        let expected = ["a", "b", "c", ""].join(path::MAIN_SEPARATOR_STR);
+        assert_eq!(normalize_separators(r"a//b\\/c/"), expected);
+        assert_eq!(normalize_separators("abc"), "abc");
+        if cfg!(windows) {
+            assert_eq!(normalize_separators("//server//share"), r"\\server\share");
+        } else {
+            assert_eq!(normalize_separators(r"\\server\share"), "/server/share");
+        }
+    }
+
     #[test]
     fn dir_path_text_works() {
         assert_eq!(dir_path_text("something"), "");
@@ -338,4 +766,249 @@ mod tests {
         assert_eq!(strip_n_levels(&Path::new("a/b/c"), 1), PathBuf::from("b/c"));
         assert_eq!(strip_n_levels(&Path::new("a/b/c"), 2), PathBuf::from("c"));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn owner_and_group_names_work() {
+        let entries = UsableDirEntry::get_entries(&"src").unwrap();
+        let entry = &entries[0];
+        let metadata = entry.metadata().unwrap();
+        assert_eq!(entry.owner_name(), owners::user_name(metadata.uid()));
+        assert_eq!(entry.group_name(), owners::group_name(metadata.gid()));
+    }
+
+    #[test]
+    fn entry_timestamps_work() {
+        let entries = UsableDirEntry::get_entries(&"src").unwrap();
+        let entry = &entries[0];
+        assert_eq!(
+            entry.timestamps().unwrap(),
+            times::Timestamps::from_metadata(&entry.metadata().unwrap()).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_count_and_inode_work() {
+        use std::fs;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        let link = temp_dir.path().join("link");
+        fs::write(&file, "").unwrap();
+        str_path_hard_link!(&file, &link).unwrap();
+        assert!(str_path_hard_link!(&file, &link).is_err());
+        let entries = UsableDirEntry::get_entries(&temp_dir.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].nlink().unwrap(), 2);
+        assert_eq!(entries[0].inode(), entries[1].inode());
+        assert_eq!(entries[0].inode(), fs::metadata(&file).unwrap().ino());
+        assert_eq!(entries[0].device().unwrap(), entries[1].device().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn get_entries_types_work() {
+        use std::fs;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("file"), "").unwrap();
+        ::std::os::unix::fs::symlink("dir", root.join("link")).unwrap();
+        let mut entries = UsableDirEntry::get_entries(&root).unwrap();
+        entries.sort_by_key(|e| e.file_name());
+        let types: Vec<_> = entries
+            .iter()
+            .map(|e| (e.is_dir(), e.is_file(), e.is_symlink()))
+            .collect();
+        // symbolic links aren't followed
+        assert_eq!(
+            types,
+            vec![
+                (true, false, false),
+                (false, true, false),
+                (false, false, true)
+            ]
+        );
+        for entry in entries {
+            assert_eq!(
+                entry.file_type(),
+                fs::symlink_metadata(entry.path()).unwrap().file_type()
+            );
+        }
+    }
+
+    #[test]
+    fn get_entries_lazy_works() {
+        let summary = |mut entries: Vec<UsableDirEntry>| -> Vec<(String, bool, bool)> {
+            entries.sort_by_key(|e| e.file_name());
+            entries
+                .iter()
+                .map(|e| (e.file_name(), e.is_dir(), e.is_file()))
+                .collect()
+        };
+        let lazy = summary(UsableDirEntry::get_entries_lazy(&".").unwrap());
+        assert!(lazy.contains(&("src".to_string(), true, false)));
+        assert!(lazy.contains(&("Cargo.toml".to_string(), false, true)));
+        assert_eq!(lazy, summary(UsableDirEntry::get_entries(&".").unwrap()));
+        assert!(UsableDirEntry::get_entries_lazy(&"Cargo.toml").is_err());
+    }
+
+    #[test]
+    fn path_buf_in_ctx_works() {
+        let ctx = PathCtx::new(&"/work/dir", None);
+        assert_eq!(
+            absolute_path_buf_in_ctx(Path::new("./a/b"), &ctx),
+            PathBuf::from("/work/dir/a/b")
+        );
+        assert_eq!(
+            absolute_path_buf_in_ctx(Path::new(""), &ctx),
+            PathBuf::from("/work/dir")
+        );
+        assert_eq!(
+            relative_path_buf_in_ctx(Path::new("/work/dir/a"), &ctx),
+            Some(PathBuf::from("a"))
+        );
+        assert_eq!(relative_path_buf_in_ctx(Path::new("/tmp"), &ctx), None);
+        assert_eq!(
+            relative_path_buf_in_ctx(Path::new("a"), &ctx),
+            Some(PathBuf::from("a"))
+        );
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    fn expand_home_dir_with_works() {
+        let opts = ExpandOptions::new()
+            .home(&"/home/other")
+            .cwd(&"/pathux/nowhere");
+        assert_eq!(
+            expand_home_dir_with(Path::new("~/a"), &opts),
+            Some(PathBuf::from("/home/other/a"))
+        );
+        assert_eq!(expand_home_dir_with(Path::new("a"), &opts), None);
+        let opts = ExpandOptions::new().env(Default::default());
+        assert_eq!(
+            expand_home_dir_with(Path::new("~/pathux/none"), &opts),
+            None
+        );
+    }
+
+    #[test]
+    fn expand_tilde_is_lexical() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("~")).unwrap();
+        let opts = ExpandOptions::new().home(&"/home/other").cwd(&dir.path());
+        // a "~" in the current directory makes no difference
+        assert_eq!(
+            expand_tilde_with(Path::new("~/a"), &opts).unwrap(),
+            PathBuf::from("/home/other/a")
+        );
+        assert_eq!(
+            expand_tilde_with(Path::new("a/~"), &opts).unwrap(),
+            PathBuf::from("a/~")
+        );
+        let opts = ExpandOptions::new().env(Default::default());
+        assert!(expand_tilde_with(Path::new("~"), &opts).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_works() {
+        let opts = ExpandOptions::new().home(&"/home/other").cwd(&"/srv/www");
+        for (path, expected) in &[
+            ("~/a/../b", "/home/other/b"),
+            ("./x/./y", "/srv/www/x/y"),
+            ("../../../etc", "/etc"),
+            ("/a/b/..", "/a"),
+            ("", "/srv/www"),
+        ] {
+            assert_eq!(
+                resolve_with(Path::new(path), &opts).unwrap(),
+                PathBuf::from(expected)
+            );
+        }
+        assert_eq!(
+            normalize_lexically(Path::new("../a/..")),
+            PathBuf::from("..")
+        );
+    }
+
+    #[test]
+    fn checked_variants_work() {
+        let home = Path::new("/pathux/home");
+        Context::with_home(&home, || {
+            assert_eq!(
+                expand_home_dir_checked(Path::new("~/a")).unwrap(),
+                home.join("a")
+            );
+            assert_eq!(
+                expand_home_dir_checked(Path::new("a")).unwrap(),
+                PathBuf::from("a")
+            );
+        });
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            relative_path_buf_checked(&cwd.join("src")).unwrap(),
+            PathBuf::from("src")
+        );
+        let outside = cwd.parent().unwrap();
+        match relative_path_buf_checked(outside) {
+            Err(PathuxError::NotUnderBase(path)) => assert_eq!(Path::new(&path), outside),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn absolutize_works() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            absolutize(Path::new("./src/../Cargo.toml"), Resolve::Lexical).unwrap(),
+            cwd.join("Cargo.toml")
+        );
+        assert_eq!(
+            absolutize(Path::new("./src/nowhere/.."), Resolve::Physical).unwrap(),
+            cwd.canonicalize().unwrap().join("src")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn absolutize_follows_links_physically() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(base.join("real/sub")).unwrap();
+        std::os::unix::fs::symlink(base.join("real/sub"), base.join("link")).unwrap();
+        let path = base.join("link/../x");
+        assert_eq!(absolutize(&path, Resolve::Lexical).unwrap(), base.join("x"));
+        assert_eq!(
+            absolutize(&path, Resolve::Physical).unwrap(),
+            base.join("real/x")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_path_buf_from_works() {
+        for (path, base, max_up, expected) in &[
+            ("/a/b/c", "/a/d", None, "../b/c"),
+            ("/a/b/c", "/a", None, "b/c"),
+            ("/a/b", "/a/b/", None, "."),
+            ("/a", "/a/b/c", None, "../.."),
+            ("/x/y", "/a/b/c", Some(2), "/x/y"),
+            ("/x/y", "/a/b/c", Some(3), "../../../x/y"),
+            ("/a/./b/../c", "/a/d/..", None, "c"),
+        ] {
+            assert_eq!(
+                relative_path_buf_from(Path::new(path), Path::new(base), *max_up).unwrap(),
+                PathBuf::from(expected)
+            );
+        }
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            relative_path_buf_from(Path::new("src"), &cwd.join("target"), None).unwrap(),
+            PathBuf::from("../src")
+        );
+    }
 }
@@ -0,0 +1,285 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide `ls -l` style formatting of `UsableDirEntry` lists.
+
+use std::fs::{self, Metadata};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+use super::datetime::DateTime;
+#[cfg(unix)]
+use super::owners::Owner;
+use super::sorting::SortBy;
+use super::UsableDirEntry;
+
+#[derive(Debug, Clone, Default)]
+pub struct ListingOptions {
+    /// Show sizes as e.g. "1.5K" rather than a number of bytes.
+    pub human_readable: bool,
+}
+
+/// Format `bytes` in the style of `ls -h` (powers of 1024, one decimal
+/// place for values less than 10).
+pub fn human_readable_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+fn file_type_char(entry: &UsableDirEntry) -> char {
+    if entry.is_dir() {
+        'd'
+    } else if entry.is_symlink() {
+        'l'
+    } else if entry.is_file() {
+        '-'
+    } else {
+        file_type_char_special(entry)
+    }
+}
+
+#[cfg(unix)]
+fn file_type_char_special(entry: &UsableDirEntry) -> char {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = entry.file_type();
+    if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '?'
+    }
+}
+
+#[cfg(not(unix))]
+fn file_type_char_special(_entry: &UsableDirEntry) -> char {
+    '?'
+}
+
+#[cfg(unix)]
+fn permissions_string(metadata: &Metadata) -> String {
+    let mode = metadata.mode();
+    let mut string = String::with_capacity(9);
+    for &(read, write, exec, special, special_char) in &[
+        (0o400, 0o200, 0o100, 0o4000, 's'),
+        (0o040, 0o020, 0o010, 0o2000, 's'),
+        (0o004, 0o002, 0o001, 0o1000, 't'),
+    ] {
+        string.push(if mode & read != 0 { 'r' } else { '-' });
+        string.push(if mode & write != 0 { 'w' } else { '-' });
+        string.push(match (mode & exec != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+#[cfg(not(unix))]
+fn permissions_string(metadata: &Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "r--r--r--".to_string()
+    } else {
+        "rw-rw-rw-".to_string()
+    }
+}
+
+#[cfg(unix)]
+fn link_count(metadata: &Metadata) -> String {
+    metadata.nlink().to_string()
+}
+
+#[cfg(not(unix))]
+fn link_count(_metadata: &Metadata) -> String {
+    "1".to_string()
+}
+
+#[cfg(unix)]
+fn owner_and_group(metadata: &Metadata) -> (String, String) {
+    let owner = Owner::from(metadata);
+    (owner.user_display(), owner.group_display())
+}
+
+#[cfg(not(unix))]
+fn owner_and_group(_metadata: &Metadata) -> (String, String) {
+    ("-".to_string(), "-".to_string())
+}
+
+fn modified_string(metadata: &Metadata) -> String {
+    match metadata.modified() {
+        Ok(time) => {
+            let dt = DateTime::from_system_time(time);
+            format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}",
+                dt.year, dt.month, dt.day, dt.hour, dt.minute
+            )
+        }
+        Err(_) => "?".to_string(),
+    }
+}
+
+/// Sort `entries` by file name in the given order.
+pub fn sort_entries(entries: &mut [UsableDirEntry], sort_by: SortBy) {
+    entries.sort_by(|a, b| sort_by.compare(&a.file_name(), &b.file_name()));
+}
+
+/// Format `entries` as rows of aligned columns in the style of `ls -l`:
+/// permissions, link count, owner, group, size, modification time (UTC)
+/// and name (with the target of symbolic links).  Entries whose metadata
+/// can't be read have "?" in the metadata columns.
+pub fn long_listing(entries: &[UsableDirEntry], options: &ListingOptions) -> Vec<String> {
+    let mut rows: Vec<[String; 7]> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let mut name = entry.file_name();
+        if entry.is_symlink() {
+            if let Ok(target) = fs::read_link(entry.path()) {
+                name = format!("{} -> {}", name, target.to_string_lossy());
+            }
+        }
+        let type_char = file_type_char(entry);
+        let row = match entry.metadata() {
+            Ok(metadata) => {
+                let (owner, group) = owner_and_group(&metadata);
+                let size = if options.human_readable {
+                    human_readable_size(metadata.len())
+                } else {
+                    metadata.len().to_string()
+                };
+                [
+                    format!("{}{}", type_char, permissions_string(&metadata)),
+                    link_count(&metadata),
+                    owner,
+                    group,
+                    size,
+                    modified_string(&metadata),
+                    name,
+                ]
+            }
+            Err(_) => {
+                let unknown = "?".to_string();
+                [
+                    format!("{}?????????", type_char),
+                    unknown.clone(),
+                    unknown.clone(),
+                    unknown.clone(),
+                    unknown.clone(),
+                    unknown,
+                    name,
+                ]
+            }
+        };
+        rows.push(row);
+    }
+    let mut widths = [0usize; 7];
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            format!(
+                "{} {:>w1$} {:<w2$} {:<w3$} {:>w4$} {} {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                row[5],
+                row[6],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3],
+                w4 = widths[4],
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn human_readable_size_works() {
+        assert_eq!(human_readable_size(0), "0");
+        assert_eq!(human_readable_size(1023), "1023");
+        assert_eq!(human_readable_size(1536), "1.5K");
+        assert_eq!(human_readable_size(20 * 1024 * 1024), "20M");
+    }
+
+    #[test]
+    fn long_listing_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("file"), vec![0u8; 2048]).unwrap();
+        let mut entries = UsableDirEntry::get_entries(&root).unwrap();
+        sort_entries(&mut entries, SortBy::Name);
+
+        let rows = long_listing(&entries, &ListingOptions::default());
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with('d'));
+        assert!(rows[0].ends_with(" dir"));
+        assert!(rows[1].starts_with('-'));
+        assert!(rows[1].contains(" 2048 "));
+        assert_eq!(rows[0].len() + 1, rows[1].len());
+
+        let options = ListingOptions {
+            human_readable: true,
+        };
+        let rows = long_listing(&entries, &options);
+        assert!(rows[1].contains(" 2.0K "));
+    }
+
+    #[test]
+    fn sort_entries_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for name in &["f10", "f9", "F1"] {
+            fs::write(temp_dir.path().join(name), "").unwrap();
+        }
+        let mut entries = UsableDirEntry::get_entries(&temp_dir.path()).unwrap();
+        let names = |entries: &[UsableDirEntry]| -> Vec<String> {
+            entries.iter().map(|e| e.file_name()).collect()
+        };
+        sort_entries(&mut entries, SortBy::Name);
+        assert_eq!(names(&entries), vec!["F1", "f10", "f9"]);
+        sort_entries(&mut entries, SortBy::Natural);
+        assert_eq!(names(&entries), vec!["F1", "f9", "f10"]);
+        sort_entries(&mut entries, SortBy::NaturalCollated);
+        assert_eq!(names(&entries), vec!["F1", "f9", "f10"]);
+    }
+}
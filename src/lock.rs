@@ -0,0 +1,139 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide advisory file locks (flock() on unix and
+//! LockFileEx() on Windows) keyed by path so that separate processes can
+//! coordinate access to shared state files.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A lock on the file at a path which is held until the guard is dropped.
+/// The file is created (empty) if it doesn't exist and isn't removed when
+/// the lock is released.
+#[derive(Debug)]
+pub struct PathLock {
+    path: PathBuf,
+    file: File,
+}
+
+fn open_lock_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+fn try_lock(result: Result<(), TryLockError>) -> io::Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+impl PathLock {
+    /// Wait for, and take, an exclusive lock on `path`.
+    pub fn exclusive<P: AsRef<Path>>(path: &P) -> io::Result<PathLock> {
+        let file = open_lock_file(path.as_ref())?;
+        file.lock()?;
+        Ok(PathLock {
+            path: path.as_ref().to_path_buf(),
+            file,
+        })
+    }
+
+    /// Wait for, and take, a shared lock on `path`.
+    pub fn shared<P: AsRef<Path>>(path: &P) -> io::Result<PathLock> {
+        let file = open_lock_file(path.as_ref())?;
+        file.lock_shared()?;
+        Ok(PathLock {
+            path: path.as_ref().to_path_buf(),
+            file,
+        })
+    }
+
+    /// Take an exclusive lock on `path` if that can be done without
+    /// waiting.  `None` is returned if another lock is held.
+    pub fn try_exclusive<P: AsRef<Path>>(path: &P) -> io::Result<Option<PathLock>> {
+        let file = open_lock_file(path.as_ref())?;
+        if try_lock(file.try_lock())? {
+            Ok(Some(PathLock {
+                path: path.as_ref().to_path_buf(),
+                file,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Take a shared lock on `path` if that can be done without waiting.
+    /// `None` is returned if an exclusive lock is held.
+    pub fn try_shared<P: AsRef<Path>>(path: &P) -> io::Result<Option<PathLock>> {
+        let file = open_lock_file(path.as_ref())?;
+        if try_lock(file.try_lock_shared())? {
+            Ok(Some(PathLock {
+                path: path.as_ref().to_path_buf(),
+                file,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The locked file (e.g. for reading or writing the state it guards).
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Drop for PathLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn path_lock_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("state.lock");
+        {
+            let lock = PathLock::exclusive(&path).unwrap();
+            assert_eq!(lock.path(), path);
+            assert!(PathLock::try_exclusive(&path).unwrap().is_none());
+            assert!(PathLock::try_shared(&path).unwrap().is_none());
+        }
+        let shared = PathLock::shared(&path).unwrap();
+        let other = PathLock::try_shared(&path).unwrap();
+        assert!(other.is_some());
+        assert!(PathLock::try_exclusive(&path).unwrap().is_none());
+        drop(shared);
+        drop(other);
+        assert!(PathLock::try_exclusive(&path).unwrap().is_some());
+        assert!(PathLock::exclusive(&temp_dir.path().join("missing/x")).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide conversion to and from Windows extended length
+//! (`\\?\`) paths which aren't subject to the `MAX_PATH` limit.
+//!
+//! The conversions are purely textual so they behave the same way on all
+//! platforms (which is useful when handling paths destined for Windows).
+
+use std::path::{Path, PathBuf};
+
+use super::portability::WINDOWS_MAX_PATH;
+
+const VERBATIM: &str = r"\\?\";
+const VERBATIM_UNC: &str = r"\\?\UNC\";
+const DEVICE: &str = r"\\.\";
+
+fn is_drive_absolute(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\'
+}
+
+// Resolve "." and ".." components (which Windows doesn't do for extended
+// length paths) and drop empty ones.
+fn normalize(text: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in text.split('\\') {
+        match part {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("\\")
+}
+
+/// Convert an absolute drive (`C:\...`) or UNC (`\\server\share\...`)
+/// path to extended length form.  Forward slashes are converted and "."
+/// and ".." components resolved as extended length paths are used
+/// verbatim.  Paths that are already in extended length or device form,
+/// relative paths and non UTF-8 paths are returned unchanged.
+pub fn to_extended_length<P: AsRef<Path>>(path: &P) -> PathBuf {
+    let path: &Path = path.as_ref();
+    let text = match path.to_str() {
+        Some(text) => text,
+        None => return path.to_path_buf(),
+    };
+    if text.starts_with(VERBATIM) || text.starts_with(DEVICE) {
+        return path.to_path_buf();
+    }
+    let text = text.replace('/', "\\");
+    if let Some(unc) = text.strip_prefix(r"\\") {
+        PathBuf::from(format!("{}{}", VERBATIM_UNC, normalize(unc)))
+    } else if is_drive_absolute(&text) {
+        PathBuf::from(format!(
+            "{}{}\\{}",
+            VERBATIM,
+            &text[..2],
+            normalize(&text[3..])
+        ))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Convert an extended length drive or UNC path back to its ordinary
+/// form.  Other paths are returned unchanged.
+pub fn from_extended_length<P: AsRef<Path>>(path: &P) -> PathBuf {
+    let path: &Path = path.as_ref();
+    let text = match path.to_str() {
+        Some(text) => text,
+        None => return path.to_path_buf(),
+    };
+    if let Some(unc) = text.strip_prefix(VERBATIM_UNC) {
+        PathBuf::from(format!(r"\\{}", unc))
+    } else if let Some(rest) = text.strip_prefix(VERBATIM) {
+        if is_drive_absolute(rest) || (rest.len() == 2 && rest.ends_with(':')) {
+            PathBuf::from(rest)
+        } else {
+            path.to_path_buf()
+        }
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// On Windows, return the extended length form of `path` if it is too
+/// long to be used otherwise (relative paths are made absolute first).
+/// On other platforms `path` is returned unchanged.
+pub fn long_path_safe<P: AsRef<Path>>(path: &P) -> PathBuf {
+    let path: &Path = path.as_ref();
+    if cfg!(windows) && path.as_os_str().len() > WINDOWS_MAX_PATH {
+        to_extended_length(&super::absolute_path_buf(path))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_length_conversions_work() {
+        let cases = [
+            (r"C:\Users\me\x", r"\\?\C:\Users\me\x"),
+            (r"\\server\share\dir", r"\\?\UNC\server\share\dir"),
+        ];
+        for &(ordinary, extended) in cases.iter() {
+            assert_eq!(to_extended_length(&ordinary), PathBuf::from(extended));
+            assert_eq!(from_extended_length(&extended), PathBuf::from(ordinary));
+        }
+        assert_eq!(
+            to_extended_length(&r"c:/a/./b/../c"),
+            PathBuf::from(r"\\?\c:\a\c")
+        );
+        assert_eq!(to_extended_length(&r"\\?\C:\x"), PathBuf::from(r"\\?\C:\x"));
+        assert_eq!(to_extended_length(&r"\\.\COM1"), PathBuf::from(r"\\.\COM1"));
+        assert_eq!(to_extended_length(&"relative"), PathBuf::from("relative"));
+        assert_eq!(
+            from_extended_length(&r"\\?\Volume{1234}\x"),
+            PathBuf::from(r"\\?\Volume{1234}\x")
+        );
+        assert_eq!(long_path_safe(&"short"), PathBuf::from("short"));
+    }
+}
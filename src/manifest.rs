@@ -0,0 +1,347 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide generation and verification of manifests
+//! (size, modification time and content hash) for the files in a tree.
+//!
+//! The text form of a manifest is a header line followed by one line per
+//! file: `<hash> <size> <seconds>.<nanoseconds> <relative path>` with any
+//! backslashes or newlines in the path escaped.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::hash::{hash_file, HashAlgo};
+use super::walk::Walker;
+
+const HEADER: &str = "# pw_pathux manifest";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManifestRecord {
+    pub size: u64,
+    pub modified: SystemTime,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Mismatch {
+    /// In the manifest but not in the tree.
+    Missing(String),
+    /// In the tree but not in the manifest.
+    Unexpected(String),
+    Changed {
+        path: String,
+        size: bool,
+        modified: bool,
+        content: bool,
+    },
+}
+
+impl Mismatch {
+    pub fn path(&self) -> &str {
+        match self {
+            Mismatch::Missing(path) => path,
+            Mismatch::Unexpected(path) => path,
+            Mismatch::Changed { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Manifest {
+    algo: HashAlgo,
+    records: BTreeMap<String, ManifestRecord>,
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn escape_path(path: &str) -> String {
+    path.replace('\\', r"\\").replace('\n', r"\n")
+}
+
+fn unescape_path(text: &str) -> io::Result<String> {
+    let mut path = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('\\') => path.push('\\'),
+                Some('n') => path.push('\n'),
+                _ => return Err(invalid_data(format!("{}: bad escape in path", text))),
+            }
+        } else {
+            path.push(ch);
+        }
+    }
+    Ok(path)
+}
+
+fn modified_to_text(modified: SystemTime) -> String {
+    let duration = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:09}", duration.as_secs(), duration.subsec_nanos())
+}
+
+fn modified_from_text(text: &str) -> Option<SystemTime> {
+    let mut parts = text.splitn(2, '.');
+    let secs: u64 = parts.next()?.parse().ok()?;
+    let nanos: u32 = parts.next()?.parse().ok()?;
+    if nanos >= 1_000_000_000 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+// Relative paths are recorded with "/" separators (as in `hash_tree()`)
+// so that manifests can be moved between platforms.
+fn relative_path_string(root: &Path, path: &Path) -> String {
+    let parts: Vec<String> = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .iter()
+        .map(|part| part.to_string_lossy().into_owned())
+        .collect();
+    parts.join("/")
+}
+
+fn record_path(root: &Path, rel_path: &str) -> PathBuf {
+    let mut path = root.to_path_buf();
+    for part in rel_path.split('/') {
+        path.push(part);
+    }
+    path
+}
+
+impl Manifest {
+    /// Record the size, modification time and content hash of every
+    /// regular file below `root`.  Symbolic links are not followed.
+    pub fn generate<P: AsRef<Path>>(root: &P, algo: HashAlgo) -> io::Result<Manifest> {
+        let root: &Path = root.as_ref();
+        let mut records = BTreeMap::new();
+        for entry in Walker::new(&root) {
+            let entry = entry?;
+            if !entry.is_file() {
+                continue;
+            }
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            let record = ManifestRecord {
+                size: metadata.len(),
+                modified: metadata.modified()?,
+                hash: hash_file(&path, algo)?,
+            };
+            records.insert(relative_path_string(root, &path), record);
+        }
+        Ok(Manifest { algo, records })
+    }
+
+    pub fn algo(&self) -> HashAlgo {
+        self.algo
+    }
+
+    pub fn records(&self) -> &BTreeMap<String, ManifestRecord> {
+        &self.records
+    }
+
+    pub fn get(&self, rel_path: &str) -> Option<&ManifestRecord> {
+        self.records.get(rel_path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Compare the tree at `root` with the manifest and return the
+    /// differences sorted by path.  Content is only hashed for files
+    /// whose size is unchanged.
+    pub fn verify<P: AsRef<Path>>(&self, root: &P) -> io::Result<Vec<Mismatch>> {
+        let root: &Path = root.as_ref();
+        let mut mismatches = Vec::new();
+        for (rel_path, record) in self.records.iter() {
+            let path = record_path(root, rel_path);
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    if err.kind() == io::ErrorKind::NotFound {
+                        mismatches.push(Mismatch::Missing(rel_path.clone()));
+                        continue;
+                    }
+                    return Err(err);
+                }
+            };
+            if !metadata.is_file() {
+                mismatches.push(Mismatch::Missing(rel_path.clone()));
+                continue;
+            }
+            let size = metadata.len() != record.size;
+            let modified = metadata.modified()? != record.modified;
+            let content = size || hash_file(&path, self.algo)? != record.hash;
+            if size || modified || content {
+                mismatches.push(Mismatch::Changed {
+                    path: rel_path.clone(),
+                    size,
+                    modified,
+                    content,
+                });
+            }
+        }
+        for entry in Walker::new(&root) {
+            let entry = entry?;
+            if !entry.is_file() {
+                continue;
+            }
+            let rel_path = relative_path_string(root, &entry.path());
+            if !self.records.contains_key(&rel_path) {
+                mismatches.push(Mismatch::Unexpected(rel_path));
+            }
+        }
+        mismatches.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(mismatches)
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Manifest> {
+        let mut lines = text.lines();
+        let algo = match lines.next() {
+            Some(line) if line.starts_with(HEADER) => {
+                let name = line[HEADER.len()..].trim();
+                match HashAlgo::from_name(name) {
+                    Some(algo) => algo,
+                    None => return Err(invalid_data(format!("{}: unknown hash algorithm", name))),
+                }
+            }
+            _ => return Err(invalid_data("missing manifest header".to_string())),
+        };
+        let mut records = BTreeMap::new();
+        for line in lines {
+            let fields: Vec<&str> = line.splitn(4, ' ').collect();
+            if fields.len() != 4 {
+                return Err(invalid_data(format!("{}: malformed manifest line", line)));
+            }
+            let size = fields[1]
+                .parse()
+                .map_err(|_| invalid_data(format!("{}: malformed size", line)))?;
+            let modified = modified_from_text(fields[2])
+                .ok_or_else(|| invalid_data(format!("{}: malformed time", line)))?;
+            let record = ManifestRecord {
+                size,
+                modified,
+                hash: fields[0].to_string(),
+            };
+            records.insert(unescape_path(fields[3])?, record);
+        }
+        Ok(Manifest { algo, records })
+    }
+
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: &P) -> io::Result<Manifest> {
+        Manifest::from_text(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: &P) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", HEADER, self.algo.name())?;
+        for (path, record) in self.records.iter() {
+            writeln!(
+                f,
+                "{} {} {} {}",
+                record.hash,
+                record.size,
+                modified_to_text(record.modified),
+                escape_path(path)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn manifest_round_trips_and_verifies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("one"), "one").unwrap();
+        fs::write(root.join("sub/two words"), "two").unwrap();
+
+        let manifest = Manifest::generate(&root, HashAlgo::Sha256).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest.get("one").unwrap().size, 3);
+        let reloaded = Manifest::from_text(&manifest.to_text()).unwrap();
+        assert_eq!(reloaded, manifest);
+        assert_eq!(reloaded.verify(&root).unwrap(), vec![]);
+
+        fs::remove_file(root.join("one")).unwrap();
+        fs::write(root.join("three"), "three").unwrap();
+        fs::write(root.join("sub/two words"), "TWO").unwrap();
+        let mismatches = manifest.verify(&root).unwrap();
+        assert_eq!(mismatches.len(), 3);
+        assert_eq!(mismatches[0], Mismatch::Missing("one".to_string()));
+        match mismatches[1] {
+            Mismatch::Changed { size, content, .. } => assert!(!size && content),
+            _ => panic!("expected a content change"),
+        }
+        assert_eq!(mismatches[2], Mismatch::Unexpected("three".to_string()));
+    }
+
+    #[test]
+    fn manifest_paths_use_slashes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a").join("b")).unwrap();
+        fs::write(root.join("a").join("b").join("c"), "c").unwrap();
+        let manifest = Manifest::generate(&root, HashAlgo::Sha256).unwrap();
+        assert!(manifest.get("a/b/c").is_some());
+        assert_eq!(manifest.verify(&root).unwrap(), vec![]);
+        fs::remove_file(root.join("a").join("b").join("c")).unwrap();
+        fs::create_dir(root.join("a").join("b").join("c")).unwrap();
+        assert_eq!(
+            manifest.verify(&root).unwrap(),
+            vec![Mismatch::Missing("a/b/c".to_string())]
+        );
+    }
+
+    #[test]
+    fn manifest_paths_are_escaped() {
+        let text = format!("{} fnv1a64\n0 0 0.000000000 a\\\\b\\nc\n", HEADER);
+        let manifest = Manifest::from_text(&text).unwrap();
+        assert!(manifest.get("a\\b\nc").is_some());
+        assert_eq!(manifest.to_text(), text);
+        assert!(Manifest::from_text("0 0 0.0 a\n").is_err());
+    }
+}
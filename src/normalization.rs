@@ -0,0 +1,62 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide Unicode normalization of paths.
+//!
+//! HFS+ (and, for some APIs, APFS) return file names in decomposed form
+//! (NFD) whereas most input is composed (NFC) so names that look the same
+//! may not compare equal unless they are normalized first.
+
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// Return `path` in Unicode normalization form C (composed).
+pub fn path_nfc(path: &str) -> String {
+    path.nfc().collect()
+}
+
+/// Return `path` in Unicode normalization form D (decomposed).
+pub fn path_nfd(path: &str) -> String {
+    path.nfd().collect()
+}
+
+/// Return `true` if `path` is in Unicode normalization form C.
+pub fn is_nfc(path: &str) -> bool {
+    match is_nfc_quick(path.chars()) {
+        IsNormalized::Yes => true,
+        IsNormalized::No => false,
+        IsNormalized::Maybe => path.nfc().eq(path.chars()),
+    }
+}
+
+/// Return `true` if `path1` and `path2` are the same once normalized.
+pub fn paths_equivalent(path1: &str, path2: &str) -> bool {
+    path1 == path2 || path1.nfc().eq(path2.nfc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization_works() {
+        let composed = "caf\u{e9}/r\u{e9}sum\u{e9}";
+        let decomposed = "cafe\u{301}/re\u{301}sume\u{301}";
+        assert_eq!(path_nfc(decomposed), composed);
+        assert_eq!(path_nfd(composed), decomposed);
+        assert!(is_nfc(composed));
+        assert!(!is_nfc(decomposed));
+        assert!(paths_equivalent(composed, decomposed));
+        assert!(!paths_equivalent(composed, "cafe/resume"));
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide (memoized) translation of unix user and group ids
+//! into names.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+use libc;
+
+static USER_NAMES: Mutex<BTreeMap<u32, Option<String>>> = Mutex::new(BTreeMap::new());
+static GROUP_NAMES: Mutex<BTreeMap<u32, Option<String>>> = Mutex::new(BTreeMap::new());
+
+// Call a getpwuid_r() style function growing the buffer as required.
+fn lookup<T, F>(mut get: F, name: fn(&T) -> *const libc::c_char) -> Option<String>
+where
+    F: FnMut(*mut T, *mut libc::c_char, usize, *mut *mut T) -> libc::c_int,
+{
+    let mut buffer: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let mut entry: T = unsafe { mem::zeroed() };
+        let mut result: *mut T = ptr::null_mut();
+        match get(&mut entry, buffer.as_mut_ptr(), buffer.len(), &mut result) {
+            0 if result.is_null() => return None,
+            0 => {
+                let name = unsafe { CStr::from_ptr(name(&entry)) };
+                return Some(name.to_string_lossy().into_owned());
+            }
+            libc::ERANGE if buffer.len() < 1 << 20 => buffer.resize(buffer.len() * 2, 0),
+            _ => return None,
+        }
+    }
+}
+
+fn memoized<F>(cache: &Mutex<BTreeMap<u32, Option<String>>>, id: u32, f: F) -> Option<String>
+where
+    F: FnOnce() -> Option<String>,
+{
+    if let Some(name) = cache.lock().unwrap().get(&id) {
+        return name.clone();
+    }
+    let name = f();
+    cache.lock().unwrap().insert(id, name.clone());
+    name
+}
+
+/// Return the name of the user with id `uid` (if there is one).
+pub fn user_name(uid: u32) -> Option<String> {
+    memoized(&USER_NAMES, uid, || {
+        lookup(
+            |entry, buf, len, result| unsafe { libc::getpwuid_r(uid, entry, buf, len, result) },
+            |entry: &libc::passwd| entry.pw_name,
+        )
+    })
+}
+
+/// Return the name of the group with id `gid` (if there is one).
+pub fn group_name(gid: u32) -> Option<String> {
+    memoized(&GROUP_NAMES, gid, || {
+        lookup(
+            |entry, buf, len, result| unsafe { libc::getgrgid_r(gid, entry, buf, len, result) },
+            |entry: &libc::group| entry.gr_name,
+        )
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+}
+
+impl Owner {
+    /// The user name or, if it is unknown, the uid.
+    pub fn user_display(&self) -> String {
+        self.user_name
+            .clone()
+            .unwrap_or_else(|| self.uid.to_string())
+    }
+
+    /// The group name or, if it is unknown, the gid.
+    pub fn group_display(&self) -> String {
+        self.group_name
+            .clone()
+            .unwrap_or_else(|| self.gid.to_string())
+    }
+}
+
+impl From<&fs::Metadata> for Owner {
+    fn from(metadata: &fs::Metadata) -> Self {
+        Owner {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            user_name: user_name(metadata.uid()),
+            group_name: group_name(metadata.gid()),
+        }
+    }
+}
+
+/// Return the owner and group of `path` (not following symbolic links).
+pub fn lookup_owner<P: AsRef<Path>>(path: &P) -> io::Result<Owner> {
+    Ok(Owner::from(&fs::symlink_metadata(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn name_lookups_work() {
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert!(group_name(0).is_some());
+        assert_eq!(user_name(0xFFFF_FFF0), None);
+        // cached values
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert_eq!(user_name(0xFFFF_FFF0), None);
+    }
+
+    #[test]
+    fn lookup_owner_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let owner = lookup_owner(&temp_dir.path()).unwrap();
+        assert_eq!(owner.uid, unsafe { libc::geteuid() });
+        assert_eq!(owner.user_name, user_name(owner.uid));
+        assert!(!owner.user_display().is_empty());
+        assert!(lookup_owner(&temp_dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide path literals that are checked (and split into
+//! components) at compile time.
+//!
+//! ```compile_fail
+//! #[macro_use]
+//! extern crate pw_pathux;
+//!
+//! fn main() {
+//!     // doubled separator
+//!     let path = path_lit!("~/config//app.toml");
+//! }
+//! ```
+
+use std::fmt;
+
+/// The maximum number of components in a `PathLit`.
+pub const MAX_COMPONENTS: usize = 32;
+
+/// A validated path literal (see `path_lit!`) whose components were found
+/// at compile time.  It implements `AsRef<str>` so it can be used with
+/// `StrPath` and the rest of this crate's `str` path API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathLit {
+    text: &'static str,
+    starts: [usize; MAX_COMPONENTS],
+    ends: [usize; MAX_COMPONENTS],
+    len: usize,
+}
+
+const fn is_illegal(byte: u8) -> bool {
+    // control characters and those reserved on Windows (and '\\' which
+    // isn't a separator everywhere)
+    byte < 0x20
+        || matches!(
+            byte,
+            b'<' | b'>' | b':' | b'"' | b'|' | b'?' | b'*' | b'\\' | 0x7f
+        )
+}
+
+impl PathLit {
+    /// Validate and split `text` panicking (which is a compile error when
+    /// evaluated in a `const`) if it isn't a well formed portable path:
+    /// components are separated by single '/'s, there is no trailing
+    /// separator, "~" only appears as the first component and no
+    /// characters that are illegal in Windows file names are used.
+    pub const fn new(text: &'static str) -> PathLit {
+        let bytes = text.as_bytes();
+        if bytes.is_empty() {
+            panic!("empty path literal");
+        }
+        let mut starts = [0; MAX_COMPONENTS];
+        let mut ends = [0; MAX_COMPONENTS];
+        let mut len = 0;
+        let mut index = 0;
+        if bytes[0] == b'/' {
+            // the root directory
+            ends[0] = 1;
+            len = 1;
+            index = 1;
+            if bytes.len() == 1 {
+                return PathLit {
+                    text,
+                    starts,
+                    ends,
+                    len,
+                };
+            }
+        }
+        loop {
+            let start = index;
+            while index < bytes.len() && bytes[index] != b'/' {
+                if is_illegal(bytes[index]) {
+                    panic!("illegal character in path literal");
+                }
+                index += 1;
+            }
+            if index == start {
+                if index == bytes.len() {
+                    panic!("trailing separator in path literal");
+                }
+                panic!("empty component (doubled separator) in path literal");
+            }
+            if index - start == 1 && bytes[start] == b'~' && start != 0 {
+                panic!("\"~\" is only allowed at the start of a path literal");
+            }
+            if len == MAX_COMPONENTS {
+                panic!("too many components in path literal");
+            }
+            starts[len] = start;
+            ends[len] = index;
+            len += 1;
+            if index == bytes.len() {
+                break;
+            }
+            index += 1;
+        }
+        PathLit {
+            text,
+            starts,
+            ends,
+            len,
+        }
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        self.text
+    }
+
+    /// The number of components (including the root directory).
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Always `false` as empty literals are rejected.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `n`th component (the root directory is "/").
+    pub fn component(&self, n: usize) -> Option<&'static str> {
+        if n < self.len {
+            Some(&self.text[self.starts[n]..self.ends[n]])
+        } else {
+            None
+        }
+    }
+
+    /// The components (without any allocation or parsing).
+    pub fn components(&self) -> impl DoubleEndedIterator<Item = &'static str> + '_ {
+        (0..self.len).map(move |n| &self.text[self.starts[n]..self.ends[n]])
+    }
+}
+
+impl AsRef<str> for PathLit {
+    fn as_ref(&self) -> &str {
+        self.text
+    }
+}
+
+impl fmt::Display for PathLit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.text)
+    }
+}
+
+/// A `PathLit` for the string literal argument which is validated at
+/// compile time (e.g. doubled separators are a compile error).
+#[macro_export]
+macro_rules! path_lit {
+    ( $text:literal ) => {{
+        const PATH: $crate::path_lit::PathLit = $crate::path_lit::PathLit::new($text);
+        PATH
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use str_path::{StrPath, StrPathComponentRef};
+
+    #[test]
+    fn path_lit_works() {
+        let path = path_lit!("~/config/app.toml");
+        assert_eq!(path.as_str(), "~/config/app.toml");
+        assert_eq!(path.len(), 3);
+        assert_eq!(
+            path.components().collect::<Vec<_>>(),
+            vec!["~", "config", "app.toml"]
+        );
+        assert_eq!(path.component(2), Some("app.toml"));
+        assert_eq!(path.component(3), None);
+        assert!(path.path_is_relative_to_home());
+        assert_eq!(path.path_component(0), Some(StrPathComponentRef::HomeDir));
+
+        let root = path_lit!("/etc/app");
+        assert_eq!(
+            root.components().collect::<Vec<_>>(),
+            vec!["/", "etc", "app"]
+        );
+        assert_eq!(path_lit!("/").len(), 1);
+        assert_eq!(path_lit!("../x").components().next_back(), Some("x"));
+    }
+
+    #[test]
+    fn invalid_path_lits_are_rejected() {
+        for text in ["", "a//b", "a/", "a/~/b", "a\\b", "a:b", "//"].iter() {
+            let text: &'static str = text;
+            assert!(std::panic::catch_unwind(|| PathLit::new(text)).is_err());
+        }
+    }
+}
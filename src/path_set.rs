@@ -0,0 +1,141 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide a set of paths which can answer ancestor and
+//! descendant queries (e.g. for ignore rules or selections in file trees).
+
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+
+use super::path_trie::PathTrie;
+
+/// A set of paths compared by component (so that "a/b", "a//b/", "./a/b"
+/// and "a/./b" are the same path).
+#[derive(Debug, Clone, Default)]
+pub struct PathSet {
+    trie: PathTrie<()>,
+}
+
+impl PathSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trie.is_empty()
+    }
+
+    /// Add `path` returning `true` if it wasn't already present.
+    pub fn insert<P: AsRef<Path>>(&mut self, path: &P) -> bool {
+        self.trie.insert(path, ()).is_none()
+    }
+
+    /// Remove `path` returning `true` if it was present.
+    pub fn remove<P: AsRef<Path>>(&mut self, path: &P) -> bool {
+        self.trie.remove(path).is_some()
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: &P) -> bool {
+        self.trie.contains_key(path)
+    }
+
+    /// Return `true` if the set contains `path` or one of its ancestors
+    /// (i.e. a path that `path` starts with).
+    pub fn contains_ancestor_of<P: AsRef<Path>>(&self, path: &P) -> bool {
+        self.trie.longest_prefix(path).is_some()
+    }
+
+    /// Iterate (in component order) over the paths in the set that are
+    /// `path` or its descendants.
+    pub fn descendants_of<P: AsRef<Path>>(&self, path: &P) -> impl Iterator<Item = PathBuf> + '_ {
+        self.trie.descendants(path).map(|(path, _)| path)
+    }
+
+    /// Iterate over the paths in component order.
+    pub fn iter(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.trie.iter().map(|(path, _)| path)
+    }
+
+    /// The paths that are in either set.
+    pub fn union(&self, other: &PathSet) -> PathSet {
+        let (mut union, smaller) = if self.len() >= other.len() {
+            (self.clone(), other)
+        } else {
+            (other.clone(), self)
+        };
+        union.extend(smaller.iter());
+        union
+    }
+
+    /// The paths that are in this set but not in `other`.
+    pub fn difference(&self, other: &PathSet) -> PathSet {
+        self.iter().filter(|path| !other.contains(path)).collect()
+    }
+}
+
+impl<P: AsRef<Path>> Extend<P> for PathSet {
+    fn extend<I: IntoIterator<Item = P>>(&mut self, iter: I) {
+        for path in iter {
+            self.insert(&path);
+        }
+    }
+}
+
+impl<P: AsRef<Path>> FromIterator<P> for PathSet {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let mut set = PathSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_set_works() {
+        let mut set: PathSet = ["src", "src/lib.rs", "target/debug"].iter().collect();
+        assert!(!set.insert(&"src//lib.rs"));
+        assert!(set.contains(&"./src/lib.rs"));
+        assert!(set.contains_ancestor_of(&"target/debug/build/x"));
+        assert!(set.contains_ancestor_of(&"src"));
+        assert!(!set.contains_ancestor_of(&"target"));
+        assert!(!set.contains_ancestor_of(&"srcs/x"));
+        let descendants: Vec<PathBuf> = set.descendants_of(&"src").collect();
+        assert_eq!(
+            descendants,
+            vec![PathBuf::from("src"), PathBuf::from("src/lib.rs")]
+        );
+
+        let other: PathSet = ["src", "docs"].iter().collect();
+        let union = set.union(&other);
+        assert_eq!(union.len(), 4);
+        assert!(union.contains(&"docs"));
+        let difference = set.difference(&other);
+        let paths: Vec<PathBuf> = difference.iter().collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("src/lib.rs"), PathBuf::from("target/debug")]
+        );
+        assert!(set.remove(&"src"));
+        assert!(!set.remove(&"src"));
+        assert!(set.contains_ancestor_of(&"src/lib.rs"));
+        assert!(!set.contains_ancestor_of(&"src/main.rs"));
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide a map keyed by paths that is organized by path
+//! component so that prefix (ancestor) and subtree queries are exact.
+
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone)]
+struct Node<V> {
+    value: Option<V>,
+    children: BTreeMap<OsString, Node<V>>,
+}
+
+impl<V> Default for Node<V> {
+    fn default() -> Self {
+        Node {
+            value: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+impl<V> Node<V> {
+    fn is_empty(&self) -> bool {
+        self.value.is_none() && self.children.is_empty()
+    }
+
+    fn count(&self) -> usize {
+        self.value.iter().count() + self.children.values().map(Node::count).sum::<usize>()
+    }
+
+    fn drain_into(self, path: PathBuf, drained: &mut Vec<(PathBuf, V)>) {
+        if let Some(value) = self.value {
+            drained.push((path.clone(), value));
+        }
+        for (name, child) in self.children {
+            child.drain_into(path.join(name), drained);
+        }
+    }
+}
+
+// "." components (which std only keeps at the start) are ignored.
+fn components(path: &Path) -> Vec<&OsStr> {
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .map(|c| c.as_os_str())
+        .collect()
+}
+
+/// A map from paths to values keyed by their components (so that "a/b",
+/// "a//b/", "./a/b" and "a/./b" are the same key and "a/b" is a prefix of "a/b/c"
+/// but not of "a/bc").
+#[derive(Debug, Clone)]
+pub struct PathTrie<V> {
+    root: Node<V>,
+    len: usize,
+}
+
+impl<V> Default for PathTrie<V> {
+    fn default() -> Self {
+        PathTrie {
+            root: Node::default(),
+            len: 0,
+        }
+    }
+}
+
+impl<V> PathTrie<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn node<P: AsRef<Path>>(&self, path: &P) -> Option<&Node<V>> {
+        let mut node = &self.root;
+        for name in components(path.as_ref()) {
+            node = node.children.get(name)?;
+        }
+        Some(node)
+    }
+
+    /// Insert `value` for `path` returning the value it replaces (if any).
+    pub fn insert<P: AsRef<Path>>(&mut self, path: &P, value: V) -> Option<V> {
+        let mut node = &mut self.root;
+        for name in components(path.as_ref()) {
+            node = node.children.entry(name.to_os_string()).or_default();
+        }
+        let old_value = node.value.replace(value);
+        if old_value.is_none() {
+            self.len += 1;
+        }
+        old_value
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: &P) -> Option<&V> {
+        self.node(path)?.value.as_ref()
+    }
+
+    pub fn get_mut<P: AsRef<Path>>(&mut self, path: &P) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        for name in components(path.as_ref()) {
+            node = node.children.get_mut(name)?;
+        }
+        node.value.as_mut()
+    }
+
+    pub fn contains_key<P: AsRef<Path>>(&self, path: &P) -> bool {
+        self.get(path).is_some()
+    }
+
+    /// Return the longest key that is `path` or one of its ancestors and
+    /// its value.
+    pub fn longest_prefix<P: AsRef<Path>>(&self, path: &P) -> Option<(PathBuf, &V)> {
+        let mut node = &self.root;
+        let mut prefix = PathBuf::new();
+        let mut found = node.value.as_ref().map(|value| (prefix.clone(), value));
+        for name in components(path.as_ref()) {
+            node = match node.children.get(name) {
+                Some(child) => child,
+                None => break,
+            };
+            prefix.push(name);
+            if let Some(ref value) = node.value {
+                found = Some((prefix.clone(), value));
+            }
+        }
+        found
+    }
+
+    /// Iterate (in component order) over `path` and its descendants.
+    pub fn descendants<P: AsRef<Path>>(&self, path: &P) -> Iter<'_, V> {
+        let mut stack = Vec::new();
+        if let Some(node) = self.node(path) {
+            let path: PathBuf = components(path.as_ref()).iter().collect();
+            stack.push((path, node));
+        }
+        Iter { stack }
+    }
+
+    /// Iterate (in component order) over all the entries.
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            stack: vec![(PathBuf::new(), &self.root)],
+        }
+    }
+
+    /// Remove the value for `path` (but not those of its descendants).
+    pub fn remove<P: AsRef<Path>>(&mut self, path: &P) -> Option<V> {
+        fn remove<V>(node: &mut Node<V>, names: &[&OsStr]) -> Option<V> {
+            match names.split_first() {
+                None => node.value.take(),
+                Some((name, rest)) => {
+                    let child = node.children.get_mut(*name)?;
+                    let value = remove(child, rest);
+                    if child.is_empty() {
+                        node.children.remove(*name);
+                    }
+                    value
+                }
+            }
+        }
+        let value = remove(&mut self.root, &components(path.as_ref()));
+        if value.is_some() {
+            self.len -= 1;
+        }
+        value
+    }
+
+    /// Remove `path` and all of its descendants returning the removed
+    /// entries (in component order).
+    pub fn remove_subtree<P: AsRef<Path>>(&mut self, path: &P) -> Vec<(PathBuf, V)> {
+        fn take<V>(node: &mut Node<V>, names: &[&OsStr]) -> Option<Node<V>> {
+            let (name, rest) = names.split_first()?;
+            if rest.is_empty() {
+                node.children.remove(*name)
+            } else {
+                let child = node.children.get_mut(*name)?;
+                let subtree = take(child, rest);
+                if child.is_empty() {
+                    node.children.remove(*name);
+                }
+                subtree
+            }
+        }
+        let names = components(path.as_ref());
+        let subtree = if names.is_empty() {
+            Some(std::mem::take(&mut self.root))
+        } else {
+            take(&mut self.root, &names)
+        };
+        let mut drained = Vec::new();
+        if let Some(subtree) = subtree {
+            self.len -= subtree.count();
+            subtree.drain_into(names.iter().collect(), &mut drained);
+        }
+        drained
+    }
+}
+
+/// An iterator over the entries of a `PathTrie` (or part of one).
+pub struct Iter<'a, V> {
+    stack: Vec<(PathBuf, &'a Node<V>)>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (PathBuf, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, node)) = self.stack.pop() {
+            for (name, child) in node.children.iter().rev() {
+                self.stack.push((path.join(name), child));
+            }
+            if let Some(ref value) = node.value {
+                return Some((path, value));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_trie_works() {
+        let mut trie = PathTrie::new();
+        assert_eq!(trie.insert(&"a/b", 1), None);
+        assert_eq!(trie.insert(&"a//b/", 2), Some(1));
+        trie.insert(&"a/b/c/d", 3);
+        trie.insert(&"a/bc", 4);
+        trie.insert(&"x", 5);
+        assert_eq!(trie.len(), 4);
+        assert_eq!(trie.get(&"a/./b"), Some(&2));
+        assert_eq!(trie.get(&"a"), None);
+        *trie.get_mut(&"x").unwrap() += 1;
+        assert_eq!(trie.get(&"x"), Some(&6));
+
+        assert_eq!(
+            trie.longest_prefix(&"a/b/c/e"),
+            Some((PathBuf::from("a/b"), &2))
+        );
+        assert_eq!(trie.longest_prefix(&"a/bcd"), None);
+        let descendants: Vec<_> = trie.descendants(&"a/b").collect();
+        assert_eq!(
+            descendants,
+            vec![(PathBuf::from("a/b"), &2), (PathBuf::from("a/b/c/d"), &3)]
+        );
+        let all: Vec<_> = trie.iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            all,
+            ["a/b", "a/b/c/d", "a/bc", "x"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(trie.remove(&"a/b"), Some(2));
+        assert_eq!(trie.remove(&"a/b"), None);
+        assert_eq!(trie.get(&"a/b/c/d"), Some(&3));
+        trie.insert(&"a/b", 2);
+        let removed = trie.remove_subtree(&"a/b");
+        assert_eq!(
+            removed,
+            vec![(PathBuf::from("a/b"), 2), (PathBuf::from("a/b/c/d"), 3)]
+        );
+        assert_eq!(trie.len(), 2);
+        assert_eq!(trie.descendants(&"a/b").count(), 0);
+        assert_eq!(trie.remove_subtree(&"a").len(), 1);
+        assert_eq!(trie.remove_subtree(&"").len(), 1);
+        assert!(trie.is_empty());
+    }
+}
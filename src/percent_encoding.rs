@@ -0,0 +1,90 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide percent encoding (as used in URLs) of paths.
+
+use super::error::PathuxError;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+// The characters allowed in a URL path (other than "/") without encoding.
+fn is_path_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&byte)
+}
+
+/// Percent encode `path` for use as the path of a URL (or a link in a
+/// document) following the RFC 3986 rules for path segments.  Separators
+/// ("/") are left as is.
+pub fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for &byte in path.as_bytes() {
+        if byte == b'/' || is_path_char(byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push('%');
+            encoded.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+            encoded.push(HEX_DIGITS[(byte & 0xF) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Decode the percent encoded `path`.  Fails if an escape is malformed or
+/// the result isn't valid UTF-8.
+pub fn percent_decode_path(path: &str) -> Result<String, PathuxError> {
+    decode(path).ok_or_else(|| PathuxError::InvalidEncoding(path.to_string()))
+}
+
+fn decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = text.get(index + 1..index + 3)?;
+            if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encoding_works() {
+        assert_eq!(
+            percent_encode_path("/a b/c%d#e?é"),
+            "/a%20b/c%25d%23e%3F%C3%A9"
+        );
+        assert_eq!(percent_encode_path("x-y_z~(1);a=b:@"), "x-y_z~(1);a=b:@");
+        assert_eq!(percent_encode_path(r"a\b[c]"), "a%5Cb%5Bc%5D");
+        assert_eq!(
+            percent_decode_path("/a%20b/c%25d%c3%a9").unwrap(),
+            "/a b/c%dé"
+        );
+        assert!(percent_decode_path("%2").is_err());
+        assert!(percent_decode_path("%zz").is_err());
+        assert!(percent_decode_path("%+1").is_err());
+        assert!(percent_decode_path("%FF").is_err());
+    }
+}
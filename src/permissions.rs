@@ -0,0 +1,444 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide queries as to whether the current process may
+//! read, write or execute a file and setting of permissions with `chmod`
+//! style modes.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{self, Metadata};
+    use std::io;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    use libc;
+
+    use sys::c_path;
+
+    // Use the effective (rather than real) user and group ids as that is
+    // what matters when the file is actually used.
+    fn access(path: &Path, mode: libc::c_int) -> bool {
+        match c_path(path) {
+            Ok(c_path) => unsafe {
+                libc::faccessat(libc::AT_FDCWD, c_path.as_ptr(), mode, libc::AT_EACCESS) == 0
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn is_readable(path: &Path) -> bool {
+        access(path, libc::R_OK)
+    }
+
+    pub fn is_writable(path: &Path) -> bool {
+        access(path, libc::W_OK)
+    }
+
+    // access() says that root may execute any file with an execute bit
+    // set but it also says root may "execute" directories.
+    pub fn is_executable(path: &Path) -> bool {
+        path.is_file() && access(path, libc::X_OK)
+    }
+
+    pub fn mode(metadata: &Metadata) -> u32 {
+        metadata.permissions().mode() & 0o7777
+    }
+
+    pub fn set_mode(path: &Path, _metadata: &Metadata, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::env;
+    use std::fs::{self, Metadata};
+    use std::io;
+    use std::path::Path;
+
+    const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+    pub fn is_readable(path: &Path) -> bool {
+        if path.is_dir() {
+            fs::read_dir(path).is_ok()
+        } else {
+            fs::File::open(path).is_ok()
+        }
+    }
+
+    // Windows ignores the read only attribute for directories.
+    pub fn is_writable(path: &Path) -> bool {
+        match fs::metadata(path) {
+            Ok(metadata) => metadata.is_dir() || !metadata.permissions().readonly(),
+            Err(_) => false,
+        }
+    }
+
+    // Windows decides what is executable by extension (as listed in the
+    // PATHEXT environment variable).
+    pub fn is_executable(path: &Path) -> bool {
+        let extension = match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) => format!(".{}", extension),
+            None => return false,
+        };
+        let path_ext = env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+        path.is_file()
+            && path_ext
+                .split(';')
+                .any(|ext| ext.eq_ignore_ascii_case(&extension))
+    }
+
+    // Only the read only attribute can be changed so emulate a mode with
+    // it (and use that to decide whether to set it).
+    pub fn mode(metadata: &Metadata) -> u32 {
+        let mode = if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o666
+        };
+        if metadata.is_dir() {
+            mode | 0o111
+        } else {
+            mode
+        }
+    }
+
+    pub fn set_mode(path: &Path, metadata: &Metadata, mode: u32) -> io::Result<()> {
+        let mut permissions = metadata.permissions();
+        permissions.set_readonly(mode & 0o200 == 0);
+        fs::set_permissions(path, permissions)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::fs::{self, Metadata};
+    use std::io;
+    use std::path::Path;
+
+    pub fn is_readable(path: &Path) -> bool {
+        path.exists()
+    }
+
+    pub fn is_writable(path: &Path) -> bool {
+        match fs::metadata(path) {
+            Ok(metadata) => !metadata.permissions().readonly(),
+            Err(_) => false,
+        }
+    }
+
+    pub fn is_executable(_path: &Path) -> bool {
+        false
+    }
+
+    // Only the read only attribute can be changed so emulate a mode with
+    // it (and use that to decide whether to set it).
+    pub fn mode(metadata: &Metadata) -> u32 {
+        let mode = if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o666
+        };
+        if metadata.is_dir() {
+            mode | 0o111
+        } else {
+            mode
+        }
+    }
+
+    pub fn set_mode(path: &Path, metadata: &Metadata, mode: u32) -> io::Result<()> {
+        let mut permissions = metadata.permissions();
+        permissions.set_readonly(mode & 0o200 == 0);
+        fs::set_permissions(path, permissions)
+    }
+}
+
+/// Return `true` if `path` exists and the current process may read it.
+pub fn path_is_readable<P: AsRef<Path>>(path: &P) -> bool {
+    imp::is_readable(path.as_ref())
+}
+
+/// Return `true` if `path` exists and the current process may write it.
+pub fn path_is_writable<P: AsRef<Path>>(path: &P) -> bool {
+    imp::is_writable(path.as_ref())
+}
+
+/// Return `true` if `path` is a file that the current process may
+/// execute.
+pub fn path_is_executable<P: AsRef<Path>>(path: &P) -> bool {
+    imp::is_executable(path.as_ref())
+}
+
+/// Return `true` if `path` has no write permissions (or, on Windows, has
+/// the read only attribute set).
+pub fn is_readonly<P: AsRef<Path>>(path: &P) -> io::Result<bool> {
+    Ok(imp::mode(&fs::metadata(path)?) & 0o222 == 0)
+}
+
+/// Make `path` read only (by removing all write permissions) or writable
+/// (by giving the owner write permission).
+pub fn set_readonly<P: AsRef<Path>>(path: &P, readonly: bool) -> io::Result<()> {
+    let path: &Path = path.as_ref();
+    let metadata = fs::metadata(path)?;
+    let mode = imp::mode(&metadata);
+    let mode = if readonly {
+        mode & !0o222
+    } else {
+        mode | 0o200
+    };
+    imp::set_mode(path, &metadata, mode)
+}
+
+#[cfg(all(feature = "immutable", any(target_os = "linux", target_os = "android")))]
+mod immutable {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use libc;
+
+    // from linux/fs.h
+    const FS_IMMUTABLE_FL: libc::c_int = 0x0000_0010;
+
+    pub fn get_flags(file: &File) -> io::Result<libc::c_int> {
+        let mut flags: libc::c_int = 0;
+        if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(flags)
+    }
+
+    /// Return `true` if `path` has the immutable attribute (see chattr(1))
+    /// set.
+    pub fn is_immutable<P: AsRef<Path>>(path: &P) -> io::Result<bool> {
+        Ok(get_flags(&File::open(path)?)? & FS_IMMUTABLE_FL != 0)
+    }
+
+    /// Set or clear the immutable attribute of `path` (which needs the
+    /// CAP_LINUX_IMMUTABLE capability).
+    pub fn set_immutable<P: AsRef<Path>>(path: &P, immutable: bool) -> io::Result<()> {
+        let file = File::open(path)?;
+        let flags = get_flags(&file)?;
+        let flags = if immutable {
+            flags | FS_IMMUTABLE_FL
+        } else {
+            flags & !FS_IMMUTABLE_FL
+        };
+        if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "immutable", any(target_os = "linux", target_os = "android")))]
+pub use self::immutable::{is_immutable, set_immutable};
+
+fn invalid_mode(spec: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{:?}: invalid mode", spec),
+    )
+}
+
+// The permission bits that each of "u", "g" and "o" controls.
+fn who_mask(who: char) -> u32 {
+    match who {
+        'u' => 0o4700,
+        'g' => 0o2070,
+        'o' => 0o1007,
+        _ => 0o7777,
+    }
+}
+
+// Apply the `chmod` style `spec` (octal or symbolic) to `mode`.
+fn apply_mode(spec: &str, mode: u32, is_dir: bool) -> io::Result<u32> {
+    if !spec.is_empty() && spec.len() <= 4 && spec.chars().all(|c| c.is_digit(8)) {
+        return u32::from_str_radix(spec, 8).map_err(|_| invalid_mode(spec));
+    }
+    let mut mode = mode;
+    for clause in spec.split(',') {
+        let op_index = clause
+            .find(['+', '-', '='])
+            .ok_or_else(|| invalid_mode(spec))?;
+        let (who, mut ops) = clause.split_at(op_index);
+        if !who.chars().all(|c| "ugoa".contains(c)) {
+            return Err(invalid_mode(spec));
+        }
+        let mask = if who.is_empty() {
+            who_mask('a')
+        } else {
+            who.chars().fold(0, |mask, c| mask | who_mask(c))
+        };
+        while let Some(op) = ops.chars().next() {
+            let perms = &ops[1..];
+            let end = perms.find(['+', '-', '=']).unwrap_or(perms.len());
+            let (perms, rest) = perms.split_at(end);
+            ops = rest;
+            let mut bits = 0;
+            for perm in perms.chars() {
+                bits |= match perm {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    'X' if is_dir || mode & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    'u' => ((mode >> 6) & 0o7) * 0o111,
+                    'g' => ((mode >> 3) & 0o7) * 0o111,
+                    'o' => (mode & 0o7) * 0o111,
+                    _ => return Err(invalid_mode(spec)),
+                };
+            }
+            bits &= mask;
+            mode = match op {
+                '+' => mode | bits,
+                '-' => mode & !bits,
+                _ => (mode & !mask) | bits,
+            };
+        }
+    }
+    Ok(mode)
+}
+
+/// Set the permissions of `path` using a `chmod` style mode which may be
+/// octal (e.g. "755") or symbolic (e.g. "u+x,go-w").  If no "who" is
+/// given in a symbolic clause "a" is assumed (the umask is ignored).  On
+/// Windows only the read only attribute can be changed: it is set if the
+/// resulting mode lacks the owner write permission.
+pub fn set_mode<P: AsRef<Path>>(path: &P, spec: &str) -> io::Result<()> {
+    let path: &Path = path.as_ref();
+    let metadata = fs::metadata(path)?;
+    let mode = apply_mode(spec, imp::mode(&metadata), metadata.is_dir())?;
+    imp::set_mode(path, &metadata, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn permission_queries_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file.txt");
+        fs::write(&file, "").unwrap();
+        assert!(path_is_readable(&file));
+        assert!(path_is_writable(&file));
+        assert!(!path_is_executable(&file));
+        assert!(path_is_readable(&temp_dir.path()));
+        assert!(!path_is_executable(&temp_dir.path()));
+        let missing = temp_dir.path().join("missing");
+        assert!(!path_is_readable(&missing));
+        assert!(!path_is_writable(&missing));
+        assert!(!path_is_executable(&missing));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_query_works() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("script");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        assert!(!path_is_executable(&script));
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(path_is_executable(&script));
+        assert!(str_path_is_executable!(script.to_str().unwrap()));
+    }
+
+    #[test]
+    fn readonly_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(!is_readonly(&file).unwrap());
+        set_readonly(&file, true).unwrap();
+        assert!(is_readonly(&file).unwrap());
+        assert!(fs::metadata(&file).unwrap().permissions().readonly());
+        set_readonly(&file, false).unwrap();
+        assert!(!is_readonly(&file).unwrap());
+        assert!(is_readonly(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[cfg(all(feature = "immutable", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn immutable_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(!is_immutable(&file).unwrap_or(false));
+        // needs privileges and a file system that supports it
+        if set_immutable(&file, true).is_err() {
+            return;
+        }
+        assert!(is_immutable(&file).unwrap());
+        assert!(fs::write(&file, "changed").is_err());
+        set_immutable(&file, false).unwrap();
+        assert!(!is_immutable(&file).unwrap());
+        fs::write(&file, "changed").unwrap();
+    }
+
+    #[test]
+    fn apply_mode_works() {
+        let apply = |spec, mode| apply_mode(spec, mode, false).unwrap();
+        assert_eq!(apply("755", 0o600), 0o755);
+        assert_eq!(apply("u+x", 0o644), 0o744);
+        assert_eq!(apply("u+x,go-w", 0o666), 0o744);
+        assert_eq!(apply("a=r", 0o777), 0o444);
+        assert_eq!(apply("+x", 0o644), 0o755);
+        assert_eq!(apply("go=u", 0o640), 0o666);
+        assert_eq!(apply("u=rw,g=r,o=", 0o777), 0o640);
+        assert_eq!(apply("g+s,o+t", 0o755), 0o3755);
+        assert_eq!(apply("u-w+x", 0o644), 0o544);
+        assert_eq!(apply("a+X", 0o644), 0o644);
+        assert_eq!(apply("a+X", 0o744), 0o755);
+        assert_eq!(apply_mode("a+X", 0o644, true).unwrap(), 0o755);
+        assert!(apply_mode("u+q", 0o644, false).is_err());
+        assert!(apply_mode("z+x", 0o644, false).is_err());
+        assert!(apply_mode("ux", 0o644, false).is_err());
+        assert!(apply_mode("8", 0o644, false).is_err());
+    }
+
+    #[test]
+    fn set_mode_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        set_mode(&file, "a-w").unwrap();
+        assert!(fs::metadata(&file).unwrap().permissions().readonly());
+        set_mode(&file, "u+w").unwrap();
+        assert!(!fs::metadata(&file).unwrap().permissions().readonly());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            set_mode(&file, "640").unwrap();
+            set_mode(&file, "u+x,g-r").unwrap();
+            let mode = fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o7777, 0o700);
+        }
+        assert!(set_mode(&file, "bogus").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide PID files for ensuring that only one instance of
+//! a program is running.
+
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::lock::PathLock;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+mod imp {
+    use libc;
+
+    pub fn is_running(pid: u32) -> bool {
+        // the process exists if we may (or merely aren't allowed to) signal it
+        let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+        result == 0 || ::std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    pub fn is_running(pid: u32) -> bool {
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle.is_null() {
+                return false;
+            }
+            let mut exit_code = 0;
+            let ok = GetExitCodeProcess(handle, &mut exit_code);
+            CloseHandle(handle);
+            ok != 0 && exit_code == STILL_ACTIVE
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    // without a way to check assume that the owner is still running
+    pub fn is_running(_pid: u32) -> bool {
+        true
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn write_pid(path: &Path, pid: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{}", pid)?;
+    file.sync_all()
+}
+
+// Make `temp_path` (containing `pid`) the PID file `path` unless that is
+// held by another running process.
+fn link_pid_file(path: &Path, temp_path: &Path, pid: u32) -> io::Result<()> {
+    match fs::hard_link(temp_path, path) {
+        Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+        result => return result,
+    }
+    // only one process at a time may decide that the file is stale and
+    // replace it (otherwise one could remove the other's new file)
+    let _lock = PathLock::exclusive(&with_suffix(path, ".~lock~"))?;
+    match read_pid(&path) {
+        Ok(Some(owner)) if owner == pid || !imp::is_running(owner) => fs::rename(temp_path, path),
+        Ok(Some(owner)) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{}: in use by process {}", path.display(), owner),
+        )),
+        // PID files are never written in place so this isn't one that was
+        // interrupted and it isn't safe to assume that it's unused
+        Ok(None) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{}: in use (no process ID recorded)", path.display()),
+        )),
+        // released while we were waiting
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => fs::hard_link(temp_path, path),
+        Err(err) => Err(err),
+    }
+}
+
+/// A file containing the process ID of the (single) running instance of
+/// a program which is removed when this is dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    pid: u32,
+}
+
+impl PidFile {
+    /// Create the PID file `path` containing this process's ID.  Files
+    /// left behind by processes that are no longer running are replaced
+    /// but if the recorded process is still running (or the file doesn't
+    /// contain a process ID) an error of kind `AlreadyExists` is returned.
+    /// Replacement is done under a lock on the file "`path`.~lock~" which
+    /// is left in place.
+    pub fn acquire<P: AsRef<Path>>(path: &P) -> io::Result<PidFile> {
+        let path: &Path = path.as_ref();
+        let pid = process::id();
+        // the ID is written to a private file which is then linked into
+        // place so that the PID file is never seen empty or half written
+        let temp_path = with_suffix(
+            path,
+            &format!(".{}.{}~", pid, COUNTER.fetch_add(1, Ordering::Relaxed)),
+        );
+        let result = write_pid(&temp_path, pid).and_then(|_| link_pid_file(path, &temp_path, pid));
+        let _ = fs::remove_file(&temp_path);
+        result?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+            pid,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // don't remove a file that has been taken over by another process
+        if let Ok(Some(pid)) = read_pid(&self.path) {
+            if pid == self.pid {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+/// Return the process ID recorded in the PID file `path` or `None` if it
+/// doesn't contain one.
+pub fn read_pid<P: AsRef<Path>>(path: &P) -> io::Result<Option<u32>> {
+    Ok(fs::read_to_string(path)?.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn pid_file_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("tool.pid");
+        {
+            let pid_file = PidFile::acquire(&path).unwrap();
+            assert_eq!(read_pid(&path).unwrap(), Some(process::id()));
+            assert_eq!(pid_file.pid(), process::id());
+        }
+        assert!(!path.exists());
+
+        // a live process other than this one
+        let mut child = process::Command::new(if cfg!(windows) { "cmd" } else { "sh" })
+            .args(if cfg!(windows) {
+                ["/C", "pause"]
+            } else {
+                ["-c", "read x"]
+            })
+            .stdin(process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        fs::write(&path, format!("{}\n", child.id())).unwrap();
+        let err = PidFile::acquire(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        drop(child.stdin.take());
+        child.wait().unwrap();
+
+        // left behind by a dead one
+        let _pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(read_pid(&path).unwrap(), Some(process::id()));
+
+        // empty or unreadable files aren't assumed to be stale
+        for contents in &["", "garbage"] {
+            fs::write(&path, contents).unwrap();
+            let err = PidFile::acquire(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        }
+        fs::remove_file(&path).unwrap();
+        let _pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(read_pid(&path).unwrap(), Some(process::id()));
+        assert_eq!(
+            fs::read_dir(temp_dir.path()).unwrap().count(),
+            2,
+            "only the PID file and its lock should remain"
+        );
+    }
+}
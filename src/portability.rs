@@ -0,0 +1,362 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide detection of file paths that won't work (or won't
+//! work the same way) on all of the common platforms.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use super::hash::{hash_bytes, HashAlgo};
+use super::normalization::is_nfc;
+use super::sanitize::{
+    has_trailing_dot_or_space, is_illegal_on_windows, is_windows_reserved_name,
+    shorten_keeping_extension,
+};
+
+/// The longest path (in UTF-16 units excluding the terminating nul) that
+/// Windows accepts without the extended length prefix.
+pub const WINDOWS_MAX_PATH: usize = 259;
+/// The longest file name (in bytes) allowed by most file systems.
+pub const MAX_COMPONENT_LEN: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortabilityIssue {
+    /// The component contains a character that Windows doesn't allow.
+    IllegalChar { component: String, ch: char },
+    /// The component is a Windows device name e.g. "CON" or "nul.txt".
+    ReservedName(String),
+    /// Windows silently drops trailing dots and spaces.
+    TrailingDotOrSpace(String),
+    /// The component is longer than `MAX_COMPONENT_LEN` bytes.
+    ComponentTooLong { component: String, len: usize },
+    /// The whole path is longer than `WINDOWS_MAX_PATH`.
+    PathTooLong(usize),
+    /// An existing entry in the same directory has the same name except
+    /// for case (and would clash on case insensitive file systems).
+    CaseCollision {
+        component: String,
+        existing: PathBuf,
+    },
+    /// The component isn't in Unicode normalization form C (and may be
+    /// changed by macOS or be unequal to a name that looks the same).
+    NotNfc(String),
+}
+
+impl fmt::Display for PortabilityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PortabilityIssue::IllegalChar { component, ch } => {
+                write!(f, "{:?}: contains illegal character {:?}", component, ch)
+            }
+            PortabilityIssue::ReservedName(component) => {
+                write!(f, "{:?}: reserved name on Windows", component)
+            }
+            PortabilityIssue::TrailingDotOrSpace(component) => {
+                write!(f, "{:?}: ends with a dot or space", component)
+            }
+            PortabilityIssue::ComponentTooLong { component, len } => {
+                write!(f, "{:?}: name is {} bytes long", component, len)
+            }
+            PortabilityIssue::PathTooLong(len) => write!(f, "path is {} characters long", len),
+            PortabilityIssue::CaseCollision {
+                component,
+                existing,
+            } => write!(
+                f,
+                "{:?}: differs only in case from {}",
+                component,
+                existing.display()
+            ),
+            PortabilityIssue::NotNfc(component) => {
+                write!(f, "{:?}: not in Unicode normalization form C", component)
+            }
+        }
+    }
+}
+
+fn check_name(name: &str, issues: &mut Vec<PortabilityIssue>) {
+    for ch in name.chars() {
+        if ch == '\\' || is_illegal_on_windows(ch) {
+            issues.push(PortabilityIssue::IllegalChar {
+                component: name.to_string(),
+                ch,
+            });
+        }
+    }
+    if is_windows_reserved_name(name) {
+        issues.push(PortabilityIssue::ReservedName(name.to_string()));
+    }
+    if has_trailing_dot_or_space(name) {
+        issues.push(PortabilityIssue::TrailingDotOrSpace(name.to_string()));
+    }
+    if name.len() > MAX_COMPONENT_LEN {
+        issues.push(PortabilityIssue::ComponentTooLong {
+            component: name.to_string(),
+            len: name.len(),
+        });
+    }
+    if !is_nfc(name) {
+        issues.push(PortabilityIssue::NotNfc(name.to_string()));
+    }
+}
+
+fn check_case_collisions(dir_path: &Path, name: &str, issues: &mut Vec<PortabilityIssue>) {
+    let lower_name = name.to_lowercase();
+    if let Ok(read_dir) = fs::read_dir(dir_path) {
+        for dir_entry in read_dir.filter_map(Result::ok) {
+            let entry_name = dir_entry.file_name().to_string_lossy().into_owned();
+            if entry_name != name && entry_name.to_lowercase() == lower_name {
+                issues.push(PortabilityIssue::CaseCollision {
+                    component: name.to_string(),
+                    existing: dir_entry.path(),
+                });
+            }
+        }
+    }
+}
+
+/// Return the reasons (if any) that `path` may not be usable on other
+/// platforms.  Case collisions are only detected for those parts of the
+/// path that already exist.
+pub fn check_portability<P: AsRef<Path>>(path: &P) -> Vec<PortabilityIssue> {
+    let path: &Path = path.as_ref();
+    let mut issues = Vec::new();
+    let mut dir_path = PathBuf::new();
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            let name = name.to_string_lossy();
+            check_name(&name, &mut issues);
+            if dir_path.as_os_str().is_empty() {
+                check_case_collisions(Path::new("."), &name, &mut issues);
+            } else {
+                check_case_collisions(&dir_path, &name, &mut issues);
+            }
+        }
+        dir_path.push(component);
+    }
+    let len = path.to_string_lossy().encode_utf16().count();
+    if len > WINDOWS_MAX_PATH {
+        issues.push(PortabilityIssue::PathTooLong(len));
+    }
+    issues
+}
+
+/// Return the groups of entries in `dir_path` whose names differ only in
+/// case (and would clash on case insensitive file systems).  Names within a
+/// group, and the groups themselves, are sorted.
+pub fn find_case_collisions<P: AsRef<Path>>(dir_path: &P) -> io::Result<Vec<Vec<String>>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for dir_entry in fs::read_dir(dir_path)? {
+        let name = dir_entry?.file_name().to_string_lossy().into_owned();
+        groups.entry(name.to_lowercase()).or_default().push(name);
+    }
+    let mut collisions: Vec<Vec<String>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    collisions.sort();
+    Ok(collisions)
+}
+
+/// Length limits (in bytes) for file names and whole paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_component: usize,
+    pub max_path: usize,
+}
+
+impl Limits {
+    /// The limits of the platform this code is running on.  (Windows
+    /// limits are in UTF-16 units which never outnumber the bytes.)
+    pub fn native() -> Limits {
+        let max_path = if cfg!(windows) {
+            WINDOWS_MAX_PATH
+        } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+            1023
+        } else {
+            4095
+        };
+        Limits {
+            max_component: MAX_COMPONENT_LEN,
+            max_path,
+        }
+    }
+}
+
+// The length of the "-" and eight hex digits appended to shortened names.
+const HASH_SUFFIX_LEN: usize = 9;
+
+// Shorten `name` to at most `max_len` bytes keeping (short) extensions
+// and appending part of its hash so that different names stay different.
+fn shorten_name(name: &str, max_len: usize) -> String {
+    if name.len() <= max_len {
+        return name.to_string();
+    }
+    let hash = hash_bytes(name.as_bytes(), HashAlgo::Sha256);
+    shorten_keeping_extension(name, max_len, &format!("-{}", &hash[..HASH_SUFFIX_LEN - 1]))
+}
+
+/// Shorten the components of `path` (preserving short extensions) so that
+/// it satisfies `limits`.  Shortened names end with part of a hash of the
+/// original name so the result is deterministic and distinct names are
+/// (very probably) kept distinct.  Returns `None` if the limits can't be
+/// met.
+pub fn fit_to_limits(path: &str, limits: &Limits) -> Option<String> {
+    if limits.max_component <= HASH_SUFFIX_LEN {
+        return None;
+    }
+    let mut components: Vec<(String, bool)> = Path::new(path)
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => (
+                shorten_name(&name.to_string_lossy(), limits.max_component),
+                true,
+            ),
+            _ => (component.as_os_str().to_string_lossy().into_owned(), false),
+        })
+        .collect();
+    let join = |components: &[(String, bool)]| {
+        components
+            .iter()
+            .fold(PathBuf::new(), |path, (name, _)| path.join(name))
+            .to_string_lossy()
+            .into_owned()
+    };
+    let mut result = join(&components);
+    while result.len() > limits.max_path {
+        let excess = result.len() - limits.max_path;
+        let (name, _) = components
+            .iter_mut()
+            .filter(|(name, is_normal)| *is_normal && name.len() > HASH_SUFFIX_LEN + 1)
+            .max_by_key(|(name, _)| name.len())?;
+        let new_len = name.len().saturating_sub(excess).max(HASH_SUFFIX_LEN + 1);
+        *name = shorten_name(name, new_len);
+        result = join(&components);
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn check_portability_works() {
+        assert!(check_portability(&"src/lib.rs").is_empty());
+        assert_eq!(
+            check_portability(&"a/b?c"),
+            vec![PortabilityIssue::IllegalChar {
+                component: "b?c".to_string(),
+                ch: '?'
+            }]
+        );
+        assert_eq!(
+            check_portability(&"aux.c"),
+            vec![PortabilityIssue::ReservedName("aux.c".to_string())]
+        );
+        assert_eq!(
+            check_portability(&"dir./x"),
+            vec![PortabilityIssue::TrailingDotOrSpace("dir.".to_string())]
+        );
+        assert_eq!(
+            check_portability(&"cafe\u{301}"),
+            vec![PortabilityIssue::NotNfc("cafe\u{301}".to_string())]
+        );
+        assert!(check_portability(&"caf\u{e9}").is_empty());
+        let long_name = "x".repeat(300);
+        let issues = check_portability(&long_name);
+        assert!(issues.contains(&PortabilityIssue::ComponentTooLong {
+            component: long_name.clone(),
+            len: 300
+        }));
+        assert!(issues.contains(&PortabilityIssue::PathTooLong(300)));
+    }
+
+    #[test]
+    fn check_portability_finds_case_collisions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("README"), "").unwrap();
+        let issues = check_portability(&root.join("readme"));
+        // case insensitive file systems only have one entry
+        if fs::read_dir(root).unwrap().count() == 1 && !root.join("Readme").exists() {
+            assert_eq!(
+                issues,
+                vec![PortabilityIssue::CaseCollision {
+                    component: "readme".to_string(),
+                    existing: root.join("README"),
+                }]
+            );
+        }
+        assert!(check_portability(&root.join("README")).is_empty());
+    }
+
+    #[test]
+    fn find_case_collisions_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        for name in &["Makefile", "README", "b", "readme", "Readme.md"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        // case insensitive file systems can't hold colliding names
+        if fs::read_dir(root).unwrap().count() == 5 {
+            assert_eq!(
+                find_case_collisions(&root).unwrap(),
+                vec![vec!["README".to_string(), "readme".to_string()]]
+            );
+        } else {
+            assert!(find_case_collisions(&root).unwrap().is_empty());
+        }
+        assert!(find_case_collisions(&root.join("nowhere")).is_err());
+    }
+
+    #[test]
+    fn fit_to_limits_works() {
+        let limits = Limits {
+            max_component: 20,
+            max_path: 40,
+        };
+        assert_eq!(
+            fit_to_limits("a/b.txt", &limits),
+            Some("a/b.txt".to_string())
+        );
+        let long = format!("dir/{}.txt", "x".repeat(30));
+        let fitted = fit_to_limits(&long, &limits).unwrap();
+        assert_eq!(fitted.len(), 4 + 20);
+        assert!(fitted.starts_with("dir/xxxxxxx-") && fitted.ends_with(".txt"));
+        assert_eq!(fit_to_limits(&long, &limits), Some(fitted.clone()));
+        let other = format!("dir/{}y.txt", "x".repeat(30));
+        assert_ne!(fit_to_limits(&other, &limits), Some(fitted));
+
+        let deep = ["abcdefghijklmnop"; 3].join("/");
+        let fitted = fit_to_limits(&deep, &limits).unwrap();
+        assert!(fitted.len() <= 40);
+        assert!(Path::new(&fitted)
+            .components()
+            .all(|c| c.as_os_str().len() <= 20));
+        let too_deep = ["a"; 30].join("/");
+        assert_eq!(fit_to_limits(&too_deep, &limits), None);
+        assert!(Limits::native().max_path >= WINDOWS_MAX_PATH);
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide owned path types for POSIX and Windows paths (like
+//! Python's `PurePosixPath` and `PureWindowsPath`) that are manipulated
+//! purely lexically without any calls to the OS.  They are intended for
+//! paths destined for other systems (e.g. remote servers and archives).
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use super::foreign_path::ForeignPath;
+use super::str_path::StrPathComponent;
+
+macro_rules! pure_path {
+    ( $(#[$meta:meta])* $name:ident, $syntax:expr, $case_sensitive:expr ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Eq)]
+        pub struct $name(String);
+
+        impl $name {
+            /// The path for `path` (with redundant separators and "."
+            /// components removed).
+            pub fn new(path: &str) -> Self {
+                $name($syntax.normalize_separators(path))
+            }
+
+            fn syntax() -> ForeignPath {
+                $syntax
+            }
+
+            fn from_components(components: &[StrPathComponent]) -> Self {
+                $name(Self::syntax().path_from_components(components))
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn components(&self) -> Vec<StrPathComponent> {
+                Self::syntax().components(&self.0)
+            }
+
+            pub fn is_absolute(&self) -> bool {
+                Self::syntax().is_absolute(&self.0)
+            }
+
+            pub fn is_relative(&self) -> bool {
+                Self::syntax().is_relative(&self.0)
+            }
+
+            /// The last component (if it's a normal one).
+            pub fn name(&self) -> Option<String> {
+                Self::syntax().file_name(&self.0)
+            }
+
+            /// The name without its extension.
+            pub fn stem(&self) -> Option<String> {
+                let name = self.name()?;
+                match name.rfind('.') {
+                    Some(index) if index > 0 => Some(name[..index].to_string()),
+                    _ => Some(name),
+                }
+            }
+
+            /// The extension of the name (without the ".").
+            pub fn extension(&self) -> Option<String> {
+                let name = self.name()?;
+                match name.rfind('.') {
+                    Some(index) if index > 0 => Some(name[index + 1..].to_string()),
+                    _ => None,
+                }
+            }
+
+            pub fn parent(&self) -> Option<Self> {
+                Self::syntax().parent(&self.0).map($name)
+            }
+
+            /// This path with `other` appended (see `ForeignPath::join()`).
+            pub fn join(&self, other: &str) -> Self {
+                $name(Self::syntax().join(&self.0, other))
+            }
+
+            pub fn with_name(&self, name: &str) -> Self {
+                $name(Self::syntax().with_file_name(&self.0, name))
+            }
+
+            /// This path relative to `base` (if it is below it).
+            pub fn strip_prefix(&self, base: &Self) -> Option<Self> {
+                Self::syntax().strip_prefix(&self.0, &base.0).map($name)
+            }
+
+            /// This path with "." and ".." resolved lexically.
+            pub fn normalize(&self) -> Self {
+                $name(Self::syntax().normalize(&self.0))
+            }
+
+            // The form used for comparison and hashing.
+            fn key(&self) -> Vec<String> {
+                self.components()
+                    .iter()
+                    .map(|c| {
+                        if $case_sensitive {
+                            c.to_string()
+                        } else {
+                            c.to_string().to_lowercase()
+                        }
+                    })
+                    .collect()
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.key() == other.key()
+            }
+        }
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.key().hash(state)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl<'a> From<&'a str> for $name {
+            fn from(path: &'a str) -> Self {
+                $name::new(path)
+            }
+        }
+
+        impl<'a> From<&'a [StrPathComponent]> for $name {
+            fn from(components: &'a [StrPathComponent]) -> Self {
+                $name::from_components(components)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+    };
+}
+
+pure_path!(
+    /// A '/' separated path.
+    PurePosixPath,
+    ForeignPath::posix(),
+    true
+);
+
+pure_path!(
+    /// A Windows path (with '\' separators and an optional prefix such as
+    /// "C:").  Comparisons ignore case.
+    PureWindowsPath,
+    ForeignPath::windows(),
+    false
+);
+
+impl PurePosixPath {
+    /// The equivalent Windows path (with the same components).
+    pub fn to_windows(&self) -> PureWindowsPath {
+        PureWindowsPath::from(&self.components()[..])
+    }
+}
+
+impl PureWindowsPath {
+    /// The equivalent POSIX path (any prefix is kept as a component).
+    pub fn to_posix(&self) -> PurePosixPath {
+        PurePosixPath::from(&self.components()[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn pure_posix_path_works() {
+        let path = PurePosixPath::new("/srv//www/./site/index.html");
+        assert_eq!(path.as_str(), "/srv/www/site/index.html");
+        assert!(path.is_absolute());
+        assert_eq!(path.name(), Some("index.html".to_string()));
+        assert_eq!(path.stem(), Some("index".to_string()));
+        assert_eq!(path.extension(), Some("html".to_string()));
+        assert_eq!(path.parent().unwrap().as_str(), "/srv/www/site");
+        assert_eq!(path.with_name("x.css").as_str(), "/srv/www/site/x.css");
+        assert_eq!(
+            path.strip_prefix(&PurePosixPath::new("/srv/www")).unwrap(),
+            PurePosixPath::new("site/index.html")
+        );
+        assert_eq!(
+            PurePosixPath::new("a/b").join("../c").normalize().as_str(),
+            "a/c"
+        );
+        assert_eq!(PurePosixPath::new(".bashrc").extension(), None);
+        assert_eq!(path.to_windows().as_str(), r"\srv\www\site\index.html");
+        assert_ne!(PurePosixPath::new("/A"), PurePosixPath::new("/a"));
+    }
+
+    #[test]
+    fn pure_windows_path_works() {
+        let path = PureWindowsPath::new("C:/Users/Me/file.TXT");
+        assert_eq!(path.as_str(), r"C:\Users\Me\file.TXT");
+        assert!(path.is_absolute());
+        assert_eq!(path.parent().unwrap().to_string(), r"C:\Users\Me");
+        assert_eq!(path.join(r"\Windows").as_str(), r"C:\Windows");
+        assert_eq!(path.extension(), Some("TXT".to_string()));
+        assert_eq!(path, PureWindowsPath::new(r"c:\users\me\FILE.txt"));
+        let mut set = HashSet::new();
+        set.insert(path.clone());
+        assert!(set.contains(&PureWindowsPath::new(r"C:\USERS\ME\file.txt")));
+        assert_eq!(
+            PureWindowsPath::new(r"a\b").to_posix(),
+            PurePosixPath::new("a/b")
+        );
+        assert!(!PureWindowsPath::new(r"\x").is_absolute());
+        assert!(PureWindowsPath::new("x").is_relative());
+        let drive_relative = PureWindowsPath::new("C:foo");
+        assert_eq!(drive_relative.as_str(), "C:foo");
+        assert!(!drive_relative.is_absolute());
+        assert_eq!(drive_relative.join("x").as_str(), r"C:foo\x");
+        assert_eq!(PureWindowsPath::new("C:").join("x").as_str(), "C:x");
+    }
+}
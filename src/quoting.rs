@@ -0,0 +1,153 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide quoting of paths for inclusion in command lines.
+
+use super::error::PathuxError;
+
+fn is_shell_safe(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || "_@%+=:,./-".contains(ch)
+}
+
+fn is_cmd_special(ch: char) -> bool {
+    ch.is_whitespace() || "&|<>^()%!,;=".contains(ch)
+}
+
+fn is_powershell_safe(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || r"_-./\:".contains(ch)
+}
+
+// PowerShell also treats the typographic single quotes as quotes.
+fn is_powershell_quote(ch: char) -> bool {
+    "'\u{2018}\u{2019}\u{201a}\u{201b}".contains(ch)
+}
+
+/// Quote `path` (if necessary) so that a POSIX shell will treat it as a
+/// single word without any expansion.
+pub fn shell_quote(path: &str) -> String {
+    if !path.is_empty() && path.chars().all(is_shell_safe) {
+        path.to_string()
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+/// Quote `path` (if necessary) so that it is passed as a single argument
+/// by Windows cmd (using the argument parsing rules of the Microsoft C
+/// runtime).  Paths containing double quotes or control characters (which
+/// Windows file names can't contain) are rejected as cmd offers no way of
+/// quoting them safely.  Environment variable references ("%NAME%") are
+/// still expanded by cmd even within quotes.  See `powershell_quote()`
+/// for PowerShell.
+pub fn cmd_quote(path: &str) -> Result<String, PathuxError> {
+    if path.chars().any(|ch| ch == '"' || ch.is_control()) {
+        return Err(PathuxError::InvalidName(path.to_string()));
+    }
+    if !path.is_empty() && !path.chars().any(is_cmd_special) {
+        return Ok(path.to_string());
+    }
+    // backslashes preceding the closing quote must be escaped
+    let trailing = path.len() - path.trim_end_matches('\\').len();
+    Ok(format!("\"{}{}\"", path, "\\".repeat(trailing)))
+}
+
+/// Quote `path` (if necessary) so that PowerShell will treat it as a
+/// single literal string without any expansion.
+pub fn powershell_quote(path: &str) -> String {
+    if !path.is_empty() && !path.starts_with('-') && path.chars().all(is_powershell_safe) {
+        return path.to_string();
+    }
+    let mut quoted = String::with_capacity(path.len() + 2);
+    quoted.push('\'');
+    for ch in path.chars() {
+        if is_powershell_quote(ch) {
+            quoted.push(ch);
+        }
+        quoted.push(ch);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_quote_works() {
+        assert_eq!(shell_quote("/usr/bin/env"), "/usr/bin/env");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$HOME/*"), "'$HOME/*'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn cmd_quote_works() {
+        assert_eq!(
+            cmd_quote(r"C:\Windows\notepad.exe").unwrap(),
+            r"C:\Windows\notepad.exe"
+        );
+        assert_eq!(
+            cmd_quote(r"C:\Program Files\x").unwrap(),
+            r#""C:\Program Files\x""#
+        );
+        assert_eq!(
+            cmd_quote(r"C:\dir with space\").unwrap(),
+            r#""C:\dir with space\\""#
+        );
+        assert_eq!(cmd_quote(r"a\b c").unwrap(), r#""a\b c""#);
+        assert_eq!(cmd_quote("a&b").unwrap(), r#""a&b""#);
+        assert_eq!(cmd_quote("").unwrap(), r#""""#);
+    }
+
+    #[test]
+    fn cmd_quote_prevents_injection() {
+        // a quote would end cmd's quoting and let "&calc" run
+        assert!(cmd_quote(r#"a"&calc"#).is_err());
+        assert!(cmd_quote(r#"say "hi""#).is_err());
+        assert!(cmd_quote("a\r\ncalc").is_err());
+        assert_eq!(cmd_quote("a&calc").unwrap(), r#""a&calc""#);
+        assert_eq!(cmd_quote("a|calc>x").unwrap(), r#""a|calc>x""#);
+        assert_eq!(cmd_quote(r"a\&calc\").unwrap(), r#""a\&calc\\""#);
+    }
+
+    #[test]
+    fn powershell_quote_works() {
+        assert_eq!(
+            powershell_quote(r"C:\Windows\notepad.exe"),
+            r"C:\Windows\notepad.exe"
+        );
+        assert_eq!(
+            powershell_quote(r"C:\Program Files\x"),
+            r"'C:\Program Files\x'"
+        );
+        assert_eq!(powershell_quote("it's"), "'it''s'");
+        assert_eq!(powershell_quote("-x"), "'-x'");
+        assert_eq!(powershell_quote(""), "''");
+    }
+
+    #[test]
+    fn powershell_quote_prevents_injection() {
+        assert_eq!(powershell_quote("$(calc)"), "'$(calc)'");
+        assert_eq!(powershell_quote("$env:PATH"), "'$env:PATH'");
+        assert_eq!(powershell_quote("a`; calc"), "'a`; calc'");
+        assert_eq!(powershell_quote("a'; calc; '"), "'a''; calc; '''");
+        assert_eq!(
+            powershell_quote("a\u{2019}; calc; \u{2018}"),
+            "'a\u{2019}\u{2019}; calc; \u{2018}\u{2018}'"
+        );
+        assert_eq!(powershell_quote(r#"a"b"#), r#"'a"b'"#);
+    }
+}
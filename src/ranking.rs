@@ -0,0 +1,149 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide largest/smallest and newest/oldest file queries.
+//! Only the best `n` candidates are held in memory during the scan.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::walk::Walker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Criterion {
+    Size,
+    Modified,
+    Created,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Metric {
+    Size(u64),
+    Time(SystemTime),
+}
+
+impl Criterion {
+    pub fn metric(&self, metadata: &Metadata) -> io::Result<Metric> {
+        match self {
+            Criterion::Size => Ok(Metric::Size(metadata.len())),
+            Criterion::Modified => Ok(Metric::Time(metadata.modified()?)),
+            Criterion::Created => Ok(Metric::Time(metadata.created()?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankedFile {
+    pub path: PathBuf,
+    pub metric: Metric,
+}
+
+fn scan<P, F>(root: &P, criterion: Criterion, mut f: F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(Metric, PathBuf),
+{
+    for entry in Walker::new(root) {
+        let entry = entry?;
+        if entry.is_file() {
+            let metric = criterion.metric(&entry.metadata()?)?;
+            f(metric, entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Return the (at most) `n` largest or newest regular files below `root`
+/// in descending order of the chosen metric.
+pub fn top_n_by<P: AsRef<Path>>(
+    root: &P,
+    n: usize,
+    criterion: Criterion,
+) -> io::Result<Vec<RankedFile>> {
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    scan(root, criterion, |metric, path| {
+        heap.push(Reverse((metric, path)));
+        if heap.len() > n {
+            heap.pop();
+        }
+    })?;
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((metric, path))| RankedFile { path, metric })
+        .collect())
+}
+
+/// Return the (at most) `n` smallest or oldest regular files below `root`
+/// in ascending order of the chosen metric.
+pub fn bottom_n_by<P: AsRef<Path>>(
+    root: &P,
+    n: usize,
+    criterion: Criterion,
+) -> io::Result<Vec<RankedFile>> {
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    scan(root, criterion, |metric, path| {
+        heap.push((metric, path));
+        if heap.len() > n {
+            heap.pop();
+        }
+    })?;
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|(metric, path)| RankedFile { path, metric })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn top_and_bottom_by_size_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a"), "1").unwrap();
+        fs::write(root.join("sub/b"), "123").unwrap();
+        fs::write(root.join("c"), "12").unwrap();
+
+        let top = top_n_by(&root, 2, Criterion::Size).unwrap();
+        assert_eq!(
+            top,
+            vec![
+                RankedFile {
+                    path: root.join("sub/b"),
+                    metric: Metric::Size(3)
+                },
+                RankedFile {
+                    path: root.join("c"),
+                    metric: Metric::Size(2)
+                },
+            ]
+        );
+        let bottom = bottom_n_by(&root, 1, Criterion::Size).unwrap();
+        assert_eq!(bottom[0].path, root.join("a"));
+        assert_eq!(top_n_by(&root, 10, Criterion::Modified).unwrap().len(), 3);
+        assert!(top_n_by(&root, 0, Criterion::Size).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide rewriting of path prefixes (e.g. translating
+//! between host and container or build and install paths).
+
+use std::str::FromStr;
+
+use super::error::PathuxError;
+use super::foreign_path::ForeignPath;
+
+/// An ordered table of (from prefix, to prefix) rules.  Prefixes match on
+/// component boundaries (so "/a/b" matches "/a/b/c" but not "/a/bc") and
+/// the rule with the longest matching prefix wins (the earliest of equal
+/// length ones).  The paths on each side use the native path syntax
+/// unless others are given with `with_syntax()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRemapper {
+    rules: Vec<(String, String)>,
+    from_syntax: ForeignPath,
+    to_syntax: ForeignPath,
+}
+
+impl Default for PathRemapper {
+    fn default() -> Self {
+        PathRemapper::with_syntax(ForeignPath::native(), ForeignPath::native())
+    }
+}
+
+impl PathRemapper {
+    pub fn new() -> Self {
+        PathRemapper::default()
+    }
+
+    /// A remapper from paths using the syntax `from_syntax` to paths using
+    /// `to_syntax` (e.g. from Windows host paths to POSIX container paths).
+    pub fn with_syntax(from_syntax: ForeignPath, to_syntax: ForeignPath) -> Self {
+        PathRemapper {
+            rules: Vec::new(),
+            from_syntax,
+            to_syntax,
+        }
+    }
+
+    /// Add the rule rewriting `from` to `to`.
+    pub fn rule(mut self, from: &str, to: &str) -> Self {
+        self.add_rule(from, to);
+        self
+    }
+
+    pub fn add_rule(&mut self, from: &str, to: &str) {
+        self.rules.push((from.to_string(), to.to_string()));
+    }
+
+    pub fn rules(&self) -> &[(String, String)] {
+        &self.rules
+    }
+
+    /// The remapper that undoes this one's rewrites.
+    pub fn reversed(&self) -> Self {
+        PathRemapper {
+            rules: self
+                .rules
+                .iter()
+                .map(|(from, to)| (to.clone(), from.clone()))
+                .collect(),
+            from_syntax: self.to_syntax,
+            to_syntax: self.from_syntax,
+        }
+    }
+
+    /// Rewrite `path` using the best matching rule or return `None` if no
+    /// rule matches.
+    pub fn remap(&self, path: &str) -> Option<String> {
+        let mut best: Option<(usize, &str, String)> = None;
+        for (from, to) in self.rules.iter() {
+            if let Some(rest) = self.from_syntax.strip_prefix(path, from) {
+                let depth = self.from_syntax.components(from).len();
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_depth, _, _)| depth > *best_depth)
+                {
+                    best = Some((depth, to, rest));
+                }
+            }
+        }
+        let (_, to, rest) = best?;
+        if rest.is_empty() {
+            Some(to.to_string())
+        } else {
+            let rest = self.from_syntax.translate(&rest, &self.to_syntax);
+            Some(self.to_syntax.join(to, &rest))
+        }
+    }
+
+    /// Like `remap()` but paths that no rule matches are returned as is.
+    pub fn remap_or_same(&self, path: &str) -> String {
+        self.remap(path).unwrap_or_else(|| path.to_string())
+    }
+}
+
+/// A bind mount as given to docker (or podman) with "-v" e.g.
+/// "-v /home/me/src:/src:ro".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindMount {
+    pub host: String,
+    pub container: String,
+    pub read_only: bool,
+}
+
+/// Parse "host:container[:options]" optionally preceded by "-v",
+/// "--volume" or "--volume=".  Host paths may have a Windows drive prefix
+/// but named volumes (which have no host path) are rejected.
+impl FromStr for BindMount {
+    type Err = PathuxError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || PathuxError::InvalidMount(text.to_string());
+        let spec = text.trim();
+        let spec = if let Some(rest) = spec.strip_prefix("--volume=") {
+            rest
+        } else if let Some(rest) = spec.strip_prefix("--volume ") {
+            rest.trim_start()
+        } else if let Some(rest) = spec.strip_prefix("-v") {
+            rest.trim_start()
+        } else {
+            spec
+        };
+        // don't split at the colon of a drive letter
+        let skip = match spec.as_bytes() {
+            [letter, b':', b'\\', ..] | [letter, b':', b'/', ..]
+                if letter.is_ascii_alphabetic() =>
+            {
+                2
+            }
+            _ => 0,
+        };
+        let colon = spec[skip..].find(':').ok_or_else(invalid)? + skip;
+        let host = &spec[..colon];
+        let mut rest = spec[colon + 1..].splitn(2, ':');
+        let container = rest.next().unwrap_or_default();
+        let options = rest.next().unwrap_or_default();
+        let is_host_path = skip > 0 || host.starts_with(['/', '\\', '.', '~']);
+        if !is_host_path || !container.starts_with('/') {
+            return Err(invalid());
+        }
+        Ok(BindMount {
+            host: host.to_string(),
+            container: container.to_string(),
+            read_only: options.split(',').any(|option| option == "ro"),
+        })
+    }
+}
+
+impl PathRemapper {
+    /// The remapper translating (native) host paths to (POSIX) container
+    /// paths for the bind mounts `specs` (see `BindMount`).
+    pub fn host_to_container<S: AsRef<str>>(specs: &[S]) -> Result<Self, PathuxError> {
+        let mut remapper = PathRemapper::with_syntax(ForeignPath::native(), ForeignPath::posix());
+        for spec in specs {
+            let mount: BindMount = spec.as_ref().parse()?;
+            remapper.add_rule(&mount.host, &mount.container);
+        }
+        Ok(remapper)
+    }
+
+    /// The remapper translating container paths to host paths for the
+    /// bind mounts `specs`.
+    pub fn container_to_host<S: AsRef<str>>(specs: &[S]) -> Result<Self, PathuxError> {
+        Ok(Self::host_to_container(specs)?.reversed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn remap_works() {
+        let remapper = PathRemapper::new()
+            .rule("/home/me/src", "/src")
+            .rule("/home/me/src/vendor", "/vendor")
+            .rule("/home", "/users")
+            .rule("/home/me/src", "/ignored");
+        let remap = |path| remapper.remap(path).map(PathBuf::from);
+        assert_eq!(
+            remap("/home/me/src/app/main.rs"),
+            Some(PathBuf::from("/src").join("app").join("main.rs"))
+        );
+        assert_eq!(
+            remap("/home/me/src/vendor/lib"),
+            Some(PathBuf::from("/vendor").join("lib"))
+        );
+        assert_eq!(remap("/home/me/src"), Some(PathBuf::from("/src")));
+        assert_eq!(
+            remap("/home/me/srcx"),
+            Some(PathBuf::from("/users").join("me").join("srcx"))
+        );
+        assert_eq!(remapper.remap("/etc/passwd"), None);
+        assert_eq!(remapper.remap_or_same("/etc/passwd"), "/etc/passwd");
+        let reversed = remapper.reversed();
+        assert_eq!(
+            reversed.rules()[0],
+            ("/src".to_string(), "/home/me/src".to_string())
+        );
+        assert_eq!(
+            reversed.remap("/vendor/lib").map(PathBuf::from),
+            Some(PathBuf::from("/home/me/src/vendor").join("lib"))
+        );
+    }
+
+    #[test]
+    fn bind_mount_parsing_works() {
+        let mount: BindMount = "-v /home/me/src:/src:ro,z".parse().unwrap();
+        assert_eq!(
+            mount,
+            BindMount {
+                host: "/home/me/src".to_string(),
+                container: "/src".to_string(),
+                read_only: true,
+            }
+        );
+        let mount: BindMount = r"--volume=C:\work:/work".parse().unwrap();
+        assert_eq!(mount.host, r"C:\work");
+        assert_eq!(mount.container, "/work");
+        assert!(!mount.read_only);
+        assert_eq!(
+            "--volume ./data:/data".parse::<BindMount>().unwrap().host,
+            "./data"
+        );
+        assert!(matches!(
+            "named:/data".parse::<BindMount>(),
+            Err(PathuxError::InvalidMount(_))
+        ));
+        assert!("/host".parse::<BindMount>().is_err());
+        assert!("/host:relative".parse::<BindMount>().is_err());
+    }
+
+    #[test]
+    fn remap_between_syntaxes_works() {
+        let remapper = PathRemapper::with_syntax(ForeignPath::windows(), ForeignPath::posix())
+            .rule(r"C:\Users\Me\src", "/src");
+        assert_eq!(
+            remapper.remap(r"c:\users\me\src\app\main.rs"),
+            Some("/src/app/main.rs".to_string())
+        );
+        assert_eq!(remapper.remap("C:/Users/Me/src"), Some("/src".to_string()));
+        assert_eq!(
+            remapper.reversed().remap("/src/app/main.rs"),
+            Some(r"C:\Users\Me\src\app\main.rs".to_string())
+        );
+        // container paths always use '/' whatever the host
+        let remapper = PathRemapper::with_syntax(ForeignPath::native(), ForeignPath::posix())
+            .rule(&ForeignPath::native().join("/home", "me"), "/src");
+        assert_eq!(
+            remapper.remap(&ForeignPath::native().join("/home", "me/app/main.rs")),
+            Some("/src/app/main.rs".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn container_presets_work() {
+        let specs = ["-v /home/me/src:/src", "/home/me/src/target:/target"];
+        let to_container = PathRemapper::host_to_container(&specs).unwrap();
+        assert_eq!(
+            to_container.remap("/home/me/src/target/debug/app"),
+            Some("/target/debug/app".to_string())
+        );
+        assert_eq!(
+            to_container.remap("/home/me/src/lib.rs"),
+            Some("/src/lib.rs".to_string())
+        );
+        let to_host = PathRemapper::container_to_host(&specs).unwrap();
+        assert_eq!(
+            to_host.remap("/src/lib.rs"),
+            Some("/home/me/src/lib.rs".to_string())
+        );
+        assert_eq!(to_host.remap("/etc/hosts"), None);
+        assert!(PathRemapper::host_to_container(&["bad"]).is_err());
+    }
+}
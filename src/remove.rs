@@ -0,0 +1,172 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide recursive removal of directory trees.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use permissions::set_readonly;
+use symlink::remove_link;
+
+#[derive(Debug, Clone, Default)]
+pub struct RemoveOptions {
+    /// Make read only files and directories writable if they get in the
+    /// way (as `rm -f` effectively does) rather than failing.
+    pub clear_readonly: bool,
+}
+
+// Remove the file or (empty) directory at `path` with `remove` clearing
+// read only attributes (of it and, if it's inside the tree being removed,
+// its parent) and retrying if allowed.
+fn remove_entry<F>(
+    path: &Path,
+    parent_in_tree: bool,
+    options: &RemoveOptions,
+    remove: F,
+) -> io::Result<()>
+where
+    F: Fn(&Path) -> io::Result<()>,
+{
+    match remove(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::PermissionDenied && options.clear_readonly => {
+            // Windows won't delete read only files, Unix needs to be able
+            // to write the parent directory
+            if cfg!(windows) {
+                set_readonly(&path, false)?;
+            }
+            if parent_in_tree {
+                if let Some(parent) = path.parent() {
+                    set_readonly(&parent, false)?;
+                }
+            }
+            remove(path)
+        }
+        result => result,
+    }
+}
+
+fn remove_below(path: &Path, parent_in_tree: bool, options: &RemoveOptions) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        if options.clear_readonly && metadata.permissions().readonly() {
+            // the contents can't be removed otherwise (on Unix)
+            set_readonly(&path, false)?;
+        }
+        for entry in fs::read_dir(path)? {
+            remove_below(&entry?.path(), true, options)?;
+        }
+        remove_entry(path, parent_in_tree, options, |path| fs::remove_dir(path))
+    } else if metadata.file_type().is_symlink() {
+        // Windows needs directory links (and junctions) removed as directories
+        remove_entry(path, parent_in_tree, options, |path| remove_link(&path))
+    } else {
+        remove_entry(path, parent_in_tree, options, |path| fs::remove_file(path))
+    }
+}
+
+/// Remove `path` and (if it is a directory) everything below it.
+/// Symbolic links are removed rather than followed.  Clearing read only
+/// attributes is confined to the tree itself: the directory containing
+/// `path` is never changed.
+pub fn remove_tree<P: AsRef<Path>>(path: &P, options: &RemoveOptions) -> io::Result<()> {
+    remove_below(path.as_ref(), false, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn remove_tree_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/file"), "").unwrap();
+        fs::write(root.join("top"), "").unwrap();
+        remove_tree(&root, &RemoveOptions::default()).unwrap();
+        assert!(!root.exists());
+        assert!(remove_tree(&root, &RemoveOptions::default()).is_err());
+    }
+
+    #[test]
+    fn remove_tree_clears_readonly() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("locked")).unwrap();
+        fs::write(root.join("locked/file"), "").unwrap();
+        set_readonly(&root.join("locked/file"), true).unwrap();
+        set_readonly(&root.join("locked"), true).unwrap();
+        let options = RemoveOptions {
+            clear_readonly: true,
+        };
+        remove_tree(&root, &options).unwrap();
+        assert!(!root.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_tree_leaves_parent_alone() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outer = temp_dir.path().join("outer");
+        fs::create_dir(&outer).unwrap();
+        fs::write(outer.join("file"), "").unwrap();
+        set_readonly(&outer, true).unwrap();
+        let options = RemoveOptions {
+            clear_readonly: true,
+        };
+        // fails unless we're privileged but mustn't unlock `outer` either way
+        let _ = remove_tree(&outer.join("file"), &options);
+        assert!(fs::metadata(&outer).unwrap().permissions().readonly());
+        set_readonly(&outer, false).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn remove_tree_removes_directory_links() {
+        use symlink::make_junction;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("file"), "").unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        make_junction(&target, &root.join("junction")).unwrap();
+        // directory symbolic links need privileges that we may not have
+        let _ = ::std::os::windows::fs::symlink_dir(&target, root.join("link"));
+        remove_tree(&root, &RemoveOptions::default()).unwrap();
+        assert!(!root.exists());
+        // the links' targets are left alone
+        assert!(target.join("file").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_tree_removes_links() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("file"), "").unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        ::std::os::unix::fs::symlink(&target, root.join("link")).unwrap();
+        remove_tree(&root, &RemoveOptions::default()).unwrap();
+        assert!(!root.exists());
+        assert!(target.join("file").exists());
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide logrotate style rotation of files (e.g. logs)
+//! through a series of numbered generations.
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A way of compressing older generations of rotated files.
+pub trait Compress {
+    /// The suffix (e.g. ".gz") given to compressed generations.
+    fn suffix(&self) -> &str;
+    /// Write a compressed copy of the file `from` to `to`.
+    fn compress(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+/// A `Compress` that runs an external program which writes the compressed
+/// data for the file named by its last argument to its standard output.
+#[derive(Debug, Clone)]
+pub struct CommandCompressor {
+    pub program: String,
+    pub args: Vec<String>,
+    pub suffix: String,
+}
+
+impl CommandCompressor {
+    /// Compress with "gzip -c".
+    pub fn gzip() -> Self {
+        CommandCompressor {
+            program: "gzip".to_string(),
+            args: vec!["-c".to_string()],
+            suffix: ".gz".to_string(),
+        }
+    }
+}
+
+impl Compress for CommandCompressor {
+    fn suffix(&self) -> &str {
+        &self.suffix
+    }
+
+    fn compress(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let status = Command::new(&self.program)
+            .args(&self.args)
+            .arg(from)
+            .stdin(Stdio::null())
+            .stdout(File::create(to)?)
+            .status()
+            .inspect_err(|_| {
+                let _ = fs::remove_file(to);
+            })?;
+        if status.success() {
+            Ok(())
+        } else {
+            let _ = fs::remove_file(to);
+            Err(io::Error::other(format!(
+                "{}: {} failed: {}",
+                from.display(),
+                self.program,
+                status
+            )))
+        }
+    }
+}
+
+// The path of generation `n` of `path` (with `suffix` appended).
+fn generation(path: &Path, n: usize, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}{}", n, suffix));
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Rotate `path`: "app.log" becomes "app.log.1", "app.log.1" becomes
+/// "app.log.2" and so on with at most `keep` generations being kept (the
+/// oldest is removed).  Any of the files may be missing.  Fails with
+/// `InvalidInput` if `keep` is zero.
+pub fn rotate<P: AsRef<Path>>(path: &P, keep: usize) -> io::Result<()> {
+    rotate_generations(path.as_ref(), keep, None)
+}
+
+/// Like `rotate()` but generations other than the newest (which the
+/// writer may still have open) are compressed with `compressor`.
+pub fn rotate_compressed<P, C>(path: &P, keep: usize, compressor: &C) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    C: Compress,
+{
+    rotate_generations(path.as_ref(), keep, Some(compressor))
+}
+
+fn rotate_generations(
+    path: &Path,
+    keep: usize,
+    compressor: Option<&dyn Compress>,
+) -> io::Result<()> {
+    let suffix = compressor.map(|c| c.suffix()).unwrap_or("");
+    if keep == 0 {
+        // that would just delete the live log
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "must keep at least one generation",
+        ));
+    }
+    // older generations may or may not have been compressed
+    let suffixes = if suffix.is_empty() {
+        vec![""]
+    } else {
+        vec!["", suffix]
+    };
+    for suffix in suffixes.iter() {
+        remove_if_exists(&generation(path, keep, suffix))?;
+        for n in (1..keep).rev() {
+            rename_if_exists(
+                &generation(path, n, suffix),
+                &generation(path, n + 1, suffix),
+            )?;
+        }
+    }
+    if let Some(compressor) = compressor {
+        let second = generation(path, 2, "");
+        if keep > 1 && second.exists() {
+            compressor.compress(&second, &generation(path, 2, suffix))?;
+            fs::remove_file(&second)?;
+        }
+    }
+    rename_if_exists(path, &generation(path, 1, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    // A "compressor" that just copies.
+    struct Copier;
+
+    impl Compress for Copier {
+        fn suffix(&self) -> &str {
+            ".z"
+        }
+
+        fn compress(&self, from: &Path, to: &Path) -> io::Result<()> {
+            fs::copy(from, to).map(|_| ())
+        }
+    }
+
+    #[test]
+    fn rotate_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("app.log");
+        let read = |name: &str| fs::read_to_string(temp_dir.path().join(name)).ok();
+        for text in &["one", "two", "three", "four"] {
+            fs::write(&log, text).unwrap();
+            rotate(&log, 2).unwrap();
+        }
+        assert_eq!(read("app.log"), None);
+        assert_eq!(read("app.log.1"), Some("four".to_string()));
+        assert_eq!(read("app.log.2"), Some("three".to_string()));
+        assert_eq!(read("app.log.3"), None);
+        rotate(&log, 2).unwrap();
+        assert_eq!(read("app.log.1"), None);
+        assert_eq!(read("app.log.2"), Some("four".to_string()));
+        fs::write(&log, "five").unwrap();
+        assert_eq!(
+            rotate(&log, 0).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(read("app.log"), Some("five".to_string()));
+        assert_eq!(read("app.log.2"), Some("four".to_string()));
+    }
+
+    #[test]
+    fn rotate_compressed_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("app.log");
+        let read = |name: &str| fs::read_to_string(temp_dir.path().join(name)).ok();
+        for text in &["one", "two", "three", "four"] {
+            fs::write(&log, text).unwrap();
+            rotate_compressed(&log, 3, &Copier).unwrap();
+        }
+        assert_eq!(read("app.log.1"), Some("four".to_string()));
+        assert_eq!(read("app.log.2"), None);
+        assert_eq!(read("app.log.2.z"), Some("three".to_string()));
+        assert_eq!(read("app.log.3.z"), Some("two".to_string()));
+        assert_eq!(read("app.log.4.z"), None);
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gzip_compressor_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let from = temp_dir.path().join("file");
+        let to = temp_dir.path().join("file.gz");
+        fs::write(&from, "contents").unwrap();
+        let gzip = CommandCompressor::gzip();
+        if gzip.compress(&from, &to).is_err() {
+            // no gzip installed
+            return;
+        }
+        assert_eq!(&fs::read(&to).unwrap()[..2], &[0x1f, 0x8b]);
+        assert!(gzip
+            .compress(&temp_dir.path().join("missing"), &to)
+            .is_err());
+        assert!(!to.exists());
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide joining of untrusted (e.g. user or archive
+//! supplied) relative paths to a base directory such that the result is
+//! guaranteed to lie within that directory.
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use super::error::PathuxError;
+use super::normalization::path_nfc;
+use super::sanitize::{validate_component, Platform};
+
+// Make sure that no (existing) symbolic link in `path` leads outside `base`.
+fn check_links(base: &Path, path: &Path, untrusted: &str) -> Result<(), PathuxError> {
+    let real_base = match base.canonicalize() {
+        Ok(real_base) => real_base,
+        // nothing exists so there can be no links
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut probe = path;
+    while probe.starts_with(base) {
+        match probe.canonicalize() {
+            Ok(real_path) => {
+                if real_path.starts_with(&real_base) {
+                    return Ok(());
+                }
+                return Err(PathuxError::Escapes(untrusted.to_string()));
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                // a dangling link could still lead anywhere
+                if fs::symlink_metadata(probe).is_ok() {
+                    return Err(PathuxError::Escapes(untrusted.to_string()));
+                }
+            }
+            Err(err) => return Err(err.into()),
+        }
+        probe = match probe.parent() {
+            Some(parent) => parent,
+            None => break,
+        };
+    }
+    Ok(())
+}
+
+fn join(base: &str, untrusted: &str, clamp: bool) -> Result<String, PathuxError> {
+    let base = Path::new(base);
+    let mut relative_path = PathBuf::new();
+    for component in Path::new(untrusted).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                if !clamp {
+                    return Err(PathuxError::NotRelative(untrusted.to_string()));
+                }
+            }
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !relative_path.pop() && !clamp {
+                    return Err(PathuxError::Escapes(untrusted.to_string()));
+                }
+            }
+            Component::Normal(name) => {
+                let text = name.to_string_lossy();
+                if validate_component(&text, Platform::Native).is_err() {
+                    return Err(PathuxError::InvalidName(text.into_owned()));
+                }
+                relative_path.push(name)
+            }
+        }
+    }
+    let path = base.join(relative_path);
+    check_links(base, &path, untrusted)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Join `untrusted` to `base` failing if `untrusted` is absolute (or has
+/// a drive prefix), if its ".." components would climb above `base` or if
+/// an existing symbolic link along the way leads outside `base`.
+pub fn safe_join(base: &str, untrusted: &str) -> Result<String, PathuxError> {
+    join(base, untrusted, false)
+}
+
+/// Like `safe_join()` but roots, prefixes and ".." components that would
+/// climb above `base` are dropped rather than rejected (symbolic links
+/// leading outside `base` are still an error).
+pub fn safe_join_clamped(base: &str, untrusted: &str) -> Result<String, PathuxError> {
+    join(base, untrusted, true)
+}
+
+/// Turn the name of an archive (tar, zip, etc.) entry into a relative '/'
+/// separated path suitable for `safe_join()`: '\' is treated as a
+/// separator, drive prefixes and leading separators are removed, "." and
+/// ".." are resolved (failing with `PathuxError::Escapes` if ".." would
+/// climb out of the archive) and the result is in Unicode NFC.  A trailing
+/// '/' (marking a directory entry) is kept.
+pub fn normalize_archive_entry(name: &str) -> Result<String, PathuxError> {
+    let mut text = name.replace('\\', "/");
+    if let [letter, b':', ..] = text.as_bytes() {
+        if letter.is_ascii_alphabetic() {
+            text.replace_range(..2, "");
+        }
+    }
+    let mut components: Vec<&str> = Vec::new();
+    for component in text.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                if components.pop().is_none() {
+                    return Err(PathuxError::Escapes(name.to_string()));
+                }
+            }
+            _ => {
+                if validate_component(component, Platform::Posix).is_err() {
+                    return Err(PathuxError::InvalidName(name.to_string()));
+                }
+                components.push(component);
+            }
+        }
+    }
+    if components.is_empty() {
+        return Err(PathuxError::InvalidName(name.to_string()));
+    }
+    let mut path = path_nfc(&components.join("/"));
+    if text.ends_with('/') {
+        path.push('/');
+    }
+    Ok(path)
+}
+
+// The maximum number of symbolic links followed by `resolve_within()`.
+const MAX_LINKS: usize = 40;
+
+fn push_front(remaining: &mut VecDeque<OsString>, path: &Path) {
+    for component in path.components().rev() {
+        remaining.push_front(component.as_os_str().to_os_string());
+    }
+}
+
+/// Resolve all symbolic links in `path` (which is relative to `root`)
+/// failing with `PathuxError::Escapes` if `path` or any link target along
+/// the way would lead outside `root`.  Absolute link targets are allowed
+/// if they point inside `root`.  Components that don't exist are resolved
+/// lexically.  On Linux the result is confirmed by the kernel (using
+/// `openat2()` with `RESOLVE_BENEATH`) where that is available.
+pub fn resolve_within(root: &str, path: &str) -> Result<String, PathuxError> {
+    if Path::new(path).has_root() {
+        return Err(PathuxError::NotRelative(path.to_string()));
+    }
+    let real_root = Path::new(root).canonicalize()?;
+    let escapes = || PathuxError::Escapes(path.to_string());
+    let mut remaining: VecDeque<OsString> = VecDeque::new();
+    push_front(&mut remaining, Path::new(path));
+    let mut resolved = PathBuf::new();
+    let mut links_followed = 0;
+    while let Some(name) = remaining.pop_front() {
+        if name == "." {
+            continue;
+        } else if name == ".." {
+            if !resolved.pop() {
+                return Err(escapes());
+            }
+            continue;
+        }
+        let candidate = real_root.join(&resolved).join(&name);
+        match fs::symlink_metadata(&candidate) {
+            Ok(ref metadata) if metadata.file_type().is_symlink() => {
+                links_followed += 1;
+                if links_followed > MAX_LINKS {
+                    return Err(io::Error::other(format!(
+                        "{}: too many levels of symbolic links",
+                        path
+                    ))
+                    .into());
+                }
+                let target = fs::read_link(&candidate)?;
+                if target.has_root() {
+                    // absolute targets must lead back inside the root
+                    match target.strip_prefix(&real_root) {
+                        Ok(rel_target) => {
+                            resolved = PathBuf::new();
+                            push_front(&mut remaining, rel_target);
+                        }
+                        Err(_) => return Err(escapes()),
+                    }
+                } else {
+                    push_front(&mut remaining, &target);
+                }
+            }
+            Ok(_) => resolved.push(&name),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => resolved.push(&name),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if imp::escapes_beneath(&real_root, &resolved)? {
+        return Err(escapes());
+    }
+    Ok(real_root.join(resolved).to_string_lossy().into_owned())
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::fs::File;
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use libc;
+
+    use sys::c_path;
+
+    // Ask the kernel whether `rel_path` leads outside `root` (which guards
+    // against links being changed while we were resolving them).
+    pub fn escapes_beneath(root: &Path, rel_path: &Path) -> io::Result<bool> {
+        if rel_path.as_os_str().is_empty() {
+            return Ok(false);
+        }
+        let root_dir = File::open(root)?;
+        let c_rel_path = c_path(rel_path)?;
+        let mut how: libc::open_how = unsafe { mem::zeroed() };
+        how.flags = (libc::O_PATH | libc::O_CLOEXEC) as u64;
+        how.resolve = libc::RESOLVE_BENEATH | libc::RESOLVE_NO_MAGICLINKS;
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_openat2,
+                root_dir.as_raw_fd(),
+                c_rel_path.as_ptr(),
+                &how as *const libc::open_how,
+                mem::size_of::<libc::open_how>(),
+            )
+        };
+        if fd >= 0 {
+            unsafe { libc::close(fd as libc::c_int) };
+            return Ok(false);
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EXDEV) => Ok(true),
+            // nothing to check or openat2() is unavailable
+            Some(libc::ENOENT) | Some(libc::ENOTDIR) | Some(libc::ENOSYS) | Some(libc::EPERM) => {
+                Ok(false)
+            }
+            _ => Err(err),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    pub fn escapes_beneath(_root: &Path, _rel_path: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn safe_join_works() {
+        let joined = |result: Result<String, PathuxError>| PathBuf::from(result.unwrap());
+        let srv = Path::new("/srv");
+        assert_eq!(
+            joined(safe_join("/srv", "a/./b/../c")),
+            srv.join("a").join("c")
+        );
+        assert!(safe_join("/srv", "../etc/passwd").is_err());
+        assert!(safe_join("/srv", "a/../../etc").is_err());
+        assert!(safe_join("/srv", "/etc/passwd").is_err());
+        assert_eq!(
+            joined(safe_join_clamped("/srv", "../../etc/passwd")),
+            srv.join("etc").join("passwd")
+        );
+        assert_eq!(
+            joined(safe_join_clamped("/srv", "/etc/passwd")),
+            srv.join("etc").join("passwd")
+        );
+        assert_eq!(joined(str_path_safe_join!("/srv", "x")), srv.join("x"));
+        let long_name = "x".repeat(300);
+        assert!(matches!(
+            safe_join("/srv", &long_name),
+            Err(PathuxError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn normalize_archive_entry_works() {
+        let normalize = |name| normalize_archive_entry(name).unwrap();
+        assert_eq!(normalize("a/b/c.txt"), "a/b/c.txt");
+        assert_eq!(normalize(r"C:\dir\file"), "dir/file");
+        assert_eq!(normalize("//./a//b/../c"), "a/c");
+        assert_eq!(normalize("dir/"), "dir/");
+        assert_eq!(normalize("cafe\u{301}"), "caf\u{e9}");
+        assert!(matches!(
+            normalize_archive_entry("../etc/passwd"),
+            Err(PathuxError::Escapes(_))
+        ));
+        assert!(matches!(
+            normalize_archive_entry("a/../../b"),
+            Err(PathuxError::Escapes(_))
+        ));
+        assert!(matches!(
+            normalize_archive_entry("/"),
+            Err(PathuxError::InvalidName(_))
+        ));
+        assert!(normalize_archive_entry("a\0b").is_err());
+        let base = Path::new("/srv");
+        assert_eq!(
+            PathBuf::from(safe_join("/srv", &normalize(r"\x\y")).unwrap()),
+            base.join("x").join("y")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn safe_join_detects_link_escapes() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base = temp_dir.path().join("base");
+        fs::create_dir_all(base.join("inside")).unwrap();
+        symlink(temp_dir.path(), base.join("out")).unwrap();
+        symlink("inside", base.join("in")).unwrap();
+        symlink("/nonexistent", base.join("dangling")).unwrap();
+        let base = base.to_str().unwrap();
+
+        assert!(safe_join(base, "in/file").is_ok());
+        assert!(safe_join(base, "new/file").is_ok());
+        assert!(safe_join(base, "out/file").is_err());
+        assert!(safe_join(base, "out").is_err());
+        assert!(safe_join(base, "dangling").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_within_works() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        symlink("a/b", root.join("rel")).unwrap();
+        symlink(root.join("a"), root.join("abs")).unwrap();
+        symlink("../..", root.join("a/b/up2")).unwrap();
+        symlink("..", root.join("a/b/up1")).unwrap();
+        symlink("/etc", root.join("etc")).unwrap();
+        symlink("loop", root.join("loop")).unwrap();
+        let root_str = root.to_str().unwrap();
+        let resolve = |path| resolve_within(root_str, path).map(PathBuf::from);
+
+        assert_eq!(resolve("rel/file").unwrap(), root.join("a/b/file"));
+        assert_eq!(resolve("abs/b/up1").unwrap(), root.join("a"));
+        assert_eq!(resolve("rel/up2/x/../y").unwrap(), root.join("y"));
+        assert_eq!(resolve("").unwrap(), root);
+        assert!(matches!(
+            resolve("etc/passwd"),
+            Err(PathuxError::Escapes(_))
+        ));
+        assert!(matches!(
+            resolve("a/b/up2/.."),
+            Err(PathuxError::Escapes(_))
+        ));
+        assert!(matches!(resolve("../x"), Err(PathuxError::Escapes(_))));
+        assert!(matches!(resolve("/x"), Err(PathuxError::NotRelative(_))));
+        assert!(resolve("loop").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide conversion of untrusted strings into file names
+//! that are safe to use on any of the common platforms.
+
+use std::error;
+use std::fmt;
+
+use super::error::PathuxError;
+use super::portability::MAX_COMPONENT_LEN;
+
+#[derive(Debug, Clone)]
+pub struct SanitizeOptions {
+    /// What to put in place of unusable characters (may be empty).
+    pub replacement: String,
+    /// Maximum length of the result in bytes.
+    pub max_len: usize,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        SanitizeOptions {
+            replacement: "_".to_string(),
+            max_len: 255,
+        }
+    }
+}
+
+const WINDOWS_RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Return `true` if Windows doesn't allow `ch` in names (separators
+/// aside).
+pub(crate) fn is_illegal_on_windows(ch: char) -> bool {
+    ch.is_control() || "<>:\"|?*".contains(ch)
+}
+
+/// Return `true` if `name` ends with a dot or a space (which Windows
+/// silently drops).
+pub(crate) fn has_trailing_dot_or_space(name: &str) -> bool {
+    name.ends_with(['.', ' '])
+}
+
+/// Shorten `name` to at most `max_len` bytes with `infix` inserted before
+/// its extension (which is kept if it's short enough).
+pub(crate) fn shorten_keeping_extension(name: &str, max_len: usize, infix: &str) -> String {
+    let room = max_len.saturating_sub(infix.len());
+    let (base, extension) = match name.rfind('.') {
+        Some(index) if index > 0 && name.len() - index <= room / 2 => name.split_at(index),
+        _ => (name, ""),
+    };
+    let mut end = room.saturating_sub(extension.len()).min(base.len());
+    while !base.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}{}", &base[..end], infix, extension)
+}
+
+fn is_unusable_char(ch: char) -> bool {
+    ch == '/' || ch == '\\' || is_illegal_on_windows(ch)
+}
+
+/// Return `true` if `name` (ignoring any extension) is a device name
+/// reserved by Windows e.g. "CON" or "com1.txt".
+pub fn is_windows_reserved_name(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or("").trim_end_matches(' ');
+    WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+/// The platform whose file naming rules are to be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The platform this code is running on.
+    Native,
+    Windows,
+    Posix,
+}
+
+/// The reason that a name can't be used as a path component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentError {
+    Empty,
+    /// The name is "." or "..".
+    Dots,
+    NulByte,
+    Separator(char),
+    /// A (control) character that Windows doesn't allow in file names.
+    IllegalChar(char),
+    /// A Windows device name e.g. "CON" or "nul.txt".
+    ReservedName,
+    /// Windows silently drops trailing dots and spaces.
+    TrailingDotOrSpace,
+    /// The length (in bytes, or UTF-16 units on Windows) exceeds the limit.
+    TooLong(usize),
+}
+
+impl fmt::Display for ComponentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComponentError::Empty => write!(f, "name is empty"),
+            ComponentError::Dots => write!(f, "name is \".\" or \"..\""),
+            ComponentError::NulByte => write!(f, "name contains a nul byte"),
+            ComponentError::Separator(ch) => write!(f, "name contains separator {:?}", ch),
+            ComponentError::IllegalChar(ch) => {
+                write!(f, "name contains illegal character {:?}", ch)
+            }
+            ComponentError::ReservedName => write!(f, "name is reserved on Windows"),
+            ComponentError::TrailingDotOrSpace => write!(f, "name ends with a dot or space"),
+            ComponentError::TooLong(len) => write!(f, "name is too long ({})", len),
+        }
+    }
+}
+
+impl error::Error for ComponentError {}
+
+/// Check that `name` can be used as a single path component on `platform`.
+pub fn validate_component(name: &str, platform: Platform) -> Result<(), ComponentError> {
+    let windows = match platform {
+        Platform::Native => cfg!(windows),
+        Platform::Windows => true,
+        Platform::Posix => false,
+    };
+    if name.is_empty() {
+        return Err(ComponentError::Empty);
+    } else if name == "." || name == ".." {
+        return Err(ComponentError::Dots);
+    }
+    for ch in name.chars() {
+        if ch == '\0' {
+            return Err(ComponentError::NulByte);
+        } else if ch == '/' || (windows && ch == '\\') {
+            return Err(ComponentError::Separator(ch));
+        } else if windows && is_illegal_on_windows(ch) {
+            return Err(ComponentError::IllegalChar(ch));
+        }
+    }
+    if windows {
+        if is_windows_reserved_name(name) {
+            return Err(ComponentError::ReservedName);
+        } else if has_trailing_dot_or_space(name) {
+            return Err(ComponentError::TrailingDotOrSpace);
+        }
+    }
+    let len = if windows {
+        name.encode_utf16().count()
+    } else {
+        name.len()
+    };
+    if len > MAX_COMPONENT_LEN {
+        return Err(ComponentError::TooLong(len));
+    }
+    Ok(())
+}
+
+/// Return a version of `name` that can be used as a file name: path
+/// separators, control characters and characters that Windows forbids are
+/// replaced, the result is shortened to `options.max_len`, trailing dots
+/// and spaces are removed and reserved Windows device names are altered.
+/// The result is never empty so it may be longer than an `options.max_len`
+/// that's shorter than the replacement.
+pub fn sanitize_file_name(name: &str, options: &SanitizeOptions) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for ch in name.chars() {
+        if is_unusable_char(ch) {
+            sanitized.push_str(&options.replacement);
+        } else {
+            sanitized.push(ch);
+        }
+    }
+    let fallback = if options.replacement.is_empty() {
+        "_"
+    } else {
+        &options.replacement
+    };
+    // shorten first as that may expose trailing dots or spaces
+    if sanitized.len() > options.max_len {
+        sanitized = shorten_keeping_extension(&sanitized, options.max_len, "");
+    }
+    let mut sanitized = sanitized.trim_end_matches(['.', ' ']).to_string();
+    if sanitized.is_empty() {
+        sanitized = fallback.to_string();
+    }
+    if is_windows_reserved_name(&sanitized) {
+        // reserved names are ASCII and no shorter version of one is reserved
+        let base = sanitized.split('.').next().unwrap_or("");
+        let end = base.trim_end_matches(' ').len();
+        if sanitized.len() + fallback.len() <= options.max_len {
+            sanitized.insert_str(end, fallback);
+        } else {
+            sanitized.remove(end - 1);
+        }
+    }
+    sanitized
+}
+
+/// Like `sanitize_file_name()` but fail rather than alter `name`.
+pub fn sanitize_file_name_strict(
+    name: &str,
+    options: &SanitizeOptions,
+) -> Result<String, PathuxError> {
+    if options.max_len == 0 || name.len() > options.max_len {
+        return Err(PathuxError::InvalidName(name.to_string()));
+    }
+    match validate_component(name, Platform::Windows) {
+        Ok(()) => Ok(name.to_string()),
+        Err(_) => Err(PathuxError::InvalidName(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_file_name_works() {
+        let options = SanitizeOptions::default();
+        let sanitize = |name| sanitize_file_name(name, &options);
+        assert_eq!(sanitize("report.txt"), "report.txt");
+        assert_eq!(sanitize("../etc/passwd"), ".._etc_passwd");
+        assert_eq!(sanitize("a\\b:c*?\u{7}"), "a_b_c___");
+        assert_eq!(sanitize("name. . "), "name");
+        assert_eq!(sanitize(".."), "_");
+        assert_eq!(sanitize(""), "_");
+        assert_eq!(sanitize("CON"), "CON_");
+        assert_eq!(sanitize("com1.txt"), "com1_.txt");
+        assert_eq!(sanitize("console"), "console");
+        let long = format!("{}.txt", "é".repeat(200));
+        let short = sanitize(&long);
+        assert!(short.len() <= 255);
+        assert!(short.ends_with("é.txt"));
+
+        let options = SanitizeOptions {
+            replacement: String::new(),
+            max_len: 8,
+        };
+        assert_eq!(sanitize_file_name("a/b|c", &options), "abc");
+        assert_eq!(sanitize_file_name("abcdefghij", &options), "abcdefgh");
+
+        let options = SanitizeOptions::default();
+        assert_eq!(
+            sanitize_file_name_strict("fine.txt", &options).unwrap(),
+            "fine.txt"
+        );
+        assert!(sanitize_file_name_strict("nul", &options).is_err());
+        assert!(sanitize_file_name_strict("a/b", &options).is_err());
+    }
+
+    #[test]
+    fn sanitize_file_name_shortens_first() {
+        let options = |max_len| SanitizeOptions {
+            max_len,
+            ..SanitizeOptions::default()
+        };
+        let sanitize = |name, max_len| sanitize_file_name(name, &options(max_len));
+        assert_eq!(sanitize("abcdefg.hijklmnop", 8), "abcdefg");
+        assert_eq!(sanitize("abcdefg hij", 8), "abcdefg");
+        assert_eq!(sanitize("abc.txt", 4), "abc");
+        // shortening mustn't leave a reserved name
+        assert_eq!(sanitize("console", 3), "co");
+        assert_eq!(sanitize("nul.txt", 7), "nu.txt");
+        assert_eq!(sanitize("nul.txt", 8), "nul_.txt");
+        // the result is never empty
+        assert_eq!(sanitize("name", 0), "_");
+        for (name, max_len) in &[("abcdefg.hijklmnop", 8), ("abcdefg hij", 8), ("console", 3)] {
+            let sanitized = sanitize(name, *max_len);
+            assert!(sanitized.len() <= *max_len);
+            assert_eq!(validate_component(&sanitized, Platform::Windows), Ok(()));
+        }
+
+        assert!(sanitize_file_name_strict("abcdefg.hijklmnop", &options(8)).is_err());
+        assert!(sanitize_file_name_strict("abcdefg hij", &options(8)).is_err());
+        assert!(sanitize_file_name_strict("name", &options(0)).is_err());
+        assert!(sanitize_file_name_strict("", &options(0)).is_err());
+        assert!(sanitize_file_name_strict("", &options(8)).is_err());
+        assert!(sanitize_file_name_strict("abcdefg.", &options(8)).is_err());
+        assert_eq!(
+            sanitize_file_name_strict("abcdefgh", &options(8)).unwrap(),
+            "abcdefgh"
+        );
+    }
+
+    #[test]
+    fn validate_component_works() {
+        for (name, error) in &[
+            ("", ComponentError::Empty),
+            ("..", ComponentError::Dots),
+            ("a\0b", ComponentError::NulByte),
+            ("a/b", ComponentError::Separator('/')),
+            ("a\\b", ComponentError::Separator('\\')),
+            ("a:b", ComponentError::IllegalChar(':')),
+            ("Nul.txt", ComponentError::ReservedName),
+            ("name.", ComponentError::TrailingDotOrSpace),
+        ] {
+            assert_eq!(
+                validate_component(name, Platform::Windows),
+                Err(error.clone())
+            );
+        }
+        for name in &["a\\b", "a:b", "Nul.txt", "name.", "..."] {
+            assert_eq!(validate_component(name, Platform::Posix), Ok(()));
+        }
+        assert_eq!(
+            validate_component("a/b", Platform::Posix),
+            Err(ComponentError::Separator('/'))
+        );
+        let long_name = "é".repeat(200);
+        assert_eq!(validate_component(&long_name, Platform::Windows), Ok(()));
+        assert_eq!(
+            validate_component(&long_name, Platform::Posix),
+            Err(ComponentError::TooLong(400))
+        );
+        assert_eq!(
+            validate_component("CON", Platform::Native).is_err(),
+            cfg!(windows)
+        );
+    }
+}
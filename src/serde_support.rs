@@ -0,0 +1,111 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide (optional) serde support for this crate's path
+//! types.  Components and prefixes are serialized as their textual forms
+//! (so "~" and prefixes like "C:" are preserved) and parsed back with
+//! `FromStr`.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use super::hash::HashAlgo;
+use super::str_path::{StrPathComponent, StrPathPrefix};
+
+struct FromStrVisitor<T>(PhantomData<T>);
+
+impl<T> Visitor<'_> for FromStrVisitor<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<T, E> {
+        text.parse().map_err(E::custom)
+    }
+}
+
+macro_rules! serde_as_string {
+    ( $t:ty ) => {
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_str(FromStrVisitor(PhantomData))
+            }
+        }
+    };
+}
+
+serde_as_string!(StrPathComponent);
+serde_as_string!(StrPathPrefix);
+
+impl Serialize for HashAlgo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for HashAlgo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        HashAlgo::from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("{}: unknown hash algorithm", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::de::value::{Error, StrDeserializer};
+    use serde::de::IntoDeserializer;
+
+    fn from_str<'a, T: Deserialize<'a>>(text: &'a str) -> Result<T, Error> {
+        let deserializer: StrDeserializer<Error> = text.into_deserializer();
+        T::deserialize(deserializer)
+    }
+
+    #[test]
+    fn path_types_deserialize_from_strings() {
+        assert_eq!(
+            from_str::<StrPathComponent>("~").unwrap(),
+            StrPathComponent::HomeDir
+        );
+        assert_eq!(
+            from_str::<StrPathComponent>("C:").unwrap(),
+            StrPathComponent::Prefix(StrPathPrefix::Disk(b'C'))
+        );
+        assert_eq!(
+            from_str::<StrPathPrefix>(r"\\server\share").unwrap(),
+            StrPathPrefix::UNC("server".to_string(), "share".to_string())
+        );
+        assert!(from_str::<StrPathComponent>("a/b").is_err());
+        assert_eq!(from_str::<HashAlgo>("sha256").unwrap(), HashAlgo::Sha256);
+        assert!(from_str::<HashAlgo>("md5").is_err());
+    }
+}
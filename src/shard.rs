@@ -0,0 +1,113 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide the mapping of (hexadecimal) content hashes to
+//! paths in directories sharded by hash prefix, as used by content
+//! addressed stores and caches.
+
+use std::path::{Path, PathBuf};
+
+use super::error::PathuxError;
+
+/// How keys are distributed among subdirectories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardScheme {
+    /// "root/ab/abcdef..."
+    OneLevelHex,
+    /// "root/ab/cd/abcdef..."
+    TwoLevelHex,
+}
+
+impl ShardScheme {
+    fn levels(&self) -> usize {
+        match self {
+            ShardScheme::OneLevelHex => 1,
+            ShardScheme::TwoLevelHex => 2,
+        }
+    }
+}
+
+fn is_valid_key(key: &str, scheme: ShardScheme) -> bool {
+    key.len() > 2 * scheme.levels() && key.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Return the path for `key` (a hexadecimal string such as a hash digest)
+/// within `root` according to `scheme`.
+pub fn shard_path(root: &str, key: &str, scheme: ShardScheme) -> Result<String, PathuxError> {
+    if !is_valid_key(key, scheme) {
+        return Err(PathuxError::InvalidName(key.to_string()));
+    }
+    let mut path = PathBuf::from(root);
+    for level in 0..scheme.levels() {
+        path.push(&key[2 * level..2 * level + 2]);
+    }
+    path.push(key);
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// The inverse of `shard_path()`: return the key stored at `path` if it is
+/// laid out within `root` according to `scheme`.
+pub fn shard_key(root: &str, path: &str, scheme: ShardScheme) -> Option<String> {
+    let rel_path = Path::new(path).strip_prefix(root).ok()?;
+    let parts: Vec<&str> = rel_path
+        .iter()
+        .map(|part| part.to_str())
+        .collect::<Option<_>>()?;
+    let (key, dirs) = parts.split_last()?;
+    if dirs.len() != scheme.levels() || !is_valid_key(key, scheme) {
+        return None;
+    }
+    let matches = dirs
+        .iter()
+        .enumerate()
+        .all(|(level, dir)| *dir == &key[2 * level..2 * level + 2]);
+    if matches {
+        Some(key.to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shard_path_works() {
+        let key = "abcdef0123";
+        let path = shard_path("store", key, ShardScheme::TwoLevelHex).unwrap();
+        assert_eq!(Path::new(&path), Path::new("store/ab/cd/abcdef0123"));
+        assert_eq!(
+            shard_key("store", &path, ShardScheme::TwoLevelHex),
+            Some(key.to_string())
+        );
+        assert_eq!(shard_key("store", &path, ShardScheme::OneLevelHex), None);
+        let path = shard_path("store", key, ShardScheme::OneLevelHex).unwrap();
+        assert_eq!(Path::new(&path), Path::new("store/ab/abcdef0123"));
+        assert_eq!(
+            shard_key("store", &path, ShardScheme::OneLevelHex),
+            Some(key.to_string())
+        );
+        assert!(shard_path("store", "abc", ShardScheme::TwoLevelHex).is_err());
+        assert!(shard_path("store", "../../x", ShardScheme::OneLevelHex).is_err());
+        assert_eq!(
+            shard_key("store", "store/ab/ce/abcdef", ShardScheme::TwoLevelHex),
+            None
+        );
+        assert_eq!(
+            shard_key("other", "store/ab/abcdef", ShardScheme::OneLevelHex),
+            None
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide classification of files by their content (rather
+//! than their name).
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str;
+
+/// How much of a file is examined.
+pub const SNIFF_LEN: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Empty,
+    /// An ELF (Linux/Unix) executable, object or library.
+    Elf,
+    /// A Windows PE executable or library.
+    Pe,
+    /// A macOS Mach-O executable or library.
+    MachO,
+    Png,
+    Jpeg,
+    Gif,
+    Pdf,
+    Gzip,
+    Zip,
+    Tar,
+    /// Valid UTF-8 without any nul bytes.
+    Text,
+    Binary,
+}
+
+impl FileKind {
+    pub fn is_text(&self) -> bool {
+        *self == FileKind::Text
+    }
+}
+
+const MAGIC: &[(&[u8], FileKind)] = &[
+    (b"\x7FELF", FileKind::Elf),
+    (b"MZ", FileKind::Pe),
+    (b"\xFE\xED\xFA\xCE", FileKind::MachO),
+    (b"\xFE\xED\xFA\xCF", FileKind::MachO),
+    (b"\xCE\xFA\xED\xFE", FileKind::MachO),
+    (b"\xCF\xFA\xED\xFE", FileKind::MachO),
+    (b"\x89PNG\r\n\x1A\n", FileKind::Png),
+    (b"\xFF\xD8\xFF", FileKind::Jpeg),
+    (b"GIF87a", FileKind::Gif),
+    (b"GIF89a", FileKind::Gif),
+    (b"%PDF-", FileKind::Pdf),
+    (b"\x1F\x8B", FileKind::Gzip),
+    (b"PK\x03\x04", FileKind::Zip),
+    (b"PK\x05\x06", FileKind::Zip),
+];
+
+fn is_text(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return false;
+    }
+    match str::from_utf8(bytes) {
+        Ok(_) => true,
+        // allow for a character cut short by the end of the sample
+        Err(err) => err.error_len().is_none() && bytes.len() >= SNIFF_LEN,
+    }
+}
+
+/// Classify the (start of the) content `bytes`.
+pub fn sniff_bytes(bytes: &[u8]) -> FileKind {
+    if bytes.is_empty() {
+        return FileKind::Empty;
+    }
+    for (magic, kind) in MAGIC.iter() {
+        if bytes.starts_with(magic) {
+            return *kind;
+        }
+    }
+    if bytes.len() >= 262 && &bytes[257..262] == b"ustar" {
+        FileKind::Tar
+    } else if is_text(bytes) {
+        FileKind::Text
+    } else {
+        FileKind::Binary
+    }
+}
+
+/// Classify the file at `path` by examining its first `SNIFF_LEN` bytes.
+pub fn sniff_kind<P: AsRef<Path>>(path: &P) -> io::Result<FileKind> {
+    let mut bytes = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut bytes)?;
+    Ok(sniff_bytes(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn sniff_bytes_works() {
+        assert_eq!(sniff_bytes(b""), FileKind::Empty);
+        assert_eq!(sniff_bytes(b"\x7FELF\x02\x01"), FileKind::Elf);
+        assert_eq!(sniff_bytes(b"\x89PNG\r\n\x1A\n...."), FileKind::Png);
+        assert_eq!(sniff_bytes(b"%PDF-1.7\n"), FileKind::Pdf);
+        assert_eq!(sniff_bytes(b"\x1F\x8B\x08"), FileKind::Gzip);
+        assert_eq!(sniff_bytes("plain text é\n".as_bytes()), FileKind::Text);
+        assert_eq!(sniff_bytes(b"text\0with nul"), FileKind::Binary);
+        assert_eq!(sniff_bytes(b"\xC3"), FileKind::Binary);
+        let mut tar = vec![0u8; 512];
+        tar[..4].copy_from_slice(b"file");
+        tar[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(sniff_bytes(&tar), FileKind::Tar);
+        let mut long_text = vec![b'x'; SNIFF_LEN - 1];
+        long_text.push(0xC3);
+        assert_eq!(sniff_bytes(&long_text), FileKind::Text);
+    }
+
+    #[test]
+    fn sniff_kind_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("no_extension");
+        fs::write(&path, b"MZ\x90\x00").unwrap();
+        assert_eq!(sniff_kind(&path).unwrap(), FileKind::Pe);
+        assert!(sniff_kind(&"Cargo.toml").unwrap().is_text());
+        assert!(sniff_kind(&temp_dir.path().join("missing")).is_err());
+    }
+}
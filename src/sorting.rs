@@ -0,0 +1,138 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide orderings for file names that suit people rather
+//! than computers e.g. "img2.png" before "img10.png".
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    /// Plain code point order.
+    #[default]
+    Name,
+    /// As `Name` except that runs of digits are compared numerically.
+    Natural,
+    /// As `Natural` except that case and accents are ignored (unless the
+    /// names are otherwise equal).  This is an approximation of locale
+    /// aware collation that needs no locale data.
+    NaturalCollated,
+}
+
+impl SortBy {
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortBy::Name => a.cmp(b),
+            SortBy::Natural => natural_cmp(a, b),
+            SortBy::NaturalCollated => collated_cmp(a, b),
+        }
+    }
+}
+
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+        run.push(ch);
+    }
+    run
+}
+
+fn numeric_cmp(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compare `a` and `b` treating runs of digits as numbers so that e.g.
+/// "img2.png" < "img10.png".  Names that only differ in leading zeros are
+/// ordered by code point.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => break,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a_ch), Some(b_ch)) if a_ch.is_ascii_digit() && b_ch.is_ascii_digit() => {
+                numeric_cmp(&digit_run(&mut a_chars), &digit_run(&mut b_chars))
+            }
+            (Some(a_ch), Some(b_ch)) => {
+                let ordering = a_ch.cmp(b_ch);
+                a_chars.next();
+                b_chars.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.cmp(b)
+}
+
+// Remove accents and case.
+fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|ch| !is_combining_mark(*ch))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Compare `a` and `b` as `natural_cmp()` does but ignoring case and
+/// accents unless that makes them equal.
+pub fn collated_cmp(a: &str, b: &str) -> Ordering {
+    natural_cmp(&fold(a), &fold(b)).then_with(|| natural_cmp(a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_cmp_works() {
+        let mut names = vec!["img10.png", "img2.png", "img1.png", "img02.png", "img"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["img", "img1.png", "img02.png", "img2.png", "img10.png"]
+        );
+        assert_eq!(
+            natural_cmp("a99999999999999999999b", "a100000000000000000000b"),
+            Ordering::Less
+        );
+        assert_eq!(natural_cmp("v1.2.10", "v1.2.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("same", "same"), Ordering::Equal);
+    }
+
+    #[test]
+    fn collated_cmp_works() {
+        let mut names = vec!["Zebra", "\u{e9}clair", "apple", "eclair", "Apple"];
+        names.sort_by(|a, b| SortBy::NaturalCollated.compare(a, b));
+        assert_eq!(
+            names,
+            vec!["Apple", "apple", "eclair", "\u{e9}clair", "Zebra"]
+        );
+        names.sort_by(|a, b| SortBy::Name.compare(a, b));
+        assert_eq!(
+            names,
+            vec!["Apple", "Zebra", "apple", "eclair", "\u{e9}clair"]
+        );
+    }
+}
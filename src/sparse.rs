@@ -0,0 +1,198 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide detection and copying of sparse files (files
+//! with "holes" that occupy no disk space).
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+mod imp {
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use libc;
+
+    // The offset of the next data (or hole) at or after `offset` or `None`
+    // if there is no more data.
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if result >= 0 {
+            Ok(Some(result as u64))
+        } else {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENXIO) {
+                Ok(None)
+            } else {
+                Err(err)
+            }
+        }
+    }
+
+    pub fn is_sparse(path: &Path) -> io::Result<bool> {
+        let metadata = fs::metadata(path)?;
+        if !metadata.is_file() {
+            return Ok(false);
+        }
+        if metadata.blocks() * 512 < metadata.len() {
+            return Ok(true);
+        }
+        // the allocation may be rounded up (or include metadata) so look
+        // for an actual hole before the end of the file
+        let file = File::open(path)?;
+        match seek(&file, 0, libc::SEEK_HOLE) {
+            Ok(Some(hole)) => Ok(hole < metadata.len()),
+            Ok(None) => Ok(false),
+            // SEEK_HOLE isn't supported by this file system
+            Err(ref err) if err.raw_os_error() == Some(libc::EINVAL) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn copy_sparse(from: &Path, to: &Path) -> io::Result<u64> {
+        let mut src = File::open(from)?;
+        let metadata = src.metadata()?;
+        let len = metadata.len();
+        let mut dst = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(to)?;
+        let mut offset = 0;
+        while offset < len {
+            let data = match seek(&src, offset, libc::SEEK_DATA) {
+                Ok(Some(data)) => data,
+                Ok(None) => break,
+                Err(ref err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                    // no hole support so copy everything
+                    drop(dst);
+                    return fs::copy(from, to);
+                }
+                Err(err) => return Err(err),
+            };
+            let hole = seek(&src, data, libc::SEEK_HOLE)?.unwrap_or(len);
+            src.seek(SeekFrom::Start(data))?;
+            dst.seek(SeekFrom::Start(data))?;
+            io::copy(&mut (&mut src).take(hole - data), &mut dst)?;
+            offset = hole;
+        }
+        // a trailing hole is created by setting the length
+        dst.set_len(len)?;
+        dst.flush()?;
+        fs::set_permissions(to, metadata.permissions())?;
+        Ok(len)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::fs;
+    use std::io;
+    use std::os::windows::fs::MetadataExt;
+    use std::path::Path;
+
+    use winapi::um::winnt::FILE_ATTRIBUTE_SPARSE_FILE;
+
+    pub fn is_sparse(path: &Path) -> io::Result<bool> {
+        Ok(fs::metadata(path)?.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0)
+    }
+
+    pub fn copy_sparse(from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    windows
+)))]
+mod imp {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    pub fn is_sparse(path: &Path) -> io::Result<bool> {
+        fs::metadata(path).map(|_| false)
+    }
+
+    pub fn copy_sparse(from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+}
+
+/// Return `true` if `path` is a file with holes i.e. some of its content
+/// has no disk space allocated to it.
+pub fn is_sparse<P: AsRef<Path>>(path: &P) -> io::Result<bool> {
+    imp::is_sparse(path.as_ref())
+}
+
+/// Like `std::fs::copy()` but only the data is copied so that holes in
+/// `from` are also holes in `to`.  Where holes can't be found (e.g. on
+/// Windows) this is the same as `std::fs::copy()`.
+pub fn copy_sparse<F: AsRef<Path>, T: AsRef<Path>>(from: &F, to: &T) -> io::Result<u64> {
+    let from: &Path = from.as_ref();
+    if fs::metadata(from)?.is_file() {
+        imp::copy_sparse(from, to.as_ref())
+    } else {
+        fs::copy(from, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs::File;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[test]
+    fn copy_sparse_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        let mut file = File::create(&src).unwrap();
+        file.write_all(b"start").unwrap();
+        file.seek(SeekFrom::Start(16 * 1024 * 1024)).unwrap();
+        file.write_all(b"middle").unwrap();
+        file.set_len(32 * 1024 * 1024).unwrap();
+        drop(file);
+
+        assert_eq!(copy_sparse(&src, &dst).unwrap(), 32 * 1024 * 1024);
+        assert_eq!(fs::read(&src).unwrap(), fs::read(&dst).unwrap());
+        // the file system may not support holes
+        if is_sparse(&src).unwrap() {
+            assert!(is_sparse(&dst).unwrap());
+        }
+        fs::write(&src, "dense").unwrap();
+        assert!(!is_sparse(&src).unwrap());
+        assert!(is_sparse(&temp_dir.path().join("missing")).is_err());
+    }
+}
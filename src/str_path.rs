@@ -21,178 +21,502 @@ pub use std::ffi::OsStr;
 pub use std::io;
 pub use std::path::{Component, Path, PathBuf, Prefix, MAIN_SEPARATOR};
 
-use std::string::ToString;
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Rev;
+use std::path;
+use std::str::FromStr;
 
+#[cfg(all(feature = "home", not(target_arch = "wasm32")))]
 pub use dirs;
 
-use super::strip_n_levels;
+use super::context::{Context, ExpandOptions, PathCtx};
+use super::error::PathuxError;
+use super::fs_provider::{FsProvider, RealFs};
+use super::permissions::{path_is_executable, path_is_readable, path_is_writable};
+use super::{expand_home_dir_checked, strip_n_levels};
+
+// The macros are thin wrappers around the functions below (which can
+// be used without `#[macro_use]`) but, like `Path::new()`, also accept
+// `&Path`, `&PathBuf`, `&OsStr` etc. (converted lossily to `str`).
 
 #[macro_export]
 macro_rules! str_path_file_name {
     ( $s:expr ) => {
-        match Path::new($s).file_name() {
-            Some(os_str) => Some(os_str.to_string_lossy().into_owned()),
-            None => None,
-        }
+        $crate::str_path::file_name(&$crate::str_path::Path::new($s).to_string_lossy())
     };
 }
 
 #[macro_export]
 macro_rules! str_path_parent {
     ( $s:expr ) => {
-        match Path::new($s).parent() {
-            Some(path) => Some(path.to_string_lossy().into_owned()),
-            None => None,
-        }
+        $crate::str_path::parent(&$crate::str_path::Path::new($s).to_string_lossy())
     };
 }
 
 #[macro_export]
 macro_rules! str_path_components {
-    ( $s:expr ) => {{
-        Path::new($s).components().enumerate().map(|(i, c)| {
-            if i == 0 && c == Component::Normal(OsStr::new("~")) {
-                StrPathComponent::HomeDir
-            } else {
-                StrPathComponent::from(c)
-            }
-        })
-    }};
+    ( $s:expr ) => {
+        $crate::str_path::os_components($s)
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_component_refs {
+    ( $s:expr ) => {
+        $crate::str_path::StrPathComponents::new($s)
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_rsplit {
+    ( $s:expr ) => {
+        $crate::str_path::rsplit(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_strip_prefix {
+    ( $s:expr, $base:expr ) => {
+        $crate::str_path::strip_prefix(
+            &$crate::str_path::Path::new($s).to_string_lossy(),
+            &$crate::str_path::Path::new($base).to_string_lossy(),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_with_file_name {
+    ( $s:expr, $name:expr ) => {
+        $crate::str_path::with_file_name(
+            &$crate::str_path::Path::new($s).to_string_lossy(),
+            &$crate::str_path::Path::new($name).to_string_lossy(),
+        )
+    };
 }
 
 #[macro_export]
 macro_rules! str_path_is_absolute {
-    ( $s:expr ) => {{
-        match str_path_components!($s).take(1).next() {
-            Some(StrPathComponent::HomeDir) => false,
-            _ => Path::new($s).is_absolute(),
-        }
-    }};
+    ( $s:expr ) => {
+        $crate::str_path::is_absolute(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
 }
 
 #[macro_export]
 macro_rules! str_path_is_relative {
-    ( $s:expr ) => {{
-        match str_path_components!($s).take(1).next() {
-            Some(StrPathComponent::HomeDir) => false,
-            _ => Path::new($s).is_relative(),
-        }
-    }};
+    ( $s:expr ) => {
+        $crate::str_path::is_relative(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
 }
 
 #[macro_export]
 macro_rules! str_path_is_relative_to_home {
-    ( $s:expr ) => {{
-        match str_path_components!($s).take(1).next() {
-            Some(StrPathComponent::HomeDir) => true,
-            _ => false,
-        }
-    }};
+    ( $s:expr ) => {
+        $crate::str_path::is_relative_to_home(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_is_executable {
+    ( $s:expr ) => {
+        $crate::str_path::is_executable(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
 }
 
 #[macro_export]
 macro_rules! str_path_absolute {
-    ( $s:expr ) => {{
-        if str_path_is_absolute!($s) {
-            Ok($s.to_string())
-        } else if str_path_is_relative!($s) {
-            match env::current_dir() {
-                Ok(mut cur_dir) => {
-                    for c in Path::new($s)
-                        .components()
-                        .skip_while(|c| *c == Component::CurDir)
-                    {
-                        cur_dir.push(c)
-                    }
-                    Ok(cur_dir.to_string_lossy().into_owned())
-                }
-                Err(err) => Err(err),
-            }
+    ( $s:expr ) => {
+        $crate::str_path::absolute(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_simple_relative {
+    ( $s:expr ) => {
+        $crate::str_path::simple_relative(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_simple_relative_home {
+    ( $s:expr ) => {
+        $crate::str_path::simple_relative_home(&$crate::str_path::Path::new($s).to_string_lossy())
+    };
+}
+
+/// Like `str_path_absolute!` but relative to the directories in the
+/// `PathCtx` given as the second argument.
+#[macro_export]
+macro_rules! str_path_absolute_in_ctx {
+    ( $s:expr, $ctx:expr ) => {
+        $crate::str_path::absolute_in_ctx(&$crate::str_path::Path::new($s).to_string_lossy(), $ctx)
+    };
+}
+
+/// Like `str_path_simple_relative!` but relative to the directories in the
+/// `PathCtx` given as the second argument.
+#[macro_export]
+macro_rules! str_path_simple_relative_in_ctx {
+    ( $s:expr, $ctx:expr ) => {
+        $crate::str_path::simple_relative_in_ctx(
+            &$crate::str_path::Path::new($s).to_string_lossy(),
+            $ctx,
+        )
+    };
+}
+
+/// Like `str_path_absolute!` but using the directories in the
+/// `ExpandOptions` given as the second argument.
+#[macro_export]
+macro_rules! str_path_absolute_with {
+    ( $s:expr, $opts:expr ) => {
+        $crate::str_path::absolute_with(&$crate::str_path::Path::new($s).to_string_lossy(), $opts)
+    };
+}
+
+/// Like `str_path_simple_relative!` but using the directories in the
+/// `ExpandOptions` given as the second argument.
+#[macro_export]
+macro_rules! str_path_simple_relative_with {
+    ( $s:expr, $opts:expr ) => {
+        $crate::str_path::simple_relative_with(
+            &$crate::str_path::Path::new($s).to_string_lossy(),
+            $opts,
+        )
+    };
+}
+
+/// Join an untrusted relative path to a base directory making sure that
+/// the result stays within it (see `safe_join::safe_join()`).
+#[macro_export]
+macro_rules! str_path_safe_join {
+    ( $base:expr, $untrusted:expr ) => {
+        $crate::safe_join::safe_join(
+            &$crate::str_path::Path::new($base).to_string_lossy(),
+            &$crate::str_path::Path::new($untrusted).to_string_lossy(),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! str_path_join {
+    ( $s1:expr, $s2:expr ) => {
+        $crate::str_path::join(
+            &$crate::str_path::Path::new($s1).to_string_lossy(),
+            &$crate::str_path::Path::new($s2).to_string_lossy(),
+        )
+    };
+}
+
+/// Create a hard link `$dst` to the existing file `$src` (after expanding
+/// any leading "~" in either).
+#[macro_export]
+macro_rules! str_path_hard_link {
+    ( $src:expr, $dst:expr ) => {
+        $crate::str_path::hard_link(
+            &$crate::str_path::Path::new($src).to_string_lossy(),
+            &$crate::str_path::Path::new($dst).to_string_lossy(),
+        )
+    };
+}
+
+/// Read the file at `$path` (after expanding any leading "~") into a
+/// `String`.
+#[macro_export]
+macro_rules! str_path_read_to_string {
+    ( $path:expr ) => {
+        $crate::str_path::read_to_string(&$crate::str_path::Path::new($path).to_string_lossy())
+    };
+}
+
+/// Write `$contents` to the file at `$path` (after expanding any leading
+/// "~") creating any missing parent directories if the optional third
+/// argument is `true`.
+#[macro_export]
+macro_rules! str_path_write {
+    ( $path:expr, $contents:expr ) => {
+        $crate::str_path::write(
+            &$crate::str_path::Path::new($path).to_string_lossy(),
+            $contents,
+            false,
+        )
+    };
+    ( $path:expr, $contents:expr, $create_dirs:expr ) => {
+        $crate::str_path::write(
+            &$crate::str_path::Path::new($path).to_string_lossy(),
+            $contents,
+            $create_dirs,
+        )
+    };
+}
+
+pub fn file_name(path: &str) -> Option<String> {
+    Path::new(path)
+        .file_name()
+        .map(|os_str| os_str.to_string_lossy().into_owned())
+}
+
+pub fn parent(path: &str) -> Option<String> {
+    Path::new(path)
+        .parent()
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// The components of `path` with a leading "~" as `StrPathComponent::HomeDir`.
+pub fn components(path: &str) -> impl Iterator<Item = StrPathComponent> + '_ {
+    StrPathComponents::new(path).map(StrPathComponent::from)
+}
+
+/// Like `components()` but for anything `Path::new()` accepts with non
+/// UTF-8 components converted lossily.
+pub fn os_components<S: AsRef<OsStr> + ?Sized>(
+    path: &S,
+) -> impl Iterator<Item = StrPathComponent> + '_ {
+    Path::new(path).components().enumerate().map(|(i, c)| {
+        if i == 0 && c == Component::Normal(OsStr::new("~")) {
+            StrPathComponent::HomeDir
         } else {
-            match dirs::home_dir() {
-                Some(mut home_dir) => {
-                    for c in Path::new($s).components().skip(1) {
-                        home_dir.push(c)
-                    }
-                    Ok(home_dir.to_string_lossy().into_owned())
+            StrPathComponent::from(c)
+        }
+    })
+}
+
+/// Split `path` into its parent and its last component e.g. "a/b/" gives
+/// ("a", "b").  `None` is returned if there's no component to split off.
+pub fn rsplit(path: &str) -> Option<(String, String)> {
+    let mut components = StrPathComponents::new(path);
+    match components.next_back() {
+        Some(StrPathComponentRef::Prefix(_)) | Some(StrPathComponentRef::RootDir) | None => None,
+        Some(last) => Some((
+            components.as_path_string().to_string(),
+            last.as_str().to_string(),
+        )),
+    }
+}
+
+/// Remove `base` from the start of `path` comparing by component (so
+/// "~/SRC" is a prefix of "~/SRC/x" but "a/b" isn't a prefix of "a/bc").
+pub fn strip_prefix(path: &str, base: &str) -> Option<String> {
+    let mut components = StrPathComponents::new(path);
+    if StrPathComponents::new(base).all(|base| components.next() == Some(base)) {
+        Some(components.as_path_string().to_string())
+    } else {
+        None
+    }
+}
+
+pub fn with_file_name(path: &str, name: &str) -> String {
+    Path::new(path)
+        .with_file_name(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Paths starting with "~" are neither absolute nor relative.
+pub fn is_absolute(path: &str) -> bool {
+    !is_relative_to_home(path) && Path::new(path).is_absolute()
+}
+
+pub fn is_relative(path: &str) -> bool {
+    !is_relative_to_home(path) && Path::new(path).is_relative()
+}
+
+pub fn is_relative_to_home(path: &str) -> bool {
+    StrPathComponents::new(path).next() == Some(StrPathComponentRef::HomeDir)
+}
+
+pub fn is_executable(path: &str) -> bool {
+    path_is_executable(&path)
+}
+
+pub fn absolute(path: &str) -> io::Result<String> {
+    if is_absolute(path) {
+        Ok(path.to_string())
+    } else if is_relative(path) {
+        let mut cur_dir = Context::current_dir()?;
+        for c in Path::new(path)
+            .components()
+            .skip_while(|c| *c == Component::CurDir)
+        {
+            cur_dir.push(c)
+        }
+        Ok(cur_dir.to_string_lossy().into_owned())
+    } else {
+        match Context::home_dir() {
+            Some(mut home_dir) => {
+                for c in Path::new(path).components().skip(1) {
+                    home_dir.push(c)
                 }
-                None => Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "could not find home directory",
-                )),
+                Ok(home_dir.to_string_lossy().into_owned())
             }
+            None => Err(io::Error::other("could not find home directory")),
         }
-    }};
+    }
 }
 
-#[macro_export]
-macro_rules! str_path_simple_relative {
-    ( $s:expr ) => {{
-        match env::current_dir() {
-            Ok(curr_dir) => match str_path_absolute!($s) {
-                Ok(abs_path) => match Path::new(&abs_path).strip_prefix(curr_dir) {
-                    Ok(path) => Ok(path.to_string_lossy().into_owned()),
-                    Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
-                },
-                Err(err) => Err(err),
-            },
-            Err(err) => Err(err),
+pub fn simple_relative(path: &str) -> io::Result<String> {
+    let curr_dir = Context::current_dir()?;
+    let abs_path = absolute(path)?;
+    match Path::new(&abs_path).strip_prefix(curr_dir) {
+        Ok(path) => Ok(path.to_string_lossy().into_owned()),
+        Err(err) => Err(io::Error::other(err)),
+    }
+}
+
+pub fn simple_relative_home(path: &str) -> io::Result<String> {
+    match Context::home_dir() {
+        Some(home_dir) => {
+            let abs_path = absolute(path)?;
+            match Path::new(&abs_path).strip_prefix(home_dir) {
+                Ok(path) => {
+                    let mut home = PathBuf::new();
+                    home.push("~");
+                    home.push(path);
+                    Ok(home.to_string_lossy().into_owned())
+                }
+                Err(err) => Err(io::Error::other(err)),
+            }
         }
-    }};
+        None => Err(io::Error::other("could not find home directory")),
+    }
 }
 
-#[macro_export]
-macro_rules! str_path_simple_relative_home {
-    ( $s:expr ) => {{
-        match dirs::home_dir() {
-            Some(home_dir) => match str_path_absolute!($s) {
-                Ok(abs_path) => match Path::new(&abs_path).strip_prefix(home_dir) {
-                    Ok(path) => {
-                        let mut home = PathBuf::new();
-                        home.push("~");
-                        home.push(&path);
-                        Ok(home.to_string_lossy().into_owned())
-                    }
-                    Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
-                },
-                Err(err) => Err(err),
-            },
+/// Like `absolute()` but relative to the directories in `ctx`.
+pub fn absolute_in_ctx(path: &str, ctx: &PathCtx) -> io::Result<String> {
+    if is_absolute(path) {
+        Ok(path.to_string())
+    } else if is_relative(path) {
+        let mut cur_dir = ctx.cwd.clone();
+        for c in Path::new(path)
+            .components()
+            .skip_while(|c| *c == Component::CurDir)
+        {
+            cur_dir.push(c)
+        }
+        Ok(cur_dir.to_string_lossy().into_owned())
+    } else {
+        match ctx.home {
+            Some(ref home_dir) => {
+                let mut home_dir = home_dir.clone();
+                for c in Path::new(path).components().skip(1) {
+                    home_dir.push(c)
+                }
+                Ok(home_dir.to_string_lossy().into_owned())
+            }
             None => Err(io::Error::new(
-                io::ErrorKind::Other,
+                io::ErrorKind::NotFound,
                 "could not find home directory",
             )),
         }
-    }};
+    }
 }
 
-#[macro_export]
-macro_rules! str_path_join {
-    ( $s1:expr, $s2:expr ) => {{
-        Path::new($s1)
-            .join(Path::new($s2))
-            .to_string_lossy()
-            .into_owned()
-    }};
+/// Like `simple_relative()` but relative to the directories in `ctx`.
+pub fn simple_relative_in_ctx(path: &str, ctx: &PathCtx) -> io::Result<String> {
+    let abs_path = absolute_in_ctx(path, ctx)?;
+    match Path::new(&abs_path).strip_prefix(&ctx.cwd) {
+        Ok(path) => Ok(path.to_string_lossy().into_owned()),
+        Err(err) => Err(io::Error::new(io::ErrorKind::InvalidInput, err)),
+    }
+}
+
+/// Like `absolute()` but using the directories in `opts`.
+pub fn absolute_with(path: &str, opts: &ExpandOptions) -> io::Result<String> {
+    absolute_in_ctx(path, &opts.path_ctx()?)
+}
+
+/// Like `simple_relative()` but using the directories in `opts`.
+pub fn simple_relative_with(path: &str, opts: &ExpandOptions) -> io::Result<String> {
+    simple_relative_in_ctx(path, &opts.path_ctx()?)
+}
+
+pub fn join(path: &str, other: &str) -> String {
+    Path::new(path)
+        .join(Path::new(other))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Create a hard link `dst` to the existing file `src` (after expanding
+/// any leading "~" in either).
+pub fn hard_link(src: &str, dst: &str) -> Result<(), PathuxError> {
+    let src = expand_home_dir_checked(Path::new(src))?;
+    let dst = expand_home_dir_checked(Path::new(dst))?;
+    Ok(::std::fs::hard_link(src, dst)?)
+}
+
+pub fn read_to_string(path: &str) -> Result<String, PathuxError> {
+    let path = expand_home_dir_checked(Path::new(path))?;
+    Ok(::std::fs::read_to_string(path)?)
+}
+
+pub fn write<C: AsRef<[u8]>>(
+    path: &str,
+    contents: C,
+    create_dirs: bool,
+) -> Result<(), PathuxError> {
+    let path = expand_home_dir_checked(Path::new(path))?;
+    if create_dirs {
+        if let Some(dir_path) = path.parent() {
+            ::std::fs::create_dir_all(dir_path)?;
+        }
+    }
+    Ok(::std::fs::write(path, contents)?)
 }
 
 pub fn str_path_current_dir() -> io::Result<String> {
-    match env::current_dir() {
+    match Context::current_dir() {
         Ok(path_buf) => Ok(path_buf.to_string_lossy().into_owned()),
         Err(e) => Err(e),
     }
 }
 
+/// Replace the home directory at the start of the absolute path `path`
+/// with "~".  Unlike `str_path_simple_relative_home!` this never fails:
+/// `path` is returned unchanged if it isn't (absolute and) under home.
+pub fn contract_home(path: &str) -> String {
+    let home_dir = match Context::home_dir() {
+        Some(home_dir) => home_dir,
+        None => return path.to_string(),
+    };
+    if !str_path_is_absolute!(path) {
+        return path.to_string();
+    }
+    match Path::new(path).strip_prefix(home_dir) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) => Path::new("~").join(rest).to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// A convenience wrapper around `str_path_current_dir()` that panics if the
+/// current directory can't be found.
 pub fn str_path_current_dir_or_panic() -> String {
     str_path_current_dir().expect("Could not find current directory.")
 }
 
 pub fn str_path_current_dir_rel_home() -> io::Result<String> {
-    match env::current_dir() {
-        Ok(path_buf) => str_path_simple_relative_home!(&path_buf.to_string_lossy().into_owned()),
+    match Context::current_dir() {
+        Ok(path_buf) => str_path_simple_relative_home!(&path_buf),
         Err(e) => Err(e),
     }
 }
 
+/// The current directory relative to the home directory (i.e. starting
+/// with "~").
+pub fn current_dir_rel_home_checked() -> Result<String, PathuxError> {
+    let current_dir = Context::current_dir().map_err(PathuxError::NoCwd)?;
+    let home_dir = Context::home_dir().ok_or(PathuxError::NoHome)?;
+    match current_dir.strip_prefix(home_dir) {
+        Ok(rest) => Ok(Path::new("~").join(rest).to_string_lossy().into_owned()),
+        Err(_) => Err(PathuxError::NotUnderBase(
+            current_dir.to_string_lossy().into_owned(),
+        )),
+    }
+}
+
+/// A convenience wrapper around `str_path_current_dir_rel_home()` that
+/// panics on failure: see `current_dir_rel_home_checked()`.
 pub fn str_path_current_dir_or_rel_home_panic() -> String {
     str_path_current_dir_rel_home().expect("Could not find current directory.")
 }
@@ -200,70 +524,163 @@ pub fn str_path_current_dir_or_rel_home_panic() -> String {
 pub trait StrPath {
     fn path_absolute(&self) -> io::Result<String>;
     fn path_components(&self) -> Vec<StrPathComponent>;
+    fn path_component_refs(&self) -> StrPathComponents<'_>;
+    fn path_components_rev(&self) -> Rev<StrPathComponents<'_>>;
+    fn path_component(&self, n: usize) -> Option<StrPathComponentRef<'_>>;
+    fn path_contract_home(&self) -> String;
+    fn path_depth(&self) -> usize;
+    fn path_ends_with(&self, suffix: &str) -> bool;
     fn path_is_absolute(&self) -> bool;
     fn path_is_dir(&self) -> bool;
+    /// Like `path_is_dir()` but within the file system `fs`.
+    fn path_is_dir_with<F: FsProvider>(&self, fs: &F) -> bool;
+    fn path_is_executable(&self) -> bool;
     fn path_is_file(&self) -> bool;
+    /// Like `path_is_file()` but within the file system `fs`.
+    fn path_is_file_with<F: FsProvider>(&self, fs: &F) -> bool;
     fn path_is_relative(&self) -> bool;
+    fn path_is_readable(&self) -> bool;
     fn path_is_relative_to_home(&self) -> bool;
     fn path_file_name(&self) -> Option<String>;
+    fn path_is_writable(&self) -> bool;
     fn path_join(&self, other: &str) -> String;
     fn path_parent(&self) -> Option<String>;
+    fn path_sibling(&self, name: &str) -> Option<String>;
     fn path_simple_relative(&self) -> io::Result<String>;
     fn path_starts_with(&self, prefix: &str) -> bool;
+    fn path_strip_prefix(&self, base: &str) -> Option<String>;
     fn path_stripped_of_n_levels(&self, n: usize) -> String;
+    fn path_truncate_to_depth(&self, n: usize) -> String;
 }
 
-impl StrPath for str {
+/// Implemented for `str` and anything that can be viewed as one (e.g.
+/// `String`, `Cow<str>`, `Box<str>`, `Rc<str>` and `Arc<str>`).
+impl<T: AsRef<str> + ?Sized> StrPath for T {
     fn path_absolute(&self) -> io::Result<String> {
-        str_path_absolute!(self)
+        str_path_absolute!(self.as_ref())
     }
 
     fn path_components(&self) -> Vec<StrPathComponent> {
-        str_path_components!(self).collect()
+        self.path_component_refs()
+            .map(StrPathComponent::from)
+            .collect()
+    }
+
+    fn path_component_refs(&self) -> StrPathComponents<'_> {
+        str_path_component_refs!(self.as_ref())
+    }
+
+    fn path_components_rev(&self) -> Rev<StrPathComponents<'_>> {
+        self.path_component_refs().rev()
+    }
+
+    fn path_component(&self, n: usize) -> Option<StrPathComponentRef<'_>> {
+        self.path_component_refs().nth(n)
+    }
+
+    fn path_contract_home(&self) -> String {
+        contract_home(self.as_ref())
+    }
+
+    fn path_depth(&self) -> usize {
+        self.path_component_refs()
+            .filter(StrPathComponentRef::counts_for_depth)
+            .count()
+    }
+
+    fn path_ends_with(&self, suffix: &str) -> bool {
+        Path::new(self.as_ref()).ends_with(Path::new(suffix))
     }
 
     fn path_is_absolute(&self) -> bool {
-        str_path_is_absolute!(self)
+        str_path_is_absolute!(self.as_ref())
     }
 
     fn path_is_dir(&self) -> bool {
-        Path::new(self).is_dir()
+        self.path_is_dir_with(&RealFs)
+    }
+
+    fn path_is_dir_with<F: FsProvider>(&self, fs: &F) -> bool {
+        fs.is_dir(Path::new(self.as_ref()))
+    }
+
+    fn path_is_executable(&self) -> bool {
+        str_path_is_executable!(self.as_ref())
     }
 
     fn path_is_file(&self) -> bool {
-        Path::new(self).is_file()
+        self.path_is_file_with(&RealFs)
+    }
+
+    fn path_is_file_with<F: FsProvider>(&self, fs: &F) -> bool {
+        fs.is_file(Path::new(self.as_ref()))
     }
 
     fn path_is_relative(&self) -> bool {
-        str_path_is_relative!(self)
+        str_path_is_relative!(self.as_ref())
+    }
+
+    fn path_is_readable(&self) -> bool {
+        path_is_readable(&self.as_ref())
     }
 
     fn path_is_relative_to_home(&self) -> bool {
-        str_path_is_relative_to_home!(self)
+        str_path_is_relative_to_home!(self.as_ref())
+    }
+
+    fn path_is_writable(&self) -> bool {
+        path_is_writable(&self.as_ref())
     }
 
     fn path_file_name(&self) -> Option<String> {
-        str_path_file_name!(self)
+        str_path_file_name!(self.as_ref())
     }
 
     fn path_join(&self, other: &str) -> String {
-        str_path_join!(self, other)
+        str_path_join!(self.as_ref(), other)
     }
 
     fn path_parent(&self) -> Option<String> {
-        str_path_parent!(self)
+        str_path_parent!(self.as_ref())
+    }
+
+    fn path_sibling(&self, name: &str) -> Option<String> {
+        Path::new(self.as_ref())
+            .parent()
+            .map(|parent| parent.join(name).to_string_lossy().into_owned())
     }
 
     fn path_simple_relative(&self) -> io::Result<String> {
-        str_path_simple_relative!(self)
+        str_path_simple_relative!(self.as_ref())
     }
 
     fn path_starts_with(&self, prefix: &str) -> bool {
-        Path::new(self).starts_with(Path::new(prefix))
+        Path::new(self.as_ref()).starts_with(Path::new(prefix))
+    }
+
+    fn path_strip_prefix(&self, base: &str) -> Option<String> {
+        str_path_strip_prefix!(self.as_ref(), base)
     }
 
     fn path_stripped_of_n_levels(&self, n: usize) -> String {
-        strip_n_levels(&self, n).to_string_lossy().to_string()
+        strip_n_levels(&self.as_ref(), n)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn path_truncate_to_depth(&self, n: usize) -> String {
+        let mut path_buf = PathBuf::new();
+        let mut depth = 0;
+        for component in self.path_component_refs() {
+            if component.counts_for_depth() {
+                if depth == n {
+                    break;
+                }
+                depth += 1;
+            }
+            path_buf.push(component.as_str());
+        }
+        path_buf.to_string_lossy().into_owned()
     }
 }
 
@@ -289,7 +706,7 @@ impl StringPathBuf for String {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum StrPathPrefix {
     Verbatim(String),
     VerbatimUNC(String, String),
@@ -299,19 +716,75 @@ pub enum StrPathPrefix {
     Disk(u8),
 }
 
-impl ToString for StrPathPrefix {
-    fn to_string(&self) -> String {
+impl fmt::Display for StrPathPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            StrPathPrefix::Verbatim(string) => format!(r"\\?\{}", string),
-            StrPathPrefix::VerbatimUNC(server, share) => format!(r"\\?\UNC\{}\{}", server, share),
-            StrPathPrefix::VerbatimDisk(vid) => format!(r"\\?\{}:\", vid.to_string()),
-            StrPathPrefix::DeviceNS(device) => format!(r"\\.\{}", device),
-            StrPathPrefix::UNC(server, share) => format!(r"\\{}\{}", server, share),
-            StrPathPrefix::Disk(id) => format!(r"{}:", id.to_string()),
+            StrPathPrefix::Verbatim(string) => write!(f, r"\\?\{}", string),
+            StrPathPrefix::VerbatimUNC(server, share) => {
+                write!(f, r"\\?\UNC\{}\{}", server, share)
+            }
+            StrPathPrefix::VerbatimDisk(vid) => write!(f, r"\\?\{}:", *vid as char),
+            StrPathPrefix::DeviceNS(device) => write!(f, r"\\.\{}", device),
+            StrPathPrefix::UNC(server, share) => write!(f, r"\\{}\{}", server, share),
+            StrPathPrefix::Disk(id) => write!(f, "{}:", *id as char),
         }
     }
 }
 
+// Parse "C:" as a drive letter.
+fn parse_disk(text: &str) -> Option<u8> {
+    match text.as_bytes() {
+        [letter, b':'] if letter.is_ascii_alphabetic() => Some(letter.to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+// Parse "server\share" (with either separator).
+fn parse_server_share(text: &str) -> Option<(String, String)> {
+    let mut parts = text.split(['\\', '/']);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(server), Some(share), None) if !server.is_empty() && !share.is_empty() => {
+            Some((server.to_string(), share.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Parse the textual forms of Windows path prefixes (on any platform)
+/// e.g. "C:", "\\server\share", "\\?\C:" and "\\.\COM1".
+impl FromStr for StrPathPrefix {
+    type Err = PathuxError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let prefix = if let Some(rest) = text.strip_prefix(r"\\?\") {
+            if let Some(server_share) = rest.strip_prefix(r"UNC\") {
+                parse_server_share(server_share)
+                    .map(|(server, share)| StrPathPrefix::VerbatimUNC(server, share))
+            } else if let Some(vid) = parse_disk(rest) {
+                Some(StrPathPrefix::VerbatimDisk(vid))
+            } else if !rest.is_empty() && !rest.contains('\\') {
+                Some(StrPathPrefix::Verbatim(rest.to_string()))
+            } else {
+                None
+            }
+        } else if let Some(device) = text.strip_prefix(r"\\.\") {
+            if !device.is_empty() && !device.contains(['\\', '/']) {
+                Some(StrPathPrefix::DeviceNS(device.to_string()))
+            } else {
+                None
+            }
+        } else if let Some(server_share) =
+            text.strip_prefix(r"\\").or_else(|| text.strip_prefix("//"))
+        {
+            parse_server_share(server_share)
+                .map(|(server, share)| StrPathPrefix::UNC(server, share))
+        } else {
+            parse_disk(text).map(StrPathPrefix::Disk)
+        };
+        prefix.ok_or_else(|| PathuxError::InvalidComponent(text.to_string()))
+    }
+}
+
 impl<'a> From<Prefix<'a>> for StrPathPrefix {
     fn from(prefix: Prefix) -> Self {
         match prefix {
@@ -335,7 +808,7 @@ impl<'a> From<Prefix<'a>> for StrPathPrefix {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum StrPathComponent {
     Prefix(StrPathPrefix),
     RootDir,
@@ -345,15 +818,39 @@ pub enum StrPathComponent {
     Normal(String),
 }
 
-impl ToString for StrPathComponent {
-    fn to_string(&self) -> String {
+impl fmt::Display for StrPathComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            StrPathComponent::Prefix(stp) => stp.to_string(),
-            StrPathComponent::RootDir => MAIN_SEPARATOR.to_string(),
-            StrPathComponent::HomeDir => "~".to_string(),
-            StrPathComponent::CurDir => ".".to_string(),
-            StrPathComponent::ParentDir => "..".to_string(),
-            StrPathComponent::Normal(string) => string.clone(),
+            StrPathComponent::Prefix(stp) => write!(f, "{}", stp),
+            StrPathComponent::RootDir => write!(f, "{}", MAIN_SEPARATOR),
+            StrPathComponent::HomeDir => f.write_str("~"),
+            StrPathComponent::CurDir => f.write_str("."),
+            StrPathComponent::ParentDir => f.write_str(".."),
+            StrPathComponent::Normal(string) => f.write_str(string),
+        }
+    }
+}
+
+/// Parse the textual form of a single component (i.e. the inverse of
+/// `Display`) where either separator is accepted for the root directory.
+impl FromStr for StrPathComponent {
+    type Err = PathuxError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "/" | "\\" => Ok(StrPathComponent::RootDir),
+            "~" => Ok(StrPathComponent::HomeDir),
+            "." => Ok(StrPathComponent::CurDir),
+            ".." => Ok(StrPathComponent::ParentDir),
+            _ => {
+                if let Ok(prefix) = text.parse() {
+                    Ok(StrPathComponent::Prefix(prefix))
+                } else if text.is_empty() || text.contains(['\\', '/']) {
+                    Err(PathuxError::InvalidComponent(text.to_string()))
+                } else {
+                    Ok(StrPathComponent::Normal(text.to_string()))
+                }
+            }
         }
     }
 }
@@ -395,6 +892,141 @@ impl StrPathComponent {
     }
 }
 
+/// A borrowed (allocation free) equivalent of `StrPathComponent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrPathComponentRef<'a> {
+    Prefix(&'a str),
+    RootDir,
+    HomeDir,
+    CurDir,
+    ParentDir,
+    Normal(&'a str),
+}
+
+impl<'a> TryFrom<Component<'a>> for StrPathComponentRef<'a> {
+    type Error = PathuxError;
+
+    /// Fails for components that aren't valid UTF-8 (which can't happen
+    /// for paths made from `str`s).
+    fn try_from(component: Component<'a>) -> Result<Self, Self::Error> {
+        let text = || {
+            component.as_os_str().to_str().ok_or_else(|| {
+                PathuxError::InvalidComponent(component.as_os_str().to_string_lossy().into_owned())
+            })
+        };
+        match component {
+            Component::Prefix(_) => Ok(StrPathComponentRef::Prefix(text()?)),
+            Component::RootDir => Ok(StrPathComponentRef::RootDir),
+            Component::CurDir => Ok(StrPathComponentRef::CurDir),
+            Component::ParentDir => Ok(StrPathComponentRef::ParentDir),
+            Component::Normal(_) => Ok(StrPathComponentRef::Normal(text()?)),
+        }
+    }
+}
+
+impl<'a> From<StrPathComponentRef<'a>> for StrPathComponent {
+    fn from(component: StrPathComponentRef<'a>) -> Self {
+        match component {
+            StrPathComponentRef::Prefix(text) => match Path::new(text).components().next() {
+                Some(Component::Prefix(prefix)) => StrPathComponent::Prefix(prefix.kind().into()),
+                _ => StrPathComponent::Normal(text.to_string()),
+            },
+            StrPathComponentRef::RootDir => StrPathComponent::RootDir,
+            StrPathComponentRef::HomeDir => StrPathComponent::HomeDir,
+            StrPathComponentRef::CurDir => StrPathComponent::CurDir,
+            StrPathComponentRef::ParentDir => StrPathComponent::ParentDir,
+            StrPathComponentRef::Normal(text) => StrPathComponent::Normal(text.to_string()),
+        }
+    }
+}
+
+impl<'a> StrPathComponentRef<'a> {
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            StrPathComponentRef::Prefix(text) => text,
+            StrPathComponentRef::RootDir => path::MAIN_SEPARATOR_STR,
+            StrPathComponentRef::HomeDir => "~",
+            StrPathComponentRef::CurDir => ".",
+            StrPathComponentRef::ParentDir => "..",
+            StrPathComponentRef::Normal(text) => text,
+        }
+    }
+
+    pub fn is_cur_dir(&self) -> bool {
+        *self == StrPathComponentRef::CurDir
+    }
+
+    pub fn is_home_dir(&self) -> bool {
+        *self == StrPathComponentRef::HomeDir
+    }
+
+    pub fn is_normal(&self) -> bool {
+        matches!(self, StrPathComponentRef::Normal(_))
+    }
+
+    // Prefixes, the root directory and "." don't add to a path's depth.
+    fn counts_for_depth(&self) -> bool {
+        !matches!(
+            self,
+            StrPathComponentRef::Prefix(_)
+                | StrPathComponentRef::RootDir
+                | StrPathComponentRef::CurDir
+        )
+    }
+}
+
+/// An iterator over the components of a `str` path (the equivalent of
+/// `std::path::Components`) which yields `StrPathComponentRef`s.
+#[derive(Debug, Clone)]
+pub struct StrPathComponents<'a> {
+    components: std::path::Components<'a>,
+    at_start: bool,
+}
+
+impl<'a> StrPathComponents<'a> {
+    pub fn new(path: &'a str) -> Self {
+        StrPathComponents {
+            components: Path::new(path).components(),
+            at_start: true,
+        }
+    }
+
+    /// The path made up of the components that haven't been iterated over.
+    pub fn as_path_string(&self) -> &'a str {
+        // this is a (normalized) slice of the original str
+        self.components.as_path().to_str().unwrap_or_default()
+    }
+
+    // A leading "~" is the home directory.
+    fn convert(&self, component: Component<'a>, is_first: bool) -> StrPathComponentRef<'a> {
+        if is_first && component == Component::Normal(OsStr::new("~")) {
+            StrPathComponentRef::HomeDir
+        } else {
+            // the components of a str are always valid UTF-8
+            StrPathComponentRef::try_from(component).expect("str component is valid UTF-8")
+        }
+    }
+}
+
+impl<'a> Iterator for StrPathComponents<'a> {
+    type Item = StrPathComponentRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let component = self.components.next()?;
+        let is_first = self.at_start;
+        self.at_start = false;
+        Some(self.convert(component, is_first))
+    }
+}
+
+impl<'a> DoubleEndedIterator for StrPathComponents<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let component = self.components.next_back()?;
+        let is_first = self.at_start && self.components.clone().next().is_none();
+        Some(self.convert(component, is_first))
+    }
+}
+
 pub trait ToStringPath {
     fn to_string_path(&self) -> String;
 }
@@ -425,6 +1057,7 @@ impl ToStringPath for [StrPathComponent] {
 mod tests {
     use super::*;
 
+    // the macros are deliberately given `&String`s as well as `&str`s
     #[cfg(target_family = "unix")]
     #[test]
     fn str_path_macros_work() {
@@ -487,35 +1120,151 @@ mod tests {
         assert!(str_path_is_relative!("SRC"));
         assert!(str_path_is_relative_to_home!("~/SRC"));
 
+        // like `Path::new()` the macros accept other path types
+        let path_buf = PathBuf::from("/home/peter");
+        assert_eq!(str_path_file_name!(&path_buf), Some("peter".to_string()));
         assert_eq!(
-            str_path_absolute!("./SRC").unwrap(),
-            "/home/peter/SRC/GITHUB/rs_gwsm_git.git/pw_pathux/SRC".to_string()
-        );
-        assert_eq!(
-            str_path_absolute!("/home/peter/SRC").unwrap(),
-            "/home/peter/SRC".to_string()
+            str_path_parent!(path_buf.as_path()),
+            Some("/home".to_string())
         );
         assert_eq!(
-            str_path_absolute!("~/SRC").unwrap(),
-            "/home/peter/SRC".to_string()
+            str_path_file_name!(OsStr::new("a/b")),
+            Some("b".to_string())
         );
-
+        assert!(str_path_is_absolute!(&path_buf));
+        assert!(str_path_is_relative!(OsStr::new("SRC")));
+        assert!(str_path_is_relative_to_home!(Path::new("~/SRC")));
         assert_eq!(
-            str_path_simple_relative!("./SRC").unwrap(),
-            "SRC".to_string()
+            str_path_join!(&path_buf, Path::new("SRC")),
+            "/home/peter/SRC"
         );
+        assert_eq!(str_path_absolute!(&path_buf).unwrap(), "/home/peter");
+        let components: Vec<StrPathComponent> = str_path_components!(&path_buf).collect();
+        assert_eq!(components.len(), 3);
         assert_eq!(
-            str_path_simple_relative!("/home/peter/SRC/GITHUB/rs_gwsm_git.git/pw_pathux/SRC")
-                .unwrap(),
-            "SRC".to_string()
+            str_path_components!(Path::new("~/x")).next(),
+            Some(StrPathComponent::HomeDir)
         );
 
+        // don't depend on the real home and current directories
+        let home = "/home/peter";
+        let cwd = "/home/peter/SRC/GITHUB/rs_gwsm_git.git/pw_pathux";
+        Context::with_home(&home, || {
+            Context::with_cwd(&cwd, || {
+                assert_eq!(
+                    str_path_absolute!("./SRC").unwrap(),
+                    "/home/peter/SRC/GITHUB/rs_gwsm_git.git/pw_pathux/SRC".to_string()
+                );
+                assert_eq!(
+                    str_path_absolute!("/home/peter/SRC").unwrap(),
+                    "/home/peter/SRC".to_string()
+                );
+                assert_eq!(
+                    str_path_absolute!("~/SRC").unwrap(),
+                    "/home/peter/SRC".to_string()
+                );
+
+                assert_eq!(
+                    str_path_simple_relative!("./SRC").unwrap(),
+                    "SRC".to_string()
+                );
+                assert_eq!(
+                    str_path_simple_relative!(
+                        "/home/peter/SRC/GITHUB/rs_gwsm_git.git/pw_pathux/SRC"
+                    )
+                    .unwrap(),
+                    "SRC".to_string()
+                );
+            })
+        });
+
         assert_eq!(
             str_path_join!("/home/peter/SRC", "GITHUB/rs_gwsm_git.git/pw_pathux/SRC"),
             "/home/peter/SRC/GITHUB/rs_gwsm_git.git/pw_pathux/SRC".to_string()
         )
     }
 
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn str_path_in_ctx_macros_work() {
+        use context::PathCtx;
+
+        let ctx = PathCtx::new(&"/work/dir", Some(Path::new("/home/me")));
+        assert_eq!(
+            str_path_absolute_in_ctx!("./SRC", &ctx).unwrap(),
+            "/work/dir/SRC"
+        );
+        assert_eq!(str_path_absolute_in_ctx!("/tmp", &ctx).unwrap(), "/tmp");
+        assert_eq!(
+            str_path_absolute_in_ctx!("~/SRC", &ctx).unwrap(),
+            "/home/me/SRC"
+        );
+        assert_eq!(
+            str_path_simple_relative_in_ctx!("/work/dir/a/b", &ctx).unwrap(),
+            "a/b"
+        );
+        assert!(str_path_simple_relative_in_ctx!("/tmp", &ctx).is_err());
+        let ctx = PathCtx::new(&"/work/dir", None);
+        assert!(str_path_absolute_in_ctx!("~/SRC", &ctx).is_err());
+    }
+
+    #[test]
+    fn current_dir_rel_home_checked_works() {
+        let cwd = env::current_dir().unwrap();
+        Context::with_home(&cwd.parent().unwrap(), || {
+            let expected = Path::new("~").join(cwd.file_name().unwrap());
+            assert_eq!(
+                current_dir_rel_home_checked().unwrap(),
+                expected.to_string_lossy()
+            );
+        });
+        Context::with_home(&cwd.join("pathux-nowhere"), || {
+            assert!(matches!(
+                current_dir_rel_home_checked(),
+                Err(PathuxError::NotUnderBase(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn read_and_write_macros_work() {
+        extern crate tempfile;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        Context::with_home(&temp_dir.path(), || {
+            assert!(str_path_write!("~/a/b.txt", "text").is_err());
+            str_path_write!("~/a/b.txt", "text", true).unwrap();
+            assert_eq!(str_path_read_to_string!("~/a/b.txt").unwrap(), "text");
+            str_path_write!("~/a/b.txt", b"bytes").unwrap();
+            assert_eq!(str_path_read_to_string!("~/a/b.txt").unwrap(), "bytes");
+            assert!(matches!(
+                str_path_read_to_string!("~/none"),
+                Err(PathuxError::Io(_))
+            ));
+        });
+        let path = temp_dir.path().join("a/b.txt");
+        assert_eq!(read_to_string(&path.to_string_lossy()).unwrap(), "bytes");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn str_path_with_macros_work() {
+        let opts = ExpandOptions::new().home(&"/home/other").cwd(&"/srv");
+        assert_eq!(
+            str_path_absolute_with!("~/.config", &opts).unwrap(),
+            "/home/other/.config"
+        );
+        assert_eq!(str_path_absolute_with!("./www", &opts).unwrap(), "/srv/www");
+        assert_eq!(
+            str_path_simple_relative_with!("/srv/www/x", &opts).unwrap(),
+            "www/x"
+        );
+        let opts = ExpandOptions::new()
+            .cwd(&"/srv")
+            .env(std::collections::HashMap::new());
+        assert!(str_path_absolute_with!("~/.config", &opts).is_err());
+    }
+
     #[cfg(target_family = "unix")]
     #[test]
     fn string_path_works() {
@@ -534,6 +1283,48 @@ mod tests {
         assert!("/home".to_string().path_is_absolute());
     }
 
+    #[test]
+    fn functions_mirror_macros() {
+        assert_eq!(file_name("a/b.rs"), str_path_file_name!("a/b.rs"));
+        assert_eq!(parent("a/b.rs"), Some("a".to_string()));
+        assert_eq!(join("a", "b"), str_path_join!("a", "b"));
+        assert!(is_relative_to_home("~/x") && !is_relative("~/x") && !is_absolute("~/x"));
+        assert!(is_relative("x"));
+        let home = env::temp_dir().join("someone");
+        let ctx = PathCtx::new(&env::temp_dir(), Some(&home));
+        assert_eq!(
+            absolute_in_ctx("~/x", &ctx).unwrap(),
+            home.join("x").to_string_lossy()
+        );
+        assert_eq!(simple_relative_in_ctx("./y", &ctx).unwrap(), "y");
+        assert_eq!(
+            Some("b.rs".to_string())
+                .as_deref()
+                .map(|name| with_file_name("a/c", name)),
+            Some(join("a", "b.rs"))
+        );
+    }
+
+    #[test]
+    fn str_path_works_for_string_types() {
+        use std::borrow::Cow;
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        let expected = Some("c".to_string());
+        assert_eq!(String::from("a/b/c").path_file_name(), expected);
+        assert_eq!(Cow::Borrowed("a/b/c").path_file_name(), expected);
+        assert_eq!(Box::<str>::from("a/b/c").path_file_name(), expected);
+        assert_eq!(Rc::<str>::from("a/b/c").path_file_name(), expected);
+        let path = Arc::<str>::from("a/b/c");
+        assert_eq!(path.path_file_name(), expected);
+        assert_eq!(path.path_depth(), 3);
+        assert_eq!(
+            path.path_component(2),
+            Some(StrPathComponentRef::Normal("c"))
+        );
+    }
+
     #[test]
     fn str_path_components_work() {
         assert_eq!(
@@ -550,8 +1341,275 @@ mod tests {
         assert_eq!(components[1..].to_string_path(), "peter/SRC".to_string());
     }
 
+    #[test]
+    fn str_path_component_refs_work() {
+        let components: Vec<_> = "~/peter/../SRC".path_component_refs().collect();
+        assert_eq!(
+            components,
+            vec![
+                StrPathComponentRef::HomeDir,
+                StrPathComponentRef::Normal("peter"),
+                StrPathComponentRef::ParentDir,
+                StrPathComponentRef::Normal("SRC"),
+            ]
+        );
+        assert!(components[0].is_home_dir());
+        assert_eq!(components[3].as_str(), "SRC");
+        let owned: Vec<StrPathComponent> = str_path_component_refs!("./a")
+            .map(StrPathComponent::from)
+            .collect();
+        assert_eq!(owned, str_path_components!("./a").collect::<Vec<_>>());
+        assert_eq!(
+            StrPathComponentRef::RootDir.as_str(),
+            MAIN_SEPARATOR.to_string()
+        );
+    }
+
+    #[test]
+    fn str_path_components_iterator_works() {
+        let mut components = "~/a/b/c".path_component_refs();
+        assert_eq!(components.next(), Some(StrPathComponentRef::HomeDir));
+        assert_eq!(
+            components.as_path_string(),
+            Path::new("a/b/c").to_str().unwrap()
+        );
+        let rest = components.clone();
+        assert_eq!(
+            components.next_back(),
+            Some(StrPathComponentRef::Normal("c"))
+        );
+        assert_eq!(components.next(), Some(StrPathComponentRef::Normal("a")));
+        assert_eq!(components.as_path_string(), "b");
+        assert_eq!(rest.count(), 3);
+        let reversed: Vec<_> = "~/a".path_component_refs().rev().collect();
+        assert_eq!(
+            reversed,
+            vec![
+                StrPathComponentRef::Normal("a"),
+                StrPathComponentRef::HomeDir
+            ]
+        );
+        let mut components = "a/~".path_component_refs();
+        assert_eq!(
+            components.next_back(),
+            Some(StrPathComponentRef::Normal("~"))
+        );
+    }
+
+    #[test]
+    fn reverse_operations_work() {
+        let reversed: Vec<&str> = "a/b/c".path_components_rev().map(|c| c.as_str()).collect();
+        assert_eq!(reversed, vec!["c", "b", "a"]);
+        assert_eq!(
+            str_path_rsplit!("a/b//c/"),
+            Some((Path::new("a/b").to_string_path(), "c".to_string()))
+        );
+        assert_eq!(
+            str_path_rsplit!("a/.."),
+            Some(("a".to_string(), "..".to_string()))
+        );
+        assert_eq!(
+            str_path_rsplit!("c"),
+            Some(("".to_string(), "c".to_string()))
+        );
+        assert_eq!(str_path_rsplit!(""), None);
+        assert!("a/b/c.txt".path_ends_with("b/c.txt"));
+        assert!("a/b/c.txt/".path_ends_with("c.txt"));
+        assert!(!"a/bb/c.txt".path_ends_with("b/c.txt"));
+    }
+
+    #[test]
+    fn strip_prefix_works() {
+        assert_eq!(
+            str_path_strip_prefix!("~/SRC/x", "~/SRC"),
+            Some("x".to_string())
+        );
+        assert_eq!("~/SRC/x".path_strip_prefix("~/"), Some("SRC/x".to_string()));
+        assert_eq!("a/b".path_strip_prefix("a/b/"), Some("".to_string()));
+        assert_eq!("a/./b/c".path_strip_prefix("a"), Some("b/c".to_string()));
+        assert_eq!("a/bc".path_strip_prefix("a/b"), None);
+        assert_eq!("~/SRC".path_strip_prefix("SRC"), None);
+        assert_eq!("a".path_strip_prefix("a/b"), None);
+    }
+
+    #[test]
+    fn depth_and_indexing_work() {
+        assert_eq!("a/b/c".path_depth(), 3);
+        assert_eq!("./a//b/".path_depth(), 2);
+        assert_eq!("~/a".path_depth(), 2);
+        assert_eq!("".path_depth(), 0);
+        assert_eq!(
+            "a/b/c".path_component(1),
+            Some(StrPathComponentRef::Normal("b"))
+        );
+        assert_eq!("~/a".path_component(0), Some(StrPathComponentRef::HomeDir));
+        assert_eq!("a".path_component(1), None);
+        assert_eq!(
+            "a/b/c".path_truncate_to_depth(2),
+            Path::new("a/b").to_string_path()
+        );
+        assert_eq!(
+            "a/b".path_truncate_to_depth(5),
+            Path::new("a/b").to_string_path()
+        );
+        assert_eq!("a/b".path_truncate_to_depth(0), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn depth_of_absolute_paths_works() {
+        assert_eq!("/".path_depth(), 0);
+        assert_eq!("/a/b".path_depth(), 2);
+        assert_eq!("/a/b".path_truncate_to_depth(1), "/a");
+        assert_eq!("/a/b".path_truncate_to_depth(0), "/");
+    }
+
+    #[test]
+    fn with_file_name_and_sibling_work() {
+        let expected = Path::new("a/b").join("x.txt").to_string_path();
+        assert_eq!(str_path_with_file_name!("a/b/c.rs", "x.txt"), expected);
+        assert_eq!(str_path_with_file_name!("a/b/c/", "x.txt"), expected);
+        assert_eq!("a/b/c.rs".path_sibling("x.txt"), Some(expected));
+        assert_eq!("c.rs".path_sibling("x.txt"), Some("x.txt".to_string()));
+        assert_eq!("".path_sibling("x.txt"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rsplit_root_works() {
+        assert_eq!(
+            str_path_rsplit!("/a"),
+            Some(("/".to_string(), "a".to_string()))
+        );
+        assert_eq!(str_path_rsplit!("/"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn component_refs_reject_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"a/b\xff"));
+        let mut components = path.components();
+        assert_eq!(
+            StrPathComponentRef::try_from(components.next().unwrap()).ok(),
+            Some(StrPathComponentRef::Normal("a"))
+        );
+        assert!(StrPathComponentRef::try_from(components.next().unwrap()).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn str_path_component_ref_prefixes_work() {
+        let components: Vec<_> = r"C:\x".path_component_refs().collect();
+        assert_eq!(components[0], StrPathComponentRef::Prefix("C:"));
+        assert_eq!(
+            StrPathComponent::from(components[0]),
+            StrPathComponent::Prefix(StrPathPrefix::Disk(b'C'))
+        );
+    }
+
+    #[test]
+    fn contract_home_works() {
+        let home = env::temp_dir().join("someone");
+        let home_str = home.to_string_lossy().into_owned();
+        Context::with_home(&home, || {
+            let expected = Path::new("~").join("src").join("lib.rs");
+            let path = home.join("src").join("lib.rs");
+            assert_eq!(
+                path.to_string_lossy().path_contract_home(),
+                expected.to_string_lossy()
+            );
+            assert_eq!(contract_home(&home_str), "~");
+            let other = format!("{}else", home_str);
+            assert_eq!(contract_home(&other), other);
+            assert_eq!(contract_home("relative/path"), "relative/path");
+        });
+    }
+
+    #[test]
+    fn str_path_prefix_to_string_works() {
+        assert_eq!(StrPathPrefix::Disk(b'C').to_string(), "C:");
+        assert_eq!(StrPathPrefix::VerbatimDisk(b'C').to_string(), r"\\?\C:");
+        assert_eq!(
+            StrPathPrefix::VerbatimUNC("server".to_string(), "share".to_string()).to_string(),
+            r"\\?\UNC\server\share"
+        );
+    }
+
+    #[test]
+    fn components_parse_and_display() {
+        let texts = [
+            "C:",
+            r"\\server\share",
+            r"\\?\C:",
+            r"\\?\UNC\server\share",
+            r"\\?\Volume{1234}",
+            r"\\.\COM1",
+        ];
+        for text in texts.iter() {
+            let prefix: StrPathPrefix = text.parse().unwrap();
+            assert_eq!(prefix.to_string(), *text);
+            let component: StrPathComponent = text.parse().unwrap();
+            assert_eq!(component, StrPathComponent::Prefix(prefix));
+        }
+        assert_eq!(
+            "c:".parse::<StrPathPrefix>().unwrap(),
+            StrPathPrefix::Disk(b'C')
+        );
+        assert_eq!(
+            "//server/share".parse::<StrPathPrefix>().unwrap(),
+            StrPathPrefix::UNC("server".to_string(), "share".to_string())
+        );
+        for text in ["", "C", r"\\server", r"\\?\", "CC:"].iter() {
+            assert!(text.parse::<StrPathPrefix>().is_err());
+        }
+
+        for text in [path::MAIN_SEPARATOR_STR, "~", ".", "..", "name.txt"].iter() {
+            let component: StrPathComponent = text.parse().unwrap();
+            assert_eq!(component.to_string(), *text);
+        }
+        assert!("a/b".parse::<StrPathComponent>().is_err());
+        assert!("".parse::<StrPathComponent>().is_err());
+
+        let mut counts = std::collections::BTreeMap::new();
+        for component in "a/b/../a".path_components() {
+            *counts.entry(component).or_insert(0) += 1;
+        }
+        assert_eq!(counts[&StrPathComponent::Normal("a".to_string())], 2);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn extended_length_paths_round_trip() {
+        for path in &[r"\\?\C:\a\b", r"\\?\UNC\server\share\a", r"C:\a"] {
+            assert_eq!(path.path_components().to_string_path(), *path);
+        }
+    }
+
     #[test]
     fn stripped_of_n_levels_works() {
         assert_eq!("a/b/c".path_stripped_of_n_levels(1), "b/c".to_string());
     }
+
+    #[test]
+    fn path_is_dir_and_file_work() {
+        use fs_provider::MemFs;
+
+        assert!("src".path_is_dir());
+        assert!(!"src".path_is_file());
+        assert!("Cargo.toml".path_is_file());
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/a/b")).unwrap();
+        fs.write(Path::new("/a/file"), b"").unwrap();
+        fs.symlink(Path::new("b"), Path::new("/a/link")).unwrap();
+        assert!("/a/b".path_is_dir_with(&fs));
+        assert!(!"/a/b".path_is_file_with(&fs));
+        assert!("/a/file".path_is_file_with(&fs));
+        assert!(!"/a/file".path_is_dir_with(&fs));
+        // symbolic links are followed
+        assert!("/a/link".path_is_dir_with(&fs));
+        assert!(!"/a/missing".path_is_dir_with(&fs));
+        assert!(!"/a/missing".path_is_file_with(&fs));
+    }
 }
@@ -0,0 +1,373 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide creation and management of symbolic links (and
+//! Windows junctions) that hides the differences between the unix and
+//! Windows APIs.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+#[derive(Debug, Clone, Default)]
+pub struct SymlinkOptions {
+    /// On Windows, create a junction if a symbolic link to a directory
+    /// can't be created (e.g. because the user lacks the privilege).
+    pub junction_fallback: bool,
+}
+
+/// Create a symbolic link `link` pointing at `target`.  On Windows the
+/// kind of link (file or directory) is chosen according to what `target`
+/// (interpreted relative to the link's directory) currently is.
+pub fn make_symlink<T: AsRef<Path>, L: AsRef<Path>>(target: &T, link: &L) -> io::Result<()> {
+    make_symlink_with(target, link, &SymlinkOptions::default())
+}
+
+/// Like `make_symlink()` but as specified by `options`.
+pub fn make_symlink_with<T: AsRef<Path>, L: AsRef<Path>>(
+    target: &T,
+    link: &L,
+    options: &SymlinkOptions,
+) -> io::Result<()> {
+    imp::make_symlink(target.as_ref(), link.as_ref(), options)
+}
+
+/// Make `link` a Windows junction (directory mount point) for the
+/// directory `target`.  Unlike symbolic links, junctions can be created
+/// without special privileges.  Not supported on other platforms.
+pub fn make_junction<T: AsRef<Path>, L: AsRef<Path>>(target: &T, link: &L) -> io::Result<()> {
+    imp::make_junction(target.as_ref(), link.as_ref())
+}
+
+/// Return `true` if `path` is a Windows junction (always `false` on other
+/// platforms).  Note that the standard library (and hence `Walker`)
+/// treats junctions as symbolic links.
+pub fn is_junction<P: AsRef<Path>>(path: &P) -> io::Result<bool> {
+    imp::is_junction(path.as_ref())
+}
+
+/// Remove the symbolic link (or junction) `link` whether it points at a
+/// file or a directory (Windows treats the two differently).
+pub fn remove_link<P: AsRef<Path>>(link: &P) -> io::Result<()> {
+    imp::remove_link(link.as_ref())
+}
+
+/// Return the target of the symbolic link `path` as a `String`.
+pub fn read_link_str<P: AsRef<Path>>(path: &P) -> io::Result<String> {
+    let target = fs::read_link(path)?;
+    match target.to_str() {
+        Some(text) => Ok(text.to_string()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: link target is not valid UTF-8", target.display()),
+        )),
+    }
+}
+
+/// Make `link` a symbolic link pointing at `target` replacing any existing
+/// link of that name.  The new link is created alongside and renamed into
+/// place so (on unix) there is no moment when `link` doesn't exist.  Fails
+/// if `link` exists but isn't a symbolic link.
+pub fn relink<T: AsRef<Path>, L: AsRef<Path>>(target: &T, link: &L) -> io::Result<()> {
+    let link: &Path = link.as_ref();
+    match fs::symlink_metadata(link) {
+        Ok(ref metadata) if !metadata.file_type().is_symlink() => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{}: exists and is not a symbolic link", link.display()),
+            ));
+        }
+        Ok(_) => (),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return make_symlink(target, &link)
+        }
+        Err(err) => return Err(err),
+    }
+    let name = link.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: no file name", link.display()),
+        )
+    })?;
+    let mut n = 0;
+    let temp_link = loop {
+        let mut temp_name = name.to_os_string();
+        temp_name.push(format!(".relink-{}-{}", process::id(), n));
+        let temp_link = link.with_file_name(temp_name);
+        match make_symlink(target, &temp_link) {
+            Ok(()) => break temp_link,
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(err),
+        }
+    };
+    imp::replace_link(&temp_link, link).inspect_err(|_| {
+        let _ = imp::remove_link(&temp_link);
+    })
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    use super::SymlinkOptions;
+
+    pub fn make_symlink(target: &Path, link: &Path, _options: &SymlinkOptions) -> io::Result<()> {
+        symlink(target, link)
+    }
+
+    pub fn replace_link(new_link: &Path, link: &Path) -> io::Result<()> {
+        fs::rename(new_link, link)
+    }
+
+    pub fn make_junction(_target: &Path, _link: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "junctions are only supported on Windows",
+        ))
+    }
+
+    pub fn is_junction(path: &Path) -> io::Result<bool> {
+        fs::symlink_metadata(path).map(|_| false)
+    }
+
+    pub fn remove_link(link: &Path) -> io::Result<()> {
+        fs::remove_file(link)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::fs::{self, OpenOptions};
+    use std::io;
+    use std::mem;
+    use std::os::windows::fs::{
+        symlink_dir, symlink_file, FileTypeExt, MetadataExt, OpenOptionsExt,
+    };
+    use std::os::windows::io::AsRawHandle;
+    use std::path::Path;
+    use std::ptr;
+
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::winerror::ERROR_PRIVILEGE_NOT_HELD;
+    use winapi::um::fileapi::{FindClose, FindFirstFileW};
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::ioapiset::DeviceIoControl;
+    use winapi::um::minwinbase::WIN32_FIND_DATAW;
+    use winapi::um::winbase::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT};
+    use winapi::um::winioctl::FSCTL_SET_REPARSE_POINT;
+    use winapi::um::winnt::{FILE_ATTRIBUTE_REPARSE_POINT, IO_REPARSE_TAG_MOUNT_POINT};
+
+    use super::SymlinkOptions;
+    use sys::wide_path;
+
+    fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn make_junction(target: &Path, link: &Path) -> io::Result<()> {
+        let target = target.canonicalize()?;
+        let target = target.to_string_lossy();
+        let target = target.trim_start_matches(r"\\?\");
+        let substitute: Vec<u16> = format!(r"\??\{}", target).encode_utf16().collect();
+        let print: Vec<u16> = target.encode_utf16().collect();
+        let substitute_len = (substitute.len() * 2) as u16;
+        let print_len = (print.len() * 2) as u16;
+        // the names (each with a nul terminator) follow four u16 fields
+        let data_len = 8 + substitute_len + 2 + print_len + 2;
+        let mut buffer = Vec::with_capacity(8 + data_len as usize);
+        buffer.extend_from_slice(&IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+        push_u16(&mut buffer, data_len);
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, substitute_len);
+        push_u16(&mut buffer, substitute_len + 2);
+        push_u16(&mut buffer, print_len);
+        for unit in substitute.iter().chain(&[0]).chain(&print).chain(&[0]) {
+            push_u16(&mut buffer, *unit);
+        }
+        fs::create_dir(link)?;
+        let result = OpenOptions::new()
+            .write(true)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+            .open(link)
+            .and_then(|dir| {
+                let mut returned: DWORD = 0;
+                let ok = unsafe {
+                    DeviceIoControl(
+                        dir.as_raw_handle() as _,
+                        FSCTL_SET_REPARSE_POINT,
+                        buffer.as_mut_ptr() as _,
+                        buffer.len() as DWORD,
+                        ptr::null_mut(),
+                        0,
+                        &mut returned,
+                        ptr::null_mut(),
+                    )
+                };
+                if ok == 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            });
+        if result.is_err() {
+            let _ = fs::remove_dir(link);
+        }
+        result
+    }
+
+    pub fn make_symlink(target: &Path, link: &Path, options: &SymlinkOptions) -> io::Result<()> {
+        let target_path = match link.parent() {
+            Some(dir_path) => dir_path.join(target),
+            None => target.to_path_buf(),
+        };
+        if !target_path.is_dir() {
+            return symlink_file(target, link);
+        }
+        match symlink_dir(target, link) {
+            Err(ref err)
+                if options.junction_fallback
+                    && err.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD as i32) =>
+            {
+                make_junction(&target_path, link)
+            }
+            result => result,
+        }
+    }
+
+    // Windows won't rename over a directory (link) so the old link has to
+    // be removed first.
+    pub fn replace_link(new_link: &Path, link: &Path) -> io::Result<()> {
+        if fs::rename(new_link, link).is_err() {
+            remove_link(link)?;
+            fs::rename(new_link, link)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_link(link: &Path) -> io::Result<()> {
+        // junctions count as directory links
+        if fs::symlink_metadata(link)?.file_type().is_symlink_dir() {
+            fs::remove_dir(link)
+        } else {
+            fs::remove_file(link)
+        }
+    }
+
+    pub fn is_junction(path: &Path) -> io::Result<bool> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT == 0 {
+            return Ok(false);
+        }
+        // the reparse point's tag is only available from a directory search
+        let wide = wide_path(path);
+        let mut find_data: WIN32_FIND_DATAW = unsafe { mem::zeroed() };
+        let handle = unsafe { FindFirstFileW(wide.as_ptr(), &mut find_data) };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        unsafe { FindClose(handle) };
+        Ok(find_data.dwReserved0 == IO_REPARSE_TAG_MOUNT_POINT)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    use super::SymlinkOptions;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "symbolic links are not supported on this platform",
+        )
+    }
+
+    pub fn make_symlink(_target: &Path, _link: &Path, _options: &SymlinkOptions) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn replace_link(_new_link: &Path, _link: &Path) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn remove_link(link: &Path) -> io::Result<()> {
+        ::std::fs::remove_file(link)
+    }
+
+    pub fn make_junction(_target: &Path, _link: &Path) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn is_junction(path: &Path) -> io::Result<bool> {
+        ::std::fs::symlink_metadata(path).map(|_| false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(unix, windows))]
+    use super::*;
+
+    extern crate tempfile;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_helpers_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("file"), "text").unwrap();
+        make_symlink(&"file", &root.join("link")).unwrap();
+        assert_eq!(read_link_str(&root.join("link")).unwrap(), "file");
+        assert_eq!(fs::read_to_string(root.join("link")).unwrap(), "text");
+        assert!(make_symlink(&"dir", &root.join("link")).is_err());
+        relink(&"dir", &root.join("link")).unwrap();
+        assert_eq!(read_link_str(&root.join("link")).unwrap(), "dir");
+        assert!(root.join("link").is_dir());
+        relink(&"file", &root.join("new")).unwrap();
+        assert_eq!(read_link_str(&root.join("new")).unwrap(), "file");
+        assert!(relink(&"dir", &root.join("file")).is_err());
+        assert_eq!(fs::read_dir(root).unwrap().count(), 4);
+        assert!(read_link_str(&root.join("file")).is_err());
+        assert!(!is_junction(&root.join("link")).unwrap());
+        assert!(make_junction(&root.join("dir"), &root.join("junction")).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn junctions_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("dir/file"), "text").unwrap();
+        make_junction(&root.join("dir"), &root.join("junction")).unwrap();
+        assert!(is_junction(&root.join("junction")).unwrap());
+        assert!(!is_junction(&root.join("dir")).unwrap());
+        assert_eq!(
+            fs::read_to_string(root.join("junction/file")).unwrap(),
+            "text"
+        );
+        assert!(fs::symlink_metadata(root.join("junction"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+}
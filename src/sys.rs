@@ -0,0 +1,59 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Private helpers shared by the modules that talk to the OS directly.
+
+#[cfg(any(unix, windows))]
+use std::path::Path;
+
+#[cfg(unix)]
+use std::ffi::CString;
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+
+#[cfg(windows)]
+use long_path::long_path_safe;
+#[cfg(windows)]
+use std::iter;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+
+#[cfg(unix)]
+pub fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: path contains a nul byte", path.display()),
+        )
+    })
+}
+
+// Paths that are too long for the Windows API are converted to extended
+// length form.
+#[cfg(windows)]
+pub fn wide_path(path: &Path) -> Vec<u16> {
+    long_path_safe(&path)
+        .as_os_str()
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect()
+}
+
+#[cfg(windows)]
+pub fn from_wide(wide: &[u16]) -> String {
+    let len = wide.iter().position(|c| *c == 0).unwrap_or(wide.len());
+    String::from_utf16_lossy(&wide[..len])
+}
@@ -0,0 +1,313 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide temporary files that have no name until (and
+//! unless) they are given one, so that a file's contents can be written in
+//! full before it appears in the file system.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use durable::{sync_parent_dir, Durability};
+
+/// A temporary file that is discarded when dropped unless `persist()` is
+/// called.  On Linux (where the file system supports `O_TMPFILE`) the file
+/// has no name at all, elsewhere it has a hidden name in the directory it
+/// was created in.
+#[derive(Debug)]
+pub struct AnonymousTempFile {
+    file: File,
+    // the name used in the absence of O_TMPFILE
+    name: Option<PathBuf>,
+}
+
+/// Create an anonymous temporary file (open for reading and writing) on
+/// the file system containing the directory `dir_path`.
+pub fn anonymous_temp_file<P: AsRef<Path>>(dir_path: &P) -> io::Result<AnonymousTempFile> {
+    let dir_path: &Path = dir_path.as_ref();
+    if let Some(file) = imp::open_unnamed(dir_path)? {
+        return Ok(AnonymousTempFile { file, name: None });
+    }
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        // as private as an unnamed file would be
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    for n in 0.. {
+        let name = dir_path.join(format!(".tmp-{}-{}", process::id(), n));
+        match options.open(&name) {
+            Ok(file) => {
+                return Ok(AnonymousTempFile {
+                    file,
+                    name: Some(name),
+                })
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!()
+}
+
+// A name for a temporary in the same directory as `path`.
+fn temp_name_for(path: &Path, n: usize) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp-{}-{}", file_name, process::id(), n))
+}
+
+/// Write `contents` to the file `path` in such a way that `path` contains
+/// either its old contents or all of `contents` (never a partial write)
+/// even if the process (or, with `Durability::Full`, the system) crashes.
+pub fn atomic_write<P, C>(path: &P, contents: C, durability: Durability) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let path: &Path = path.as_ref();
+    let dir_path = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp_file = anonymous_temp_file(&dir_path)?;
+    temp_file.write_all(contents.as_ref())?;
+    if durability == Durability::Full {
+        temp_file.sync_all()?;
+    }
+    temp_file.persist_overwrite(&path)?;
+    if durability == Durability::Full {
+        sync_parent_dir(&path)?;
+    }
+    Ok(())
+}
+
+impl AnonymousTempFile {
+    /// Give the file the name `path` (which must not already exist) and
+    /// return the (still open) file.
+    pub fn persist<P: AsRef<Path>>(mut self, path: &P) -> io::Result<File> {
+        let path: &Path = path.as_ref();
+        match self.name.take() {
+            None => imp::link_unnamed(&self.file, path)?,
+            Some(name) => {
+                let result = fs::hard_link(&name, path);
+                fs::remove_file(&name)?;
+                result?
+            }
+        }
+        self.file.try_clone()
+    }
+
+    /// Like `persist()` but (atomically) replace `path` if it exists.
+    pub fn persist_overwrite<P: AsRef<Path>>(mut self, path: &P) -> io::Result<File> {
+        let path: &Path = path.as_ref();
+        match self.name.take() {
+            None => {
+                // a name is needed to rename
+                let mut n = 0;
+                let temp_name = loop {
+                    let temp_name = temp_name_for(path, n);
+                    match imp::link_unnamed(&self.file, &temp_name) {
+                        Ok(()) => break temp_name,
+                        Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+                        Err(err) => return Err(err),
+                    }
+                };
+                fs::rename(&temp_name, path).inspect_err(|_| {
+                    let _ = fs::remove_file(&temp_name);
+                })?
+            }
+            Some(name) => fs::rename(&name, path).inspect_err(|_| {
+                let _ = fs::remove_file(&name);
+            })?,
+        }
+        self.file.try_clone()
+    }
+}
+
+impl Deref for AnonymousTempFile {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl DerefMut for AnonymousTempFile {
+    fn deref_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+}
+
+impl Drop for AnonymousTempFile {
+    fn drop(&mut self) {
+        if let Some(ref name) = self.name {
+            let _ = fs::remove_file(name);
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use std::ffi::CString;
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use libc;
+
+    use sys::c_path;
+
+    // Return `None` if the file system doesn't support O_TMPFILE.
+    pub fn open_unnamed(dir_path: &Path) -> io::Result<Option<File>> {
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .mode(0o600)
+            .custom_flags(libc::O_TMPFILE)
+            .open(dir_path)
+        {
+            Ok(file) => Ok(Some(file)),
+            Err(ref err)
+                if matches!(
+                    err.raw_os_error(),
+                    Some(libc::EOPNOTSUPP) | Some(libc::EISDIR) | Some(libc::EINVAL)
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn link_unnamed(file: &File, path: &Path) -> io::Result<()> {
+        let c_new_path = c_path(path)?;
+        let proc_path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd()))
+            .expect("no nul bytes in a number");
+        let result = unsafe {
+            libc::linkat(
+                libc::AT_FDCWD,
+                proc_path.as_ptr(),
+                libc::AT_FDCWD,
+                c_new_path.as_ptr(),
+                libc::AT_SYMLINK_FOLLOW,
+            )
+        };
+        if result == 0 {
+            return Ok(());
+        }
+        // without /proc this needs the CAP_DAC_READ_SEARCH capability
+        let empty = CString::default();
+        let result = unsafe {
+            libc::linkat(
+                file.as_raw_fd(),
+                empty.as_ptr(),
+                libc::AT_FDCWD,
+                c_new_path.as_ptr(),
+                libc::AT_EMPTY_PATH,
+            )
+        };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod imp {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub fn open_unnamed(_dir_path: &Path) -> io::Result<Option<File>> {
+        Ok(None)
+    }
+
+    pub fn link_unnamed(_file: &File, _path: &Path) -> io::Result<()> {
+        unreachable!("files are always named on this platform")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::io::{Read, Seek, SeekFrom};
+
+    #[test]
+    fn anonymous_temp_file_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let mut temp_file = anonymous_temp_file(&root).unwrap();
+        temp_file.write_all(b"contents").unwrap();
+        // only the fallback has a (hidden) name
+        assert!(fs::read_dir(root).unwrap().count() <= 1);
+        let mut file = temp_file.persist(&root.join("file")).unwrap();
+        assert_eq!(fs::read_to_string(root.join("file")).unwrap(), "contents");
+        let mut text = String::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "contents");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+
+        let temp_file = anonymous_temp_file(&root).unwrap();
+        assert!(temp_file.persist(&root.join("file")).is_err());
+        drop(anonymous_temp_file(&root).unwrap());
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+        assert!(anonymous_temp_file(&root.join("missing")).is_err());
+
+        let mut temp_file = anonymous_temp_file(&root).unwrap();
+        temp_file.write_all(b"replaced").unwrap();
+        temp_file.persist_overwrite(&root.join("file")).unwrap();
+        assert_eq!(fs::read_to_string(root.join("file")).unwrap(), "replaced");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn anonymous_temp_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        anonymous_temp_file(&temp_dir.path())
+            .unwrap()
+            .persist(&path)
+            .unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn atomic_write_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("state");
+        atomic_write(&path, "one", Durability::Relaxed).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one");
+        atomic_write(&path, b"two", Durability::Full).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        assert!(atomic_write(&temp_dir.path().join("a/b"), "x", Durability::Full).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide generation of paths from templates and the
+//! parsing of paths generated from them.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{self, Path};
+use std::time::SystemTime;
+
+use super::datetime::DateTime;
+use super::error::PathuxError;
+use super::split_path_text;
+
+const COUNTER: &str = "{n}";
+
+/// Expand the strftime like tokens in `template` for `time`.  The date
+/// and time are always those of UTC (not local time) so that the paths
+/// generated don't depend on the time zone or jump when daylight saving
+/// starts or ends.  To expand for another zone pass `time` shifted by that
+/// zone's offset from UTC (but "%s" will be shifted too).  The tokens are:
+///
+/// - "%Y" the year, "%y" the year within the century,
+/// - "%m" the month, "%d" the day of the month,
+/// - "%H" the hour, "%M" the minute, "%S" the second,
+/// - "%s" the seconds since the Unix epoch, "%F" as "%Y-%m-%d" and
+/// - "%%" a literal '%'.
+///
+/// Other tokens (and "{n}" which is handled by `unique_path()`) are left
+/// unchanged e.g. "logs/%Y/%m/%d/run-%H%M%S.log".
+pub fn expand_time_template(template: &str, time: SystemTime) -> String {
+    let dt = DateTime::from_system_time(time);
+    let mut expanded = String::with_capacity(template.len() + 16);
+    let mut chars = template.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            expanded.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => expanded.push_str(&format!("{:04}", dt.year)),
+            Some('y') => expanded.push_str(&format!("{:02}", dt.year.rem_euclid(100))),
+            Some('m') => expanded.push_str(&format!("{:02}", dt.month)),
+            Some('d') => expanded.push_str(&format!("{:02}", dt.day)),
+            Some('H') => expanded.push_str(&format!("{:02}", dt.hour)),
+            Some('M') => expanded.push_str(&format!("{:02}", dt.minute)),
+            Some('S') => expanded.push_str(&format!("{:02}", dt.second)),
+            Some('F') => {
+                expanded.push_str(&format!("{:04}-{:02}-{:02}", dt.year, dt.month, dt.day))
+            }
+            Some('s') => {
+                let secs = match time.duration_since(SystemTime::UNIX_EPOCH) {
+                    Ok(duration) => duration.as_secs() as i64,
+                    Err(err) => -(err.duration().as_secs() as i64),
+                };
+                expanded.push_str(&secs.to_string())
+            }
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+fn exists(path: &str) -> bool {
+    fs::symlink_metadata(Path::new(path)).is_ok()
+}
+
+/// Return a path (based on `template`) that doesn't exist.  If `template`
+/// contains "{n}" the first of 1, 2, ... that gives a nonexistent path is
+/// substituted for it.  Otherwise `template` is returned if it doesn't
+/// exist or else "-2", "-3", ... is inserted before its extension (e.g.
+/// "run.log" becomes "run-2.log").
+pub fn unique_path(template: &str) -> io::Result<String> {
+    if template.contains(COUNTER) {
+        return (1..u32::MAX)
+            .map(|n| template.replace(COUNTER, &n.to_string()))
+            .find(|path| !exists(path))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::AlreadyExists, template.to_string()));
+    }
+    if !exists(template) {
+        return Ok(template.to_string());
+    }
+    let (dir, name) = split_path_text(template);
+    let (stem, extension) = match name.rfind('.') {
+        Some(index) if index > 0 => name.split_at(index),
+        _ => (name, ""),
+    };
+    (2..u32::MAX)
+        .map(|n| format!("{}{}-{}{}", dir, stem, n, extension))
+        .find(|path| !exists(path))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::AlreadyExists, template.to_string()))
+}
+
+/// Expand `template` for `time` in UTC (see `expand_time_template()`) and
+/// make the result unique (see `unique_path()`).
+pub fn unique_time_path(template: &str, time: SystemTime) -> io::Result<String> {
+    unique_path(&expand_time_template(template, time))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A path template with named placeholders e.g.
+/// "{project}/{target}/output.{ext}" ("{{" and "}}" give literal braces)
+/// which can be used to both construct paths and extract the values from
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    parts: Vec<Part>,
+}
+
+impl PathTemplate {
+    pub fn parse(text: &str) -> Result<PathTemplate, PathuxError> {
+        let invalid = || PathuxError::InvalidTemplate(text.to_string());
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) if ch.is_alphanumeric() || ch == '_' => name.push(ch),
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    if name.is_empty() {
+                        return Err(invalid());
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(literal.split_off(0)));
+                    }
+                    parts.push(Part::Placeholder(name));
+                }
+                '}' => return Err(invalid()),
+                _ => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(PathTemplate { parts })
+    }
+
+    /// The names of the placeholders (in order of first appearance).
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for part in self.parts.iter() {
+            if let Part::Placeholder(name) = part {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Construct a path by substituting `values` for the placeholders.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, PathuxError> {
+        let mut rendered = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(text) => rendered.push_str(text),
+                Part::Placeholder(name) => match values.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None => return Err(PathuxError::MissingValue(name.to_string())),
+                },
+            }
+        }
+        Ok(rendered)
+    }
+
+    /// Return the placeholder values for which this template renders as
+    /// `path` or `None` if there are none.  Placeholders match within a
+    /// single path component (and a placeholder that appears more than
+    /// once must have the same value each time) and the separators in the
+    /// template match any of the platform's separators.
+    pub fn extract(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut values = HashMap::new();
+        if match_parts(&self.parts, path, &mut values) {
+            Some(values)
+        } else {
+            None
+        }
+    }
+}
+
+// Remove `literal` from the start of `text` (with separators matching
+// separators).
+fn strip_literal<'a>(text: &'a str, literal: &str) -> Option<&'a str> {
+    let mut chars = text.chars();
+    for expected in literal.chars() {
+        match chars.next() {
+            Some(ch) if ch == expected => (),
+            Some(ch) if path::is_separator(ch) && path::is_separator(expected) => (),
+            _ => return None,
+        }
+    }
+    Some(chars.as_str())
+}
+
+fn match_parts(parts: &[Part], text: &str, values: &mut HashMap<String, String>) -> bool {
+    let (part, rest_parts) = match parts.split_first() {
+        Some(split) => split,
+        None => return text.is_empty(),
+    };
+    match part {
+        Part::Literal(literal) => match strip_literal(text, literal) {
+            Some(rest) => match_parts(rest_parts, rest, values),
+            None => false,
+        },
+        Part::Placeholder(name) => {
+            if let Some(value) = values.get(name).cloned() {
+                return match text.strip_prefix(value.as_str()) {
+                    Some(rest) => match_parts(rest_parts, rest, values),
+                    None => false,
+                };
+            }
+            for (index, ch) in text.char_indices() {
+                if path::is_separator(ch) {
+                    break;
+                }
+                let end = index + ch.len_utf8();
+                values.insert(name.to_string(), text[..end].to_string());
+                if match_parts(rest_parts, &text[end..], values) {
+                    return true;
+                }
+                values.remove(name);
+            }
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::time::Duration;
+
+    use path_to_string;
+
+    #[test]
+    fn expand_time_template_works() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400 + 3_723);
+        assert_eq!(
+            expand_time_template("logs/%Y/%m/%d/run-%H%M%S.log", time),
+            "logs/2000/02/29/run-010203.log"
+        );
+        assert_eq!(
+            expand_time_template("%F_%y-%s-100%%-%q-{n}%", time),
+            "2000-02-29_00-951786123-100%-%q-{n}%"
+        );
+    }
+
+    #[test]
+    fn path_template_works() {
+        let template = PathTemplate::parse("{project}/{target}/output.{ext}").unwrap();
+        assert_eq!(template.placeholders(), vec!["project", "target", "ext"]);
+        let mut values = HashMap::new();
+        values.insert("project".to_string(), "pathux".to_string());
+        values.insert("target".to_string(), "release".to_string());
+        assert!(template.render(&values).is_err());
+        values.insert("ext".to_string(), "tar.gz".to_string());
+        let path = template.render(&values).unwrap();
+        assert_eq!(path, "pathux/release/output.tar.gz");
+        assert_eq!(template.extract(&path), Some(values));
+        assert_eq!(template.extract("pathux/release/other.txt"), None);
+        assert_eq!(template.extract("a/b/c/output.txt"), None);
+
+        let template = PathTemplate::parse("{a}-{b}/{a}{{x}}").unwrap();
+        let values = template.extract("x-y-z/x-y{x}").unwrap();
+        assert_eq!(values["a"], "x-y");
+        assert_eq!(values["b"], "z");
+        for bad in ["{", "{}", "}", "{a/b}"].iter() {
+            assert!(PathTemplate::parse(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn unique_path_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base = path_to_string(temp_dir.path());
+        let template = format!("{}/run-{{n}}.log", base);
+        assert_eq!(
+            unique_path(&template).unwrap(),
+            format!("{}/run-1.log", base)
+        );
+        fs::write(format!("{}/run-1.log", base), "").unwrap();
+        assert_eq!(
+            unique_path(&template).unwrap(),
+            format!("{}/run-2.log", base)
+        );
+
+        let plain = format!("{}/run.log", base);
+        assert_eq!(unique_path(&plain).unwrap(), plain);
+        fs::write(&plain, "").unwrap();
+        assert_eq!(unique_path(&plain).unwrap(), format!("{}/run-2.log", base));
+        fs::write(format!("{}/.hidden", base), "").unwrap();
+        assert_eq!(
+            unique_path(&format!("{}/.hidden", base)).unwrap(),
+            format!("{}/.hidden-2", base)
+        );
+    }
+}
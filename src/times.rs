@@ -0,0 +1,230 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide querying, setting and copying of file access and
+//! modification times.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// The times recorded for a file.  Not all platforms (or file systems)
+/// record access and creation times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamps {
+    pub accessed: Option<SystemTime>,
+    pub modified: SystemTime,
+    pub created: Option<SystemTime>,
+}
+
+impl Timestamps {
+    /// The times of `path` (following symbolic links).
+    pub fn of<P: AsRef<Path>>(path: &P) -> io::Result<Self> {
+        Self::from_metadata(&fs::metadata(path)?)
+    }
+
+    pub fn from_metadata(metadata: &Metadata) -> io::Result<Self> {
+        Ok(Timestamps {
+            accessed: metadata.accessed().ok(),
+            modified: metadata.modified()?,
+            created: metadata.created().ok(),
+        })
+    }
+
+    /// How long ago the file was modified (zero if its modification time
+    /// is in the future).
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.modified)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::convert::TryFrom;
+    use std::io;
+    use std::mem;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use libc;
+
+    use sys::c_path;
+
+    fn timespec(time: SystemTime) -> io::Result<libc::timespec> {
+        let (secs, nsecs) = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i128, i64::from(since.subsec_nanos())),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i128), 0),
+                    nsecs => (
+                        -(before.as_secs() as i128) - 1,
+                        1_000_000_000 - i64::from(nsecs),
+                    ),
+                }
+            }
+        };
+        let tv_sec = libc::time_t::try_from(secs)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "time out of range"))?;
+        // some platforms have padding fields so no struct literal
+        let mut spec: libc::timespec = unsafe { mem::zeroed() };
+        spec.tv_sec = tv_sec;
+        spec.tv_nsec = nsecs as _;
+        Ok(spec)
+    }
+
+    // Unlike opening the file this neither blocks (e.g. on FIFOs) nor
+    // needs read permission.
+    pub fn set_times(path: &Path, atime: SystemTime, mtime: SystemTime) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        let times = [timespec(atime)?, timespec(mtime)?];
+        let result = unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), 0) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::fs::{File, FileTimes};
+    use std::io;
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    // Open an existing file or directory such that its times can be set
+    // (even if it is read only).
+    #[cfg(windows)]
+    fn open_for_times(path: &Path) -> io::Result<File> {
+        use std::fs::OpenOptions;
+        use std::os::windows::fs::OpenOptionsExt;
+        use winapi::um::winbase::FILE_FLAG_BACKUP_SEMANTICS;
+        use winapi::um::winnt::FILE_WRITE_ATTRIBUTES;
+
+        OpenOptions::new()
+            .access_mode(FILE_WRITE_ATTRIBUTES)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)
+    }
+
+    #[cfg(not(windows))]
+    fn open_for_times(path: &Path) -> io::Result<File> {
+        File::open(path)
+    }
+
+    pub fn set_times(path: &Path, atime: SystemTime, mtime: SystemTime) -> io::Result<()> {
+        open_for_times(path)?.set_times(FileTimes::new().set_accessed(atime).set_modified(mtime))
+    }
+}
+
+/// Set the access and modification times of `path` (following symbolic
+/// links).
+pub fn set_times<P: AsRef<Path>>(path: &P, atime: SystemTime, mtime: SystemTime) -> io::Result<()> {
+    imp::set_times(path.as_ref(), atime, mtime)
+}
+
+/// Give `dst` the same access and modification times as `src`.  If the
+/// platform doesn't record access times the modification time is used.
+pub fn copy_times<S, D>(src: &S, dst: &D) -> io::Result<()>
+where
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    let metadata = fs::metadata(src)?;
+    let mtime = metadata.modified()?;
+    let atime = metadata.accessed().unwrap_or(mtime);
+    set_times(dst, atime, mtime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn timestamps_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        let timestamps = Timestamps::of(&file).unwrap();
+        assert!(timestamps.age() < Duration::from_secs(3600));
+        let then = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        set_times(&file, then, then).unwrap();
+        let timestamps = Timestamps::of(&file).unwrap();
+        assert_eq!(timestamps.modified, then);
+        assert!(timestamps.age() > Duration::from_secs(365 * 24 * 3600));
+        let future = SystemTime::now() + Duration::from_secs(3600);
+        set_times(&file, future, future).unwrap();
+        assert_eq!(Timestamps::of(&file).unwrap().age(), Duration::ZERO);
+        assert!(Timestamps::of(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn set_and_copy_times_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        fs::write(&src, "").unwrap();
+        fs::write(&dst, "").unwrap();
+        let atime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        set_times(&src, atime, mtime).unwrap();
+        let metadata = fs::metadata(&src).unwrap();
+        assert_eq!(metadata.modified().unwrap(), mtime);
+        assert_eq!(metadata.accessed().unwrap(), atime);
+        copy_times(&src, &dst).unwrap();
+        assert_eq!(fs::metadata(&dst).unwrap().modified().unwrap(), mtime);
+        assert!(set_times(&temp_dir.path().join("missing"), atime, mtime).is_err());
+        let before_epoch = SystemTime::UNIX_EPOCH - Duration::new(10, 250_000_000);
+        set_times(&src, before_epoch, before_epoch).unwrap();
+        assert_eq!(
+            fs::metadata(&src).unwrap().modified().unwrap(),
+            before_epoch
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_times_needs_no_access() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::process::Command;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let then = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        // opening a FIFO (for reading) would block until there's a writer
+        let fifo = temp_dir.path().join("fifo");
+        if Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .is_ok_and(|s| s.success())
+        {
+            set_times(&fifo, then, then).unwrap();
+            assert_eq!(
+                fs::symlink_metadata(&fifo).unwrap().modified().unwrap(),
+                then
+            );
+        }
+        let write_only = temp_dir.path().join("write_only");
+        fs::write(&write_only, "").unwrap();
+        fs::set_permissions(&write_only, fs::Permissions::from_mode(0o200)).unwrap();
+        set_times(&write_only, then, then).unwrap();
+        assert_eq!(fs::metadata(&write_only).unwrap().modified().unwrap(), then);
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide `touch` and `truncate` like operations.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::times::set_times;
+
+/// Set the access and modification times of `path` to now creating it
+/// (as an empty file) if it doesn't exist.
+pub fn touch<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+    touch_at(path, SystemTime::now())
+}
+
+/// Like `touch()` but the times are set to `time`.
+pub fn touch_at<P: AsRef<Path>>(path: &P, time: SystemTime) -> io::Result<()> {
+    let path: &Path = path.as_ref();
+    match set_times(&path, time, time) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            set_times(&path, time, time)
+        }
+        result => result,
+    }
+}
+
+/// Shorten (or extend with zeroes) the existing file `path` to `len`
+/// bytes.
+pub fn truncate_file<P: AsRef<Path>>(path: &P, len: u64) -> io::Result<()> {
+    OpenOptions::new().write(true).open(path)?.set_len(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn touch_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        touch(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        fs::write(&path, "content").unwrap();
+        let then = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        touch_at(&path, then).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.modified().unwrap(), then);
+        assert_eq!(metadata.len(), 7);
+        touch(&path).unwrap();
+        assert!(fs::metadata(&path).unwrap().modified().unwrap() > then);
+        touch_at(&temp_dir.path(), then).unwrap();
+        assert_eq!(
+            fs::metadata(temp_dir.path()).unwrap().modified().unwrap(),
+            then
+        );
+        assert!(touch(&temp_dir.path().join("missing/file")).is_err());
+    }
+
+    #[test]
+    fn truncate_file_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        fs::write(&path, "0123456789").unwrap();
+        truncate_file(&path, 4).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"0123");
+        truncate_file(&path, 6).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"0123\0\0");
+        assert!(truncate_file(&temp_dir.path().join("missing"), 0).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide moving of files and directories to the desktop's
+//! trash (or recycle bin) from where they can be restored by the user.
+
+use std::io;
+use std::path::Path;
+
+use super::absolute_path_buf;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod imp {
+    use std::env;
+    use std::ffi::OsString;
+    use std::fs::{self, DirBuilder, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::path::{Path, PathBuf};
+
+    use libc;
+
+    use context::Context;
+    use filesystem::{is_mount_point, same_filesystem};
+    use percent_encoding::percent_encode_path;
+
+    // The user's "home trash" as per the FreeDesktop.org trash specification.
+    fn home_trash_dir() -> io::Result<PathBuf> {
+        match env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+            Some(ref data_home) if data_home.is_absolute() => Ok(data_home.join("Trash")),
+            _ => match Context::home_dir() {
+                Some(home_dir) => Ok(home_dir.join(".local/share/Trash")),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "home directory not found",
+                )),
+            },
+        }
+    }
+
+    fn mount_point(path: &Path) -> io::Result<&Path> {
+        for ancestor in path.ancestors().skip(1) {
+            if is_mount_point(&ancestor)? {
+                return Ok(ancestor);
+            }
+        }
+        Ok(Path::new("/"))
+    }
+
+    // Create the (private) trash directory `dir` unless it already exists
+    // as a real directory.
+    fn make_trash_dir(dir: &Path) -> io::Result<()> {
+        match DirBuilder::new().mode(0o700).create(dir) {
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                if fs::symlink_metadata(dir)?.is_dir() {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        "trash directory is not a directory",
+                    ))
+                }
+            }
+            result => result,
+        }
+    }
+
+    // The trash directory for the user `uid` on the file system mounted at
+    // `top_dir`: "$topdir/.Trash/$uid" if the administrator has provided
+    // "$topdir/.Trash" (a sticky directory that isn't a symbolic link),
+    // else "$topdir/.Trash-$uid" or `None` if neither can be used.
+    pub fn top_dir_trash_dir(top_dir: &Path, uid: u32) -> Option<PathBuf> {
+        let admin_dir = top_dir.join(".Trash");
+        if let Ok(metadata) = fs::symlink_metadata(&admin_dir) {
+            if metadata.is_dir() && metadata.permissions().mode() & 0o1000 != 0 {
+                let trash_dir = admin_dir.join(uid.to_string());
+                if make_trash_dir(&trash_dir).is_ok() {
+                    return Some(trash_dir);
+                }
+            }
+        }
+        let trash_dir = top_dir.join(format!(".Trash-{}", uid));
+        match make_trash_dir(&trash_dir) {
+            Ok(()) => Some(trash_dir),
+            Err(_) => None,
+        }
+    }
+
+    // The specification requires local time (unlike the UTC of the
+    // crate's other time stamps e.g. `template::expand_time_template()`).
+    fn deletion_date() -> String {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        unsafe {
+            let now = libc::time(std::ptr::null_mut());
+            libc::localtime_r(&now, &mut tm);
+        }
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+
+    // Move `abs_path` into the trash directory `trash_dir` recording
+    // `recorded_path` as its original location.
+    pub fn trash_to(abs_path: &Path, recorded_path: &Path, trash_dir: &Path) -> io::Result<()> {
+        let recorded_path = recorded_path
+            .to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "path is not valid UTF-8"))?;
+        let file_name = abs_path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        let files_dir = trash_dir.join("files");
+        let info_dir = trash_dir.join("info");
+        let mut builder = DirBuilder::new();
+        builder.recursive(true).mode(0o700);
+        builder.create(&files_dir)?;
+        builder.create(&info_dir)?;
+        let info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode_path(recorded_path),
+            deletion_date()
+        );
+        // creating the info file exclusively reserves the name
+        for n in 1.. {
+            let mut name = OsString::from(file_name);
+            if n > 1 {
+                name.push(format!(".{}", n));
+            }
+            let mut info_name = name.clone();
+            info_name.push(".trashinfo");
+            let info_path = info_dir.join(info_name);
+            let mut info_file = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                Ok(file) => file,
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            };
+            let target = files_dir.join(&name);
+            if target.exists() {
+                drop(info_file);
+                fs::remove_file(&info_path)?;
+                continue;
+            }
+            let result = info_file
+                .write_all(info.as_bytes())
+                .and_then(|_| fs::rename(abs_path, &target));
+            if result.is_err() {
+                fs::remove_file(&info_path)?;
+            }
+            return result;
+        }
+        unreachable!()
+    }
+
+    pub fn trash(abs_path: &Path) -> io::Result<()> {
+        let parent = abs_path.parent().unwrap_or(abs_path);
+        let home_trash_dir = home_trash_dir()?;
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&home_trash_dir)?;
+        if same_filesystem(&parent, &home_trash_dir)? {
+            trash_to(abs_path, abs_path, &home_trash_dir)
+        } else {
+            // use the trash at the top of the file system if possible
+            let top_dir = mount_point(abs_path)?;
+            let uid = unsafe { libc::getuid() };
+            match top_dir_trash_dir(top_dir, uid) {
+                Some(trash_dir) => {
+                    let recorded_path = abs_path.strip_prefix(top_dir).unwrap_or(abs_path);
+                    trash_to(abs_path, recorded_path, &trash_dir)
+                }
+                None => trash_to(abs_path, abs_path, &home_trash_dir),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    use context::Context;
+
+    pub fn trash(abs_path: &Path) -> io::Result<()> {
+        let trash_dir = match Context::home_dir() {
+            Some(home_dir) => home_dir.join(".Trash"),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "home directory not found",
+                ))
+            }
+        };
+        let file_name = abs_path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        fs::create_dir_all(&trash_dir)?;
+        let mut target = trash_dir.join(file_name);
+        let mut n = 1;
+        while fs::symlink_metadata(&target).is_ok() {
+            n += 1;
+            let mut name = OsString::from(file_name);
+            name.push(format!(" {}", n));
+            target = trash_dir.join(name);
+        }
+        fs::rename(abs_path, target)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::path::Path;
+    use std::ptr;
+
+    use winapi::um::shellapi::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, FO_DELETE,
+        SHFILEOPSTRUCTW,
+    };
+
+    use sys::wide_path;
+
+    pub fn trash(abs_path: &Path) -> io::Result<()> {
+        // the list of files must be terminated by an extra null
+        let mut from = wide_path(abs_path);
+        from.push(0);
+        let mut operation = SHFILEOPSTRUCTW {
+            hwnd: ptr::null_mut(),
+            wFunc: FO_DELETE as u32,
+            pFrom: from.as_ptr(),
+            pTo: ptr::null(),
+            fFlags: FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT,
+            fAnyOperationsAborted: 0,
+            hNameMappings: ptr::null_mut(),
+            lpszProgressTitle: ptr::null(),
+        };
+        match unsafe { SHFileOperationW(&mut operation) } {
+            0 if operation.fAnyOperationsAborted == 0 => Ok(()),
+            0 => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "move to recycle bin aborted",
+            )),
+            code => Err(io::Error::other(format!(
+                "move to recycle bin failed (code {:#x})",
+                code
+            ))),
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    pub fn trash(_abs_path: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "there is no trash on this platform",
+        ))
+    }
+}
+
+/// Move `path` (a file, directory or symbolic link) to the trash: the
+/// FreeDesktop.org trash (with a ".trashinfo" file so that it can be
+/// restored) on Linux and other unixes, "~/.Trash" on macOS and the
+/// Recycle Bin on Windows.  This is a safer alternative to deleting for
+/// interactive tools.
+pub fn trash<P: AsRef<Path>>(path: &P) -> io::Result<()> {
+    let abs_path = absolute_path_buf(path.as_ref());
+    // make sure that it exists (without following symbolic links)
+    abs_path.symlink_metadata()?;
+    imp::trash(&abs_path)
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn trash_to_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let trash_dir = temp_dir.path().join("Trash");
+        let path = temp_dir.path().join("a file");
+        for content in ["first", "second"].iter() {
+            fs::write(&path, content).unwrap();
+            imp::trash_to(&path, &path, &trash_dir).unwrap();
+            assert!(!path.exists());
+        }
+        assert_eq!(
+            fs::read_to_string(trash_dir.join("files/a file")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(trash_dir.join("files/a file.2")).unwrap(),
+            "second"
+        );
+        let info = fs::read_to_string(trash_dir.join("info/a file.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath="));
+        assert!(info.contains("/a%20file\nDeletionDate="));
+        assert!(trash(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn top_dir_trash_dir_works() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let top_dir = temp_dir.path();
+        let admin_dir = top_dir.join(".Trash");
+        // no "$topdir/.Trash"
+        assert_eq!(
+            imp::top_dir_trash_dir(top_dir, 1000),
+            Some(top_dir.join(".Trash-1000"))
+        );
+        assert!(top_dir.join(".Trash-1000").is_dir());
+        // not sticky
+        fs::create_dir(&admin_dir).unwrap();
+        assert_eq!(
+            imp::top_dir_trash_dir(top_dir, 1001),
+            Some(top_dir.join(".Trash-1001"))
+        );
+        // sticky
+        fs::set_permissions(&admin_dir, fs::Permissions::from_mode(0o1777)).unwrap();
+        assert_eq!(
+            imp::top_dir_trash_dir(top_dir, 1002),
+            Some(admin_dir.join("1002"))
+        );
+        assert!(admin_dir.join("1002").is_dir());
+        // a symbolic link to a sticky directory
+        let link_dir = tempfile::tempdir().unwrap();
+        let link_top_dir = link_dir.path();
+        symlink(&admin_dir, link_top_dir.join(".Trash")).unwrap();
+        assert_eq!(
+            imp::top_dir_trash_dir(link_top_dir, 1003),
+            Some(link_top_dir.join(".Trash-1003"))
+        );
+        // neither is usable so the home trash is used
+        fs::write(admin_dir.join("1004"), "").unwrap();
+        fs::write(top_dir.join(".Trash-1004"), "").unwrap();
+        assert_eq!(imp::top_dir_trash_dir(top_dir, 1004), None);
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide rendering of directory trees in the style of the
+//! `tree` command.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::walk::Walker;
+use super::UsableDirEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeStyle {
+    Ascii,
+    Unicode,
+}
+
+impl TreeStyle {
+    // (branch, last branch, continuation, blank)
+    fn connectors(&self) -> (&'static str, &'static str, &'static str, &'static str) {
+        match self {
+            TreeStyle::Ascii => ("|-- ", "`-- ", "|   ", "    "),
+            TreeStyle::Unicode => ("├── ", "└── ", "│   ", "    "),
+        }
+    }
+}
+
+pub type EntryFilter = Box<dyn Fn(&UsableDirEntry) -> bool>;
+
+pub struct TreeRenderOptions {
+    pub style: TreeStyle,
+    pub max_depth: Option<usize>,
+    /// Show each file's size (in bytes) before its name.
+    pub show_sizes: bool,
+    /// Omit the "N directories, M files" summary line.
+    pub no_report: bool,
+    /// Only entries for which this returns `true` are shown.  Excluding a
+    /// directory also excludes its contents.
+    pub filter: Option<EntryFilter>,
+}
+
+impl Default for TreeRenderOptions {
+    fn default() -> Self {
+        TreeRenderOptions {
+            style: TreeStyle::Unicode,
+            max_depth: None,
+            show_sizes: false,
+            no_report: false,
+            filter: None,
+        }
+    }
+}
+
+struct Node {
+    label: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+fn render_children(
+    dir_path: &Path,
+    children: &HashMap<PathBuf, Vec<Node>>,
+    prefix: &str,
+    style: TreeStyle,
+    text: &mut String,
+) {
+    let (branch, last_branch, continuation, blank) = style.connectors();
+    if let Some(nodes) = children.get(dir_path) {
+        for (index, node) in nodes.iter().enumerate() {
+            let is_last = index + 1 == nodes.len();
+            text.push_str(prefix);
+            text.push_str(if is_last { last_branch } else { branch });
+            text.push_str(&node.label);
+            text.push('\n');
+            if node.is_dir {
+                let prefix = format!("{}{}", prefix, if is_last { blank } else { continuation });
+                render_children(&node.path, children, &prefix, style, text);
+            }
+        }
+    }
+}
+
+/// Render the tree below `root` with entries sorted by name.  Symbolic
+/// links are shown (with their targets) but not followed.
+pub fn render_tree<P: AsRef<Path>>(root: &P, options: &TreeRenderOptions) -> io::Result<String> {
+    let root: &Path = root.as_ref();
+    let mut walker = Walker::new(&root);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let mut children: HashMap<PathBuf, Vec<Node>> = HashMap::new();
+    for entry in walker {
+        let entry = entry?;
+        if let Some(ref filter) = options.filter {
+            if !filter(&entry) {
+                continue;
+            }
+        }
+        let path = entry.path();
+        let mut label = entry.file_name();
+        if entry.is_symlink() {
+            if let Ok(target) = fs::read_link(&path) {
+                label = format!("{} -> {}", label, target.to_string_lossy());
+            }
+        }
+        if options.show_sizes {
+            label = format!("[{}]  {}", entry.metadata()?.len(), label);
+        }
+        let parent = path.parent().unwrap_or(root).to_path_buf();
+        children.entry(parent).or_default().push(Node {
+            label,
+            path,
+            is_dir: entry.is_dir(),
+        });
+    }
+    for nodes in children.values_mut() {
+        nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    let mut text = format!("{}\n", root.to_string_lossy());
+    render_children(root, &children, "", options.style, &mut text);
+    if !options.no_report {
+        let (mut dirs, mut files) = (0, 0);
+        // only count what's reachable from the root (i.e. what was rendered)
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(dir_path) = stack.pop() {
+            for node in children.get(&dir_path).into_iter().flatten() {
+                if node.is_dir {
+                    dirs += 1;
+                    stack.push(node.path.clone());
+                } else {
+                    files += 1;
+                }
+            }
+        }
+        text.push_str(&format!(
+            "\n{} director{}, {} file{}\n",
+            dirs,
+            if dirs == 1 { "y" } else { "ies" },
+            files,
+            if files == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    #[test]
+    fn render_tree_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/c"), "").unwrap();
+        fs::write(root.join("a/d"), "1234").unwrap();
+        fs::write(root.join("e"), "").unwrap();
+        let root_str = root.to_string_lossy();
+
+        let text = render_tree(&root, &TreeRenderOptions::default()).unwrap();
+        let expected = format!(
+            "{}\n├── a\n│   ├── b\n│   │   └── c\n│   └── d\n└── e\n\n2 directories, 3 files\n",
+            root_str
+        );
+        assert_eq!(text, expected);
+
+        let options = TreeRenderOptions {
+            style: TreeStyle::Ascii,
+            max_depth: Some(2),
+            show_sizes: false,
+            no_report: true,
+            filter: Some(Box::new(|e| e.file_name() != "b")),
+        };
+        let text = render_tree(&root, &options).unwrap();
+        assert_eq!(text, format!("{}\n|-- a\n|   `-- d\n`-- e\n", root_str));
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide parsing and construction of Windows UNC (network
+//! share) paths e.g. `\\server\share\dir\file`.
+//!
+//! Parsing is purely textual so it behaves the same way on all platforms.
+
+use std::str::FromStr;
+
+use super::filesystem::{drive_type, DriveType};
+use super::str_path::StrPathPrefix;
+
+// Split `path` after its "\\server\share" (or "\\?\UNC\server\share")
+// prefix.
+fn split_unc(path: &str) -> Option<(StrPathPrefix, &str)> {
+    let start = if path.starts_with(r"\\?\UNC\") {
+        8
+    } else if path.starts_with(r"\\") || path.starts_with("//") {
+        2
+    } else {
+        return None;
+    };
+    let is_separator = |c: char| c == '\\' || c == '/';
+    let server_len = path[start..].find(is_separator)?;
+    let share_start = start + server_len + 1;
+    let end = path[share_start..]
+        .find(is_separator)
+        .map_or(path.len(), |share_len| share_start + share_len);
+    let prefix = StrPathPrefix::from_str(&path[..end]).ok()?;
+    Some((prefix, path[end..].trim_start_matches(is_separator)))
+}
+
+/// Split the UNC path `path` into its server, share and the rest of the
+/// path (with its leading separator removed).  Extended length
+/// (`\\?\UNC\`) paths are accepted.
+pub fn parse_unc(path: &str) -> Option<(String, String, String)> {
+    match split_unc(path)? {
+        (StrPathPrefix::UNC(server, share), rest)
+        | (StrPathPrefix::VerbatimUNC(server, share), rest) => {
+            Some((server, share, rest.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Build the UNC path for `rest` (which may use either separator) within
+/// `share` on `server`.
+pub fn make_unc(server: &str, share: &str, rest: &str) -> String {
+    let mut path = StrPathPrefix::UNC(server.to_string(), share.to_string()).to_string();
+    let rest = rest.trim_start_matches(['\\', '/']);
+    if !rest.is_empty() {
+        path.push('\\');
+        path.push_str(&rest.replace('/', "\\"));
+    }
+    path
+}
+
+/// Return `true` if `path` is a UNC path or lives on a network file system
+/// (e.g. a mapped drive or an NFS mount).
+pub fn is_network_path(path: &str) -> bool {
+    parse_unc(path).is_some() || drive_type(&path) == DriveType::Network
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_unc_works() {
+        let expected = |rest: &str| Some(("srv".to_string(), "pub".to_string(), rest.to_string()));
+        assert_eq!(parse_unc(r"\\srv\pub\a\b.txt"), expected(r"a\b.txt"));
+        assert_eq!(parse_unc(r"\\srv\pub"), expected(""));
+        assert_eq!(parse_unc(r"\\srv\pub\"), expected(""));
+        assert_eq!(parse_unc("//srv/pub/a/b"), expected("a/b"));
+        assert_eq!(parse_unc(r"\\?\UNC\srv\pub\a"), expected("a"));
+        assert_eq!(parse_unc(r"\\srv"), None);
+        assert_eq!(parse_unc(r"\\?\C:\a"), None);
+        assert_eq!(parse_unc(r"\\.\COM1"), None);
+        assert_eq!(parse_unc(r"C:\a"), None);
+        assert_eq!(parse_unc("/a/b"), None);
+    }
+
+    #[test]
+    fn make_unc_works() {
+        assert_eq!(make_unc("srv", "pub", "a/b.txt"), r"\\srv\pub\a\b.txt");
+        assert_eq!(make_unc("srv", "pub", ""), r"\\srv\pub");
+        let path = make_unc("srv", "pub", r"\a");
+        assert_eq!(
+            parse_unc(&path),
+            Some(("srv".to_string(), "pub".to_string(), "a".to_string()))
+        );
+        assert!(is_network_path(&path));
+        assert!(!is_network_path("src"));
+    }
+}
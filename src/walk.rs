@@ -0,0 +1,819 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide a recursive directory walker that yields
+//! `UsableDirEntry` items (or the entries of another `FsProvider`) for
+//! everything below a root directory.
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::io;
+use std::ops::{ControlFlow, Deref};
+use std::path::{Path, PathBuf};
+use std::vec;
+
+use super::fs_provider::{FileId, FsDirEntry, FsProvider, RealFs};
+use super::sorting::SortBy;
+use super::UsableDirEntry;
+
+#[derive(Debug)]
+pub enum WalkError {
+    Io(PathBuf, io::Error),
+    /// Following the symbolic link at this path would lead back to one of
+    /// its own ancestors.
+    SymlinkLoop(PathBuf),
+}
+
+impl WalkError {
+    pub fn path(&self) -> &Path {
+        match self {
+            WalkError::Io(path, _) => path,
+            WalkError::SymlinkLoop(path) => path,
+        }
+    }
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalkError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            WalkError::SymlinkLoop(path) => {
+                write!(f, "{}: symbolic link loop detected", path.display())
+            }
+        }
+    }
+}
+
+impl error::Error for WalkError {}
+
+impl From<WalkError> for io::Error {
+    fn from(err: WalkError) -> io::Error {
+        match err {
+            WalkError::Io(path, err) => {
+                io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+            }
+            WalkError::SymlinkLoop(_) => io::Error::other(err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WalkEntry<E = UsableDirEntry> {
+    entry: E,
+    depth: usize,
+    followed: bool,
+}
+
+impl<E> WalkEntry<E> {
+    /// Whether this entry is a symbolic link that was followed, in which
+    /// case the file type methods describe the link's target.
+    pub fn is_followed_symlink(&self) -> bool {
+        self.followed
+    }
+
+    /// The number of levels below the walk's root (direct children are at depth 1).
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn entry(&self) -> &E {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> E {
+        self.entry
+    }
+}
+
+impl WalkEntry {
+    /// The metadata of the entry (or of its target if it is a followed
+    /// symbolic link).
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        if self.followed {
+            fs::metadata(self.entry.path())
+        } else {
+            self.entry.metadata()
+        }
+    }
+}
+
+impl<E> Deref for WalkEntry<E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        &self.entry
+    }
+}
+
+/// The order in which a `Walker` yields entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraversalOrder {
+    /// Directories are yielded before their contents.
+    #[default]
+    DepthFirstPre,
+    /// Directories are yielded after their contents (as needed for
+    /// recursive deletion).
+    DepthFirstPost,
+    /// All entries at one depth are yielded before any deeper entries.
+    BreadthFirst,
+}
+
+type Item<F> = Result<WalkEntry<<F as FsProvider>::DirEntry>, WalkError>;
+
+// A directory waiting to be opened.
+struct Pending<E> {
+    dir_path: PathBuf,
+    depth: usize,
+    // the directory's ancestors (only needed when following symbolic links)
+    ancestors: Vec<FileId>,
+    // the directory's own entry if it is to be yielded after its contents
+    post_entry: Option<WalkEntry<E>>,
+}
+
+// Entries are read on demand unless they have to be sorted first or the
+// directory handle had to be closed to stay within the open handle limit.
+enum FrameEntries<F: FsProvider> {
+    Open(Box<F::ReadDir>),
+    Buffered(vec::IntoIter<Item<F>>),
+}
+
+struct Frame<F: FsProvider> {
+    dir_path: PathBuf,
+    entries: FrameEntries<F>,
+    depth: usize,
+    // the directory and its ancestors (only needed when following symbolic links)
+    lineage: Vec<FileId>,
+    post_entry: Option<WalkEntry<F::DirEntry>>,
+}
+
+impl<F: FsProvider> Frame<F> {
+    fn next_entry(&mut self, fs: &F, follow_symlinks: bool) -> Option<Item<F>> {
+        match self.entries {
+            FrameEntries::Open(ref mut read_dir) => match read_dir.next()? {
+                Ok(entry) => Some(Ok(walk_entry(fs, entry, self.depth, follow_symlinks))),
+                Err(err) => Some(Err(WalkError::Io(self.dir_path.clone(), err))),
+            },
+            FrameEntries::Buffered(ref mut entries) => entries.next(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        match self.entries {
+            FrameEntries::Open(_) => true,
+            FrameEntries::Buffered(_) => false,
+        }
+    }
+
+    // Read the remaining entries into memory so that the directory handle
+    // can be closed.
+    fn buffer(&mut self, fs: &F, follow_symlinks: bool) {
+        let mut items = Vec::new();
+        while let Some(item) = self.next_entry(fs, follow_symlinks) {
+            items.push(item);
+        }
+        self.entries = FrameEntries::Buffered(items.into_iter());
+    }
+}
+
+const DEFAULT_MAX_OPEN_DIRS: usize = 32;
+
+type PruneIf<E> = Box<dyn FnMut(&E) -> bool>;
+type Compare<E> = Box<dyn FnMut(&E, &E) -> Ordering>;
+
+// Turn a directory entry into a walk entry (following it if appropriate).
+fn walk_entry<F: FsProvider>(
+    fs: &F,
+    mut entry: F::DirEntry,
+    depth: usize,
+    follow_symlinks: bool,
+) -> WalkEntry<F::DirEntry> {
+    // dangling links are reported as links
+    let followed = follow_symlinks && entry.is_symlink() && fs.follow_symlink(&mut entry);
+    WalkEntry {
+        entry,
+        depth,
+        followed,
+    }
+}
+
+/// An iterator over all entries below a root directory (by default in
+/// depth first pre-order i.e. a directory is yielded before its contents).
+/// Symbolic links are reported but not followed unless requested.  The
+/// real file system is walked unless another is given to `new_with()`.
+pub struct Walker<F: FsProvider = RealFs> {
+    fs: F,
+    root: PathBuf,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    same_device_only: bool,
+    max_open_dirs: usize,
+    order: TraversalOrder,
+    prune_if: Option<PruneIf<F::DirEntry>>,
+    compare: Option<Compare<F::DirEntry>>,
+    remaining: Option<usize>,
+    // set if the most recently yielded entry added the last item in `pending`
+    yielded_pending: bool,
+    ready: Option<WalkEntry<F::DirEntry>>,
+    pending: VecDeque<Pending<F::DirEntry>>,
+    stack: Vec<Frame<F>>,
+}
+
+impl Walker {
+    pub fn new<P: AsRef<Path>>(root: &P) -> Walker {
+        Walker::new_with(RealFs, root)
+    }
+}
+
+impl<F: FsProvider> Walker<F> {
+    /// A walker over the file system `fs` (which may be a reference).
+    pub fn new_with<P: AsRef<Path>>(fs: F, root: &P) -> Walker<F> {
+        let mut pending = VecDeque::new();
+        pending.push_back(Pending {
+            dir_path: root.as_ref().to_path_buf(),
+            depth: 1,
+            ancestors: vec![],
+            post_entry: None,
+        });
+        Walker {
+            fs,
+            root: root.as_ref().to_path_buf(),
+            max_depth: None,
+            follow_symlinks: false,
+            same_device_only: false,
+            max_open_dirs: DEFAULT_MAX_OPEN_DIRS,
+            order: TraversalOrder::default(),
+            prune_if: None,
+            compare: None,
+            remaining: None,
+            yielded_pending: false,
+            ready: None,
+            pending,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Follow symbolic links to directories.  Links that would lead back
+    /// to one of their own ancestors are reported as `WalkError::SymlinkLoop`
+    /// rather than being followed.  Windows junctions are treated as symbolic
+    /// links (i.e. only followed if this is `true`).
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Don't descend into directories on a different file system to the
+    /// root (e.g. /proc or network mounts).  The mount points themselves
+    /// are still yielded.
+    pub fn same_device_only(mut self, same_device_only: bool) -> Self {
+        self.same_device_only = same_device_only;
+        self
+    }
+
+    /// Limit the number of directory handles that are held open at the
+    /// same time (default 32) so that very deep trees don't exhaust the
+    /// process's file descriptors.  When the limit is reached the remaining
+    /// entries of the outermost open directory are read into memory and its
+    /// handle is closed.  A limit of zero is treated as one.
+    pub fn max_open_dirs(mut self, max_open_dirs: usize) -> Self {
+        self.max_open_dirs = max_open_dirs.max(1);
+        self
+    }
+
+    pub fn order(mut self, order: TraversalOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Don't descend into directories for which `prune_if` returns `true`
+    /// (the directory itself is still yielded).
+    pub fn prune_if<P>(mut self, prune_if: P) -> Self
+    where
+        P: FnMut(&F::DirEntry) -> bool + 'static,
+    {
+        self.prune_if = Some(Box::new(prune_if));
+        self
+    }
+
+    /// Yield the entries of each directory in the order defined by
+    /// `compare` (rather than the order that the operating system returns
+    /// them in).  Any errors reading a directory are reported before its
+    /// entries.
+    pub fn sort_by<C>(mut self, compare: C) -> Self
+    where
+        C: FnMut(&F::DirEntry, &F::DirEntry) -> Ordering + 'static,
+    {
+        self.compare = Some(Box::new(compare));
+        self
+    }
+
+    /// Yield the entries of each directory sorted by file name so that
+    /// the output is reproducible.
+    pub fn sort_by_file_name(self) -> Self {
+        self.sort_by(|a, b| a.name().cmp(&b.name()))
+    }
+
+    /// Yield the entries of each directory sorted by file name in the
+    /// given order.
+    pub fn sort_by_name(self, sort_by: SortBy) -> Self {
+        self.sort_by(move |a, b| {
+            sort_by.compare(&a.name().to_string_lossy(), &b.name().to_string_lossy())
+        })
+    }
+
+    /// Stop after `n` entries (including errors) have been yielded.
+    pub fn stop_after(mut self, n: usize) -> Self {
+        self.remaining = Some(n);
+        self
+    }
+
+    /// Don't descend into the directory most recently yielded.  This has
+    /// no effect in `DepthFirstPost` order.
+    pub fn skip_current_dir(&mut self) {
+        if self.yielded_pending {
+            self.pending.pop_back();
+            self.yielded_pending = false;
+        }
+    }
+
+    /// End the walk: all subsequent calls to `next()` return `None`.
+    pub fn stop(&mut self) {
+        self.ready = None;
+        self.pending.clear();
+        self.stack.clear();
+        self.remaining = Some(0);
+    }
+
+    /// Call `f` for each entry until it returns `ControlFlow::Break` (whose
+    /// value is then returned) or the walk is complete.  The walk is
+    /// abandoned at the first error.
+    pub fn visit<B, V>(self, mut f: V) -> Result<Option<B>, WalkError>
+    where
+        V: FnMut(&WalkEntry<F::DirEntry>) -> ControlFlow<B>,
+    {
+        for entry in self {
+            if let ControlFlow::Break(value) = f(&entry?) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Don't yield (or descend into) anything more than `max_depth` levels
+    /// below the root.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    fn depth_ok(&self, depth: usize) -> bool {
+        match self.max_depth {
+            Some(max_depth) => depth <= max_depth,
+            None => true,
+        }
+    }
+
+    // Make room for one more open directory handle by buffering the
+    // outermost open frame's remaining entries if necessary.
+    fn limit_open_dirs(&mut self) {
+        let open_count = self.stack.iter().filter(|frame| frame.is_open()).count();
+        if open_count + 1 > self.max_open_dirs {
+            let follow_symlinks = self.follow_symlinks;
+            if let Some(frame) = self.stack.iter_mut().find(|frame| frame.is_open()) {
+                frame.buffer(&self.fs, follow_symlinks);
+            }
+        }
+    }
+
+    fn device_ok(&self, dir_path: &Path) -> bool {
+        // if we can't tell then we don't cross
+        !self.same_device_only
+            || self
+                .fs
+                .same_filesystem(&self.root, dir_path)
+                .unwrap_or(false)
+    }
+
+    // Open the directory and make it the current frame.  On failure the
+    // directory's own entry (if being held back) is made ready to yield.
+    fn open(&mut self, pending: Pending<F::DirEntry>) -> Result<(), WalkError> {
+        let Pending {
+            dir_path,
+            depth,
+            ancestors,
+            post_entry,
+        } = pending;
+        let mut lineage = ancestors;
+        if self.follow_symlinks {
+            match self.fs.file_id(&dir_path) {
+                Ok(id) => {
+                    if lineage.contains(&id) {
+                        self.ready = post_entry;
+                        return Err(WalkError::SymlinkLoop(dir_path));
+                    }
+                    lineage.push(id);
+                }
+                Err(err) => {
+                    self.ready = post_entry;
+                    return Err(WalkError::Io(dir_path, err));
+                }
+            }
+        }
+        match self.fs.open_dir(&dir_path) {
+            Ok(read_dir) => {
+                let mut frame = Frame {
+                    dir_path,
+                    entries: FrameEntries::Open(Box::new(read_dir)),
+                    depth,
+                    lineage,
+                    post_entry,
+                };
+                if let Some(ref mut compare) = self.compare {
+                    // sorting requires all of the entries up front so no
+                    // handle is kept open
+                    let mut items = Vec::new();
+                    let mut entries = Vec::new();
+                    while let Some(item) = frame.next_entry(&self.fs, self.follow_symlinks) {
+                        match item {
+                            Ok(entry) => entries.push(entry),
+                            Err(err) => items.push(Err(err)),
+                        }
+                    }
+                    entries.sort_by(|a, b| compare(&a.entry, &b.entry));
+                    items.extend(entries.into_iter().map(Ok));
+                    frame.entries = FrameEntries::Buffered(items.into_iter());
+                } else {
+                    self.limit_open_dirs();
+                }
+                self.stack.push(frame);
+                Ok(())
+            }
+            Err(err) => {
+                self.ready = post_entry;
+                Err(WalkError::Io(dir_path, err))
+            }
+        }
+    }
+
+    fn next_item(&mut self) -> Option<Item<F>> {
+        self.yielded_pending = false;
+        if let Some(entry) = self.ready.take() {
+            return Some(Ok(entry));
+        }
+        loop {
+            // Directories are opened lazily so that any error opening them is
+            // reported after the directory's own entry has been yielded.
+            let open_next = match self.order {
+                TraversalOrder::BreadthFirst => self.stack.is_empty(),
+                _ => true,
+            };
+            if open_next {
+                if let Some(pending) = self.pending.pop_front() {
+                    if let Err(err) = self.open(pending) {
+                        return Some(Err(err));
+                    }
+                }
+            }
+            let follow_symlinks = self.follow_symlinks;
+            let (entry, lineage) = match self.stack.last_mut() {
+                Some(frame) => match frame.next_entry(&self.fs, follow_symlinks) {
+                    Some(Ok(entry)) => (entry, frame.lineage.clone()),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {
+                        if let Some(frame) = self.stack.pop() {
+                            if let Some(entry) = frame.post_entry {
+                                return Some(Ok(entry));
+                            }
+                        }
+                        continue;
+                    }
+                },
+                None => return None,
+            };
+            let depth = entry.depth;
+            let descend =
+                entry.is_dir() && self.depth_ok(depth + 1) && self.device_ok(&entry.path()) && {
+                    match self.prune_if {
+                        Some(ref mut prune_if) => !prune_if(&entry),
+                        None => true,
+                    }
+                };
+            if !descend {
+                return Some(Ok(entry));
+            }
+            let mut pending = Pending {
+                dir_path: entry.path(),
+                depth: depth + 1,
+                ancestors: lineage,
+                post_entry: None,
+            };
+            if self.order == TraversalOrder::DepthFirstPost {
+                pending.post_entry = Some(entry);
+                self.pending.push_back(pending);
+                continue;
+            }
+            self.pending.push_back(pending);
+            self.yielded_pending = true;
+            return Some(Ok(entry));
+        }
+    }
+}
+
+impl<F: FsProvider> Iterator for Walker<F> {
+    type Item = Item<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(remaining) = self.remaining {
+            if remaining == 0 {
+                return None;
+            }
+            self.remaining = Some(remaining - 1);
+        }
+        self.next_item()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs::File;
+
+    #[test]
+    fn walker_visits_everything() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        File::create(root.join("a/b/c.txt")).unwrap();
+        File::create(root.join("d.txt")).unwrap();
+
+        let mut found: Vec<(PathBuf, usize)> = Walker::new(&root)
+            .map(|e| {
+                let e = e.unwrap();
+                (
+                    e.path().strip_prefix(root).unwrap().to_path_buf(),
+                    e.depth(),
+                )
+            })
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (PathBuf::from("a"), 1),
+                (PathBuf::from("a/b"), 2),
+                (PathBuf::from("a/b/c.txt"), 3),
+                (PathBuf::from("d.txt"), 1),
+            ]
+        );
+
+        let count = Walker::new(&root).max_depth(1).count();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn walker_pruning_and_stopping_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        File::create(root.join("src/lib.rs")).unwrap();
+
+        let mut names: Vec<String> = Walker::new(&root)
+            .prune_if(|e| e.file_name() == "target")
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["lib.rs", "src", "target"]);
+
+        assert_eq!(Walker::new(&root).stop_after(2).count(), 2);
+
+        let mut walker = Walker::new(&root);
+        let mut count = 0;
+        while let Some(entry) = walker.next() {
+            count += 1;
+            if entry.unwrap().is_dir() {
+                walker.skip_current_dir();
+            }
+        }
+        assert_eq!(count, 2);
+
+        let found = Walker::new(&root)
+            .visit(|e| {
+                if e.file_name() == "lib.rs" {
+                    ControlFlow::Break(e.path())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert_eq!(found, Some(root.join("src/lib.rs")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walker_detects_symlink_loops() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        File::create(root.join("a/b/file")).unwrap();
+        symlink("../..", root.join("a/b/up")).unwrap();
+        symlink("a/b", root.join("link_to_b")).unwrap();
+
+        assert_eq!(Walker::new(&root).filter(|e| e.is_err()).count(), 0);
+
+        let mut loops = 0;
+        let mut files = 0;
+        for entry in Walker::new(&root).follow_symlinks(true) {
+            match entry {
+                Ok(entry) => {
+                    if entry.is_file() {
+                        files += 1;
+                    }
+                }
+                Err(WalkError::SymlinkLoop(_)) => loops += 1,
+                Err(err) => panic!("unexpected error: {}", err),
+            }
+        }
+        assert_eq!(files, 2);
+        assert_eq!(loops, 2);
+    }
+
+    #[test]
+    fn walker_orders_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        File::create(root.join("a/b/c")).unwrap();
+        File::create(root.join("a/d")).unwrap();
+
+        let names = |order| -> Vec<(String, usize)> {
+            Walker::new(&root)
+                .order(order)
+                .map(|e| {
+                    let e = e.unwrap();
+                    (e.file_name(), e.depth())
+                })
+                .collect()
+        };
+        let pre = names(TraversalOrder::DepthFirstPre);
+        assert_eq!(pre.len(), 4);
+        assert_eq!(pre[0].0, "a");
+        let pos = |v: &Vec<(String, usize)>, name: &str| v.iter().position(|e| e.0 == name);
+        assert!(pos(&pre, "b") < pos(&pre, "c"));
+
+        let post = names(TraversalOrder::DepthFirstPost);
+        assert_eq!(post.len(), 4);
+        assert_eq!(post[3].0, "a");
+        assert!(pos(&post, "c") < pos(&post, "b"));
+
+        let breadth = names(TraversalOrder::BreadthFirst);
+        let depths: Vec<usize> = breadth.iter().map(|e| e.1).collect();
+        assert_eq!(depths, vec![1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn sorted_walker_works() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        for name in &["b", "c", "a"] {
+            fs::create_dir(root.join(name)).unwrap();
+            File::create(root.join(name).join("2")).unwrap();
+            File::create(root.join(name).join("1")).unwrap();
+        }
+        let paths: Vec<PathBuf> = Walker::new(&root)
+            .sort_by_file_name()
+            .map(|e| e.unwrap().path().strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        let expected: Vec<PathBuf> = ["a", "a/1", "a/2", "b", "b/1", "b/2", "c", "c/1", "c/2"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(paths, expected);
+
+        let names: Vec<String> = Walker::new(&root)
+            .max_depth(1)
+            .sort_by(|a, b| b.file_name().cmp(&a.file_name()))
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["c", "b", "a"]);
+
+        File::create(root.join("10")).unwrap();
+        File::create(root.join("9")).unwrap();
+        let names: Vec<String> = Walker::new(&root)
+            .max_depth(1)
+            .sort_by_name(SortBy::Natural)
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["9", "10", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn walker_stays_on_device() {
+        use fs_provider::MemFs;
+
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/root/mnt/sub")).unwrap();
+        fs.create_dir_all(Path::new("/root/dir")).unwrap();
+        fs.write(Path::new("/root/mnt/file"), b"").unwrap();
+        fs.write(Path::new("/root/dir/file"), b"").unwrap();
+        fs.set_device(&"/root/mnt", 1).unwrap();
+        let paths: Vec<PathBuf> = Walker::new_with(&fs, &"/root")
+            .same_device_only(true)
+            .sort_by_file_name()
+            .map(|e| e.unwrap().path().to_path_buf())
+            .collect();
+        // the mount point itself is still yielded
+        let expected: Vec<PathBuf> = ["/root/dir", "/root/dir/file", "/root/mnt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(paths, expected);
+        let count = Walker::new_with(&fs, &"/root").count();
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn walker_limits_open_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let mut dir_path = root.to_path_buf();
+        for i in 0..10 {
+            File::create(dir_path.join("file")).unwrap();
+            dir_path.push(i.to_string());
+            fs::create_dir(&dir_path).unwrap();
+        }
+        let expected: Vec<PathBuf> = Walker::new(&root)
+            .sort_by_file_name()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(expected.len(), 20);
+        let mut walker = Walker::new(&root).max_open_dirs(2);
+        let mut paths = Vec::new();
+        while let Some(entry) = walker.next() {
+            assert!(walker.stack.iter().filter(|f| f.is_open()).count() <= 2);
+            paths.push(entry.unwrap().path());
+        }
+        paths.sort();
+        let mut expected = expected;
+        expected.sort();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn walker_walks_other_file_systems() {
+        use fs_provider::MemFs;
+
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/r/b/c")).unwrap();
+        fs.create_dir(Path::new("/r/a")).unwrap();
+        fs.write(Path::new("/r/b/file"), b"").unwrap();
+        fs.symlink(Path::new("../b"), Path::new("/r/a/link"))
+            .unwrap();
+        let paths: Vec<PathBuf> = Walker::new_with(&fs, &"/r")
+            .sort_by_file_name()
+            .map(|e| e.unwrap().path().to_path_buf())
+            .collect();
+        let expected: Vec<PathBuf> = ["/r/a", "/r/a/link", "/r/b", "/r/b/c", "/r/b/file"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(paths, expected);
+        assert_eq!(Walker::new_with(&fs, &"/r").max_depth(1).count(), 2);
+        let followed = Walker::new_with(&fs, &"/r")
+            .follow_symlinks(true)
+            .filter(|e| e.as_ref().unwrap().is_followed_symlink())
+            .count();
+        assert_eq!(followed, 1);
+        assert_eq!(
+            Walker::new_with(&fs, &"/r").follow_symlinks(true).count(),
+            7
+        );
+        assert!(Walker::new_with(&fs, &"/nowhere").next().unwrap().is_err());
+    }
+
+    #[test]
+    fn walker_reports_bad_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut walker = Walker::new(&temp_dir.path().join("nonexistent"));
+        assert!(walker.next().unwrap().is_err());
+        assert!(walker.next().is_none());
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2019 Peter Williams <pwil3058@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module to provide access to the extended attributes (e.g. SELinux
+//! labels and `user.*` metadata) of files on Unix like systems.
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::Path;
+
+/// Get the value of the extended attribute `name` of the file `path` or
+/// `None` if it doesn't have one.
+pub fn get_xattr<P, N>(path: &P, name: &N) -> io::Result<Option<Vec<u8>>>
+where
+    P: AsRef<Path>,
+    N: AsRef<OsStr>,
+{
+    imp::get_xattr(path.as_ref(), name.as_ref())
+}
+
+/// Set the extended attribute `name` of the file `path` to `value`.
+pub fn set_xattr<P, N>(path: &P, name: &N, value: &[u8]) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    N: AsRef<OsStr>,
+{
+    imp::set_xattr(path.as_ref(), name.as_ref(), value)
+}
+
+/// Remove the extended attribute `name` from the file `path`.
+pub fn remove_xattr<P, N>(path: &P, name: &N) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    N: AsRef<OsStr>,
+{
+    imp::remove_xattr(path.as_ref(), name.as_ref())
+}
+
+/// List the names of the extended attributes of the file `path`.
+pub fn list_xattrs<P: AsRef<Path>>(path: &P) -> io::Result<Vec<OsString>> {
+    imp::list_xattrs(path.as_ref())
+}
+
+/// Give the file `to` copies of all of the extended attributes of the file
+/// `from` (that the caller is allowed to read and set).
+pub fn copy_xattrs<F, T>(from: &F, to: &T) -> io::Result<()>
+where
+    F: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    for name in list_xattrs(from)? {
+        if let Some(value) = get_xattr(from, &name)? {
+            set_xattr(to, &name, &value)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+mod imp {
+    use std::ffi::{CString, OsStr, OsString};
+    use std::io;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+
+    use libc::{self, c_char, c_void};
+
+    use sys::c_path;
+
+    #[cfg(target_os = "macos")]
+    const NO_ATTRIBUTE: i32 = libc::ENOATTR;
+    #[cfg(not(target_os = "macos"))]
+    const NO_ATTRIBUTE: i32 = libc::ENODATA;
+
+    fn c_name(name: &OsStr) -> io::Result<CString> {
+        CString::new(name.as_bytes()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "attribute name contains a nul byte",
+            )
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn getxattr(path: &CString, name: &CString, buf: *mut c_void, size: usize) -> isize {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size, 0, 0)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    unsafe fn getxattr(path: &CString, name: &CString, buf: *mut c_void, size: usize) -> isize {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn listxattr(path: &CString, buf: *mut c_char, size: usize) -> isize {
+        libc::listxattr(path.as_ptr(), buf, size, 0)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    unsafe fn listxattr(path: &CString, buf: *mut c_char, size: usize) -> isize {
+        libc::listxattr(path.as_ptr(), buf, size)
+    }
+
+    // Call `get` with a buffer big enough for the data (which may grow
+    // between asking for the size and getting it).
+    fn fetch<G: Fn(*mut u8, usize) -> isize>(get: G) -> io::Result<Vec<u8>> {
+        loop {
+            let size = get(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buf = vec![0u8; size as usize];
+            let size = get(buf.as_mut_ptr(), buf.len());
+            if size >= 0 {
+                buf.truncate(size as usize);
+                return Ok(buf);
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(err);
+            }
+        }
+    }
+
+    pub fn get_xattr(path: &Path, name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+        let c_path = c_path(path)?;
+        let c_name = c_name(name)?;
+        match fetch(|buf, size| unsafe { getxattr(&c_path, &c_name, buf as *mut c_void, size) }) {
+            Ok(value) => Ok(Some(value)),
+            Err(ref err) if err.raw_os_error() == Some(NO_ATTRIBUTE) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn set_xattr(path: &Path, name: &OsStr, value: &[u8]) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        let c_name = c_name(name)?;
+        let ptr = value.as_ptr() as *const c_void;
+        #[cfg(target_os = "macos")]
+        let result =
+            unsafe { libc::setxattr(c_path.as_ptr(), c_name.as_ptr(), ptr, value.len(), 0, 0) };
+        #[cfg(not(target_os = "macos"))]
+        let result =
+            unsafe { libc::setxattr(c_path.as_ptr(), c_name.as_ptr(), ptr, value.len(), 0) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub fn remove_xattr(path: &Path, name: &OsStr) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        let c_name = c_name(name)?;
+        #[cfg(target_os = "macos")]
+        let result = unsafe { libc::removexattr(c_path.as_ptr(), c_name.as_ptr(), 0) };
+        #[cfg(not(target_os = "macos"))]
+        let result = unsafe { libc::removexattr(c_path.as_ptr(), c_name.as_ptr()) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub fn list_xattrs(path: &Path) -> io::Result<Vec<OsString>> {
+        let c_path = c_path(path)?;
+        let list = fetch(|buf, size| unsafe { listxattr(&c_path, buf as *mut c_char, size) })?;
+        Ok(list
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| OsString::from_vec(name.to_vec()))
+            .collect())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+mod imp {
+    use std::ffi::{OsStr, OsString};
+    use std::io;
+    use std::path::Path;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "extended attributes are not supported on this platform",
+        )
+    }
+
+    pub fn get_xattr(_path: &Path, _name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+        Err(unsupported())
+    }
+
+    pub fn set_xattr(_path: &Path, _name: &OsStr, _value: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn remove_xattr(_path: &Path, _name: &OsStr) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn list_xattrs(_path: &Path) -> io::Result<Vec<OsString>> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate tempfile;
+
+    use std::fs;
+
+    #[test]
+    fn xattrs_work() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "contents").unwrap();
+        match set_xattr(&file, &"user.pathux", b"value") {
+            Ok(()) => (),
+            // the file system (e.g. tmpfs on older kernels) may not allow it
+            Err(_) => return,
+        }
+        assert_eq!(
+            get_xattr(&file, &"user.pathux").unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(get_xattr(&file, &"user.missing").unwrap(), None);
+        assert!(list_xattrs(&file)
+            .unwrap()
+            .contains(&OsString::from("user.pathux")));
+
+        let copy = temp_dir.path().join("copy");
+        fs::write(&copy, "contents").unwrap();
+        copy_xattrs(&file, &copy).unwrap();
+        assert_eq!(
+            get_xattr(&copy, &"user.pathux").unwrap(),
+            Some(b"value".to_vec())
+        );
+        remove_xattr(&file, &"user.pathux").unwrap();
+        assert_eq!(get_xattr(&file, &"user.pathux").unwrap(), None);
+        assert!(get_xattr(&temp_dir.path().join("missing"), &"user.pathux").is_err());
+    }
+}